@@ -0,0 +1,102 @@
+use serde::{Serialize, Serializer};
+
+/// Codes the gateway closes a connection with. Each code tells the client
+/// whether it is worth reconnecting, or whether it would be closed again for
+/// the same reason.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[repr(u16)]
+pub enum CloseCode {
+	/// Something went wrong on the server, try reconnecting.
+	UnknownError = 4000,
+	/// The client sent an opcode the gateway does not accept.
+	UnknownOpcode = 4001,
+	/// The client sent a payload which could not be decoded.
+	DecodeError = 4002,
+	/// The client sent a command before identifying.
+	NotAuthenticated = 4003,
+	/// The token sent with Identify is invalid.
+	AuthenticationFailed = 4004,
+	/// The client sent more than one Identify.
+	AlreadyAuthenticated = 4005,
+	/// The client sent a payload larger than the gateway accepts.
+	PayloadTooLarge = 4006,
+	/// The sequence sent by the client is invalid.
+	InvalidSeq = 4007,
+	/// The client sent commands too quickly.
+	RateLimited = 4008,
+	/// The client did not identify or heartbeat in time.
+	SessionTimedOut = 4009,
+	/// The client sent invalid intents.
+	InvalidIntents = 4013,
+	/// A bot sent privileged intents it is not allowed to use.
+	DisallowedIntents = 4014,
+}
+
+impl CloseCode {
+	/// Every close code, in the order they are documented.
+	pub const ALL: [CloseCode; 12] = [
+		CloseCode::UnknownError,
+		CloseCode::UnknownOpcode,
+		CloseCode::DecodeError,
+		CloseCode::NotAuthenticated,
+		CloseCode::AuthenticationFailed,
+		CloseCode::AlreadyAuthenticated,
+		CloseCode::PayloadTooLarge,
+		CloseCode::InvalidSeq,
+		CloseCode::RateLimited,
+		CloseCode::SessionTimedOut,
+		CloseCode::InvalidIntents,
+		CloseCode::DisallowedIntents,
+	];
+
+	pub fn description(&self) -> &'static str {
+		match self {
+			CloseCode::UnknownError => "Unknown error",
+			CloseCode::UnknownOpcode => "Unknown opcode",
+			CloseCode::DecodeError => "Decode error",
+			CloseCode::NotAuthenticated => "Not authenticated",
+			CloseCode::AuthenticationFailed => "Authentication failed",
+			CloseCode::AlreadyAuthenticated => "Already authenticated",
+			CloseCode::PayloadTooLarge => "Payload too large",
+			CloseCode::InvalidSeq => "Invalid seq",
+			CloseCode::RateLimited => "Rate limited",
+			CloseCode::SessionTimedOut => "Session timed out",
+			CloseCode::InvalidIntents => "Invalid intents",
+			CloseCode::DisallowedIntents => "Disallowed intents",
+		}
+	}
+
+	/// Whether the client should reconnect after being closed with this code.
+	/// Codes caused by a bad token or a bad Identify will keep failing until
+	/// the client changes what it sends.
+	pub fn reconnect(&self) -> bool {
+		!matches!(
+			self,
+			CloseCode::AuthenticationFailed
+				| CloseCode::InvalidIntents
+				| CloseCode::DisallowedIntents
+		)
+	}
+}
+
+impl Serialize for CloseCode {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		serializer.serialize_u16(*self as u16)
+	}
+}
+
+impl TryFrom<u16> for CloseCode {
+	type Error = u16;
+
+	/// Finds the close code sent as `code`, or gives it back if it is not a
+	/// code of the gateway, such as a close code of the WebSocket protocol.
+	fn try_from(code: u16) -> Result<Self, Self::Error> {
+		CloseCode::ALL
+			.into_iter()
+			.find(|close_code| *close_code as u16 == code)
+			.ok_or(code)
+	}
+}
@@ -0,0 +1,216 @@
+use crate::{events::Opcode, intents::Intents, model::Status};
+use derives::GatewayCommand;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "validate")]
+use validator::{Validate, ValidationErrors};
+
+// This file contains all the commands that can be sent from the client to the server.
+
+/// Properties of the client's connection, sent with Identify.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[cfg_attr(feature = "validate", derive(Validate))]
+#[serde(deny_unknown_fields)]
+pub struct ConnectionProperties {
+	/// The client's operating system
+	#[cfg_attr(feature = "validate", validate(length(max = 64)))]
+	pub os: Option<String>,
+	/// The client's browser, or the name of the library it uses
+	#[cfg_attr(feature = "validate", validate(length(max = 64)))]
+	pub browser: Option<String>,
+	/// The client's device
+	#[cfg_attr(feature = "validate", validate(length(max = 64)))]
+	pub device: Option<String>,
+}
+
+/// Sent by the client to authenticate the session.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "validate", derive(Validate))]
+#[serde(deny_unknown_fields)]
+pub struct Identify {
+	/// The user's authentication token
+	#[cfg_attr(feature = "validate", validate(length(min = 1, max = 128)))]
+	pub token: String,
+	#[serde(default)]
+	#[cfg_attr(feature = "validate", validate)]
+	pub properties: ConnectionProperties,
+	/// Events the session subscribes to, every event unless specified
+	#[serde(default)]
+	pub intents: Intents,
+	/// Whether payloads should be compressed with zlib-stream, if no
+	/// transport compression was asked for when connecting
+	#[serde(default)]
+	pub compress: bool,
+}
+
+impl Identify {
+	/// Identifies with the token, subscribing to every event.
+	pub fn new(token: &str) -> Self {
+		Identify {
+			token: token.to_string(),
+			properties: ConnectionProperties {
+				os: Some(std::env::consts::OS.to_string()),
+				browser: Some("shiki-client".to_string()),
+				device: Some("shiki-client".to_string()),
+			},
+			intents: Intents::default(),
+			compress: false,
+		}
+	}
+}
+
+/// Sent by the client to resume a session it lost the connection to.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "validate", derive(Validate))]
+#[serde(deny_unknown_fields)]
+pub struct Resume {
+	/// The user's authentication token
+	#[cfg_attr(feature = "validate", validate(length(min = 1, max = 128)))]
+	pub token: String,
+	/// ID of the session to resume
+	pub session_id: usize,
+	/// The last sequence the client received
+	pub seq: u64,
+}
+
+/// Sent by the client to change the user's status.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "validate", derive(Validate))]
+#[serde(deny_unknown_fields)]
+pub struct PresenceUpdate {
+	pub status: Status,
+	/// Custom status text
+	#[cfg_attr(
+		feature = "validate",
+		validate(length(max = 128), non_control_character)
+	)]
+	pub custom_status: Option<String>,
+}
+
+fn default_member_limit() -> i64 {
+	100
+}
+
+/// Members of a guild requested by a client, sent back as GuildMembersChunk
+/// events.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "validate", derive(Validate))]
+#[serde(deny_unknown_fields)]
+pub struct RequestGuildMembers {
+	/// Guild ID, or null for every user
+	pub guild_id: Option<i64>,
+	/// Only returns members whose username starts with this
+	#[cfg_attr(feature = "validate", validate(length(min = 1, max = 32)))]
+	pub query: Option<String>,
+	/// Only returns these members
+	#[cfg_attr(feature = "validate", validate(length(max = 100)))]
+	pub user_ids: Option<Vec<i64>>,
+	/// Maximum number of members to return
+	#[serde(default = "default_member_limit")]
+	#[cfg_attr(feature = "validate", validate(range(min = 1, max = 1000)))]
+	pub limit: i64,
+	/// Only returns members with a greater ID, to page through them
+	#[serde(default)]
+	pub after: i64,
+	/// Whether to send the presences of the members
+	#[serde(default)]
+	pub presences: bool,
+	/// Sent back with every chunk
+	#[cfg_attr(feature = "validate", validate(length(max = 32)))]
+	pub nonce: Option<String>,
+}
+
+impl Default for RequestGuildMembers {
+	fn default() -> Self {
+		RequestGuildMembers {
+			guild_id: None,
+			query: None,
+			user_ids: None,
+			limit: default_member_limit(),
+			after: 0,
+			presences: false,
+			nonce: None,
+		}
+	}
+}
+
+/// Subscribes a session to the member list of a guild, replacing any previous
+/// subscription.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SubscribeMemberList {
+	/// Guild ID, or null for every user
+	pub guild_id: Option<i64>,
+}
+
+/// Sent by the client to join, move between or leave voice channels.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "validate", derive(Validate))]
+#[serde(deny_unknown_fields)]
+pub struct VoiceStateUpdate {
+	/// The voice channel to join, or null to leave
+	pub channel_id: Option<i64>,
+	/// Whether the user muted themselves
+	#[serde(default)]
+	pub self_mute: bool,
+	/// Whether the user deafened themselves
+	#[serde(default)]
+	pub self_deaf: bool,
+}
+
+/// Sent by the client to show the user as typing in a channel.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "validate", derive(Validate))]
+#[serde(deny_unknown_fields)]
+pub struct TypingStart {
+	pub channel_id: i64,
+}
+
+#[derive(Debug, Clone, GatewayCommand)]
+pub enum GatewayCommand {
+	#[opcode(value = "Opcode::Identify", code = 0)]
+	Identify(Identify),
+	/// The last sequence the client received, or null if it has not received
+	/// any events yet.
+	#[opcode(value = "Opcode::Heartbeat", code = 19)]
+	Heartbeat(Option<u64>),
+	#[opcode(value = "Opcode::Resume", code = 25)]
+	Resume(Resume),
+	#[opcode(value = "Opcode::PresenceUpdate", code = 8)]
+	PresenceUpdate(PresenceUpdate),
+	#[opcode(value = "Opcode::RequestGuildMembers", code = 21)]
+	RequestGuildMembers(RequestGuildMembers),
+	#[opcode(value = "Opcode::MemberListSubscribe", code = 23)]
+	MemberListSubscribe(SubscribeMemberList),
+	#[opcode(value = "Opcode::VoiceStateUpdate", code = 27)]
+	VoiceStateUpdate(VoiceStateUpdate),
+	#[opcode(value = "Opcode::TypingStart", code = 6)]
+	TypingStart(TypingStart),
+}
+
+impl GatewayCommand {
+	/// Whether the command can be sent before the session is identified.
+	pub fn allowed_before_identify(&self) -> bool {
+		matches!(
+			self,
+			GatewayCommand::Identify(_)
+				| GatewayCommand::Heartbeat(_)
+				| GatewayCommand::Resume(_)
+		)
+	}
+}
+
+#[cfg(feature = "validate")]
+impl Validate for GatewayCommand {
+	fn validate(&self) -> Result<(), ValidationErrors> {
+		match self {
+			GatewayCommand::Identify(identify) => identify.validate(),
+			GatewayCommand::Heartbeat(_) => Ok(()),
+			GatewayCommand::Resume(resume) => resume.validate(),
+			GatewayCommand::PresenceUpdate(presence) => presence.validate(),
+			GatewayCommand::RequestGuildMembers(request) => request.validate(),
+			GatewayCommand::MemberListSubscribe(_) => Ok(()),
+			GatewayCommand::VoiceStateUpdate(voice) => voice.validate(),
+			GatewayCommand::TypingStart(typing) => typing.validate(),
+		}
+	}
+}
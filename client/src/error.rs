@@ -0,0 +1,67 @@
+use reqwest::StatusCode;
+use std::fmt;
+use tokio_tungstenite::tungstenite;
+
+/// Errors returned by the REST and gateway clients.
+#[derive(Debug)]
+pub enum Error {
+	/// The request could not be sent, or its response could not be read
+	Http(reqwest::Error),
+	/// The server answered with an error, its body says why
+	Status { status: StatusCode, body: String },
+	/// The gateway connection failed
+	WebSocket(tungstenite::Error),
+	/// A payload could not be encoded or decoded
+	Json(serde_json::Error),
+	/// The gateway closed the connection with a code the client would be
+	/// closed with again if it reconnected, such as an invalid token
+	Closed { code: u16, reason: String },
+	/// The gateway sent something else than Hello when connecting
+	UnexpectedPayload,
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Error::Http(err) => write!(f, "HTTP error: {}", err),
+			Error::Status { status, body } => write!(f, "{}: {}", status, body),
+			Error::WebSocket(err) => write!(f, "WebSocket error: {}", err),
+			Error::Json(err) => write!(f, "Invalid payload: {}", err),
+			Error::Closed { code, reason } => {
+				write!(f, "Gateway closed with {}: {}", code, reason)
+			}
+			Error::UnexpectedPayload => {
+				write!(f, "Gateway did not start with Hello")
+			}
+		}
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Error::Http(err) => Some(err),
+			Error::WebSocket(err) => Some(err),
+			Error::Json(err) => Some(err),
+			_ => None,
+		}
+	}
+}
+
+impl From<reqwest::Error> for Error {
+	fn from(err: reqwest::Error) -> Self {
+		Error::Http(err)
+	}
+}
+
+impl From<tungstenite::Error> for Error {
+	fn from(err: tungstenite::Error) -> Self {
+		Error::WebSocket(err)
+	}
+}
+
+impl From<serde_json::Error> for Error {
+	fn from(err: serde_json::Error) -> Self {
+		Error::Json(err)
+	}
+}
@@ -0,0 +1,417 @@
+use crate::model::{
+	Channel, ChannelType, Guild, Presence, ReadState, Snowflakes, Status, User,
+};
+use derives::HasOpcode;
+use serde::{Deserialize, Serialize, Serializer};
+
+// This file contains all the events that can be sent from the server to the client.
+
+pub trait HasOpcode {
+	/// Number the opcode is sent as
+	const CODE: u8;
+
+	fn opcode() -> Opcode;
+}
+
+// Opcode enum used for sending and receiving many of the Gateway events similar to the Discord Gateway.
+#[derive(Deserialize, Debug, PartialEq, Eq, Copy, Clone)]
+#[repr(u8)]
+pub enum Opcode {
+	Identify = 0,
+	Ready = 1,
+	MessageCreate = 2,
+	ChannelCreate = 3,
+	ChannelUpdate = 4,
+	ChannelDelete = 5,
+	TypingStart = 6,
+	TypingStop = 7,
+	PresenceUpdate = 8,
+	MessageAck = 9,
+	ChannelPinsUpdate = 10,
+	ThreadCreate = 11,
+	ThreadUpdate = 12,
+	GuildMemberAdd = 13,
+	GuildMemberRemove = 14,
+	GuildMemberUpdate = 15,
+	GuildBanAdd = 16,
+	GuildBanRemove = 17,
+	Hello = 18,
+	Heartbeat = 19,
+	HeartbeatAck = 20,
+	RequestGuildMembers = 21,
+	GuildMembersChunk = 22,
+	MemberListSubscribe = 23,
+	MemberListUpdate = 24,
+	Resume = 25,
+	InvalidSession = 26,
+	VoiceStateUpdate = 27,
+	/// Internal messages between the server and sessions, never sent as is.
+	Custom = 255,
+}
+
+impl Serialize for Opcode {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		serializer.serialize_u8(*self as u8)
+	}
+}
+
+/// Identify Payload which is sent from the server to the client. This will contain a lot of data, like the guilds they are in, channels, etc. To be used as the entry point/hello world of the application.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::Ready", code = 1)]
+pub struct Ready {
+	/// ID of the session, sent back in Resume to resume it after a
+	/// disconnect.
+	pub session_id: usize,
+	/// List of available channels, in the guilds the user is a member of and
+	/// outside of any guild.
+	pub channels: Vec<Channel>,
+	/// The guilds the user is a member of.
+	pub guilds: Vec<Guild>,
+	/// The user who connected
+	pub user: User,
+	/// The user who connected and a few of the users who are online. Everyone
+	/// else is requested with RequestGuildMembers.
+	pub users: Vec<User>,
+	/// Presences of the users who are online, including the user who connected.
+	pub presences: Vec<Presence>,
+	/// How much of each channel the user has read.
+	pub read_states: Vec<ReadState>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::MessageCreate", code = 2)]
+pub struct MessageCreate {
+	/// The id of the message
+	pub id: i64,
+	/// The content of the message
+	pub content: String,
+	/// The ID of the channel.
+	pub channel_id: i64,
+	/// The author of the message.
+	pub author: User,
+	/// The creation date of the message
+	pub created_at: usize,
+	/// The ids of the users mentioned in the message
+	pub mentions: Snowflakes,
+	/// The ids of the roles mentioned in the message
+	pub mention_roles: Snowflakes,
+	/// Whether the message mentions everyone
+	pub mention_everyone: bool,
+	/// Whether the receiving user is notified by the message, either directly,
+	/// through one of their roles or through `@everyone`.
+	pub mentioned: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::ChannelCreate", code = 3)]
+pub struct ChannelCreate {
+	/// The id of the channel
+	pub id: i64,
+	/// The name of the channel
+	pub name: String,
+	/// The topic of the channel
+	pub description: Option<String>,
+	/// The type of the channel
+	#[serde(rename = "type")]
+	pub kind: ChannelType,
+	/// The sorting position of the channel
+	pub position: i64,
+	/// The id of the category the channel is in
+	pub parent_id: Option<i64>,
+}
+
+/// Sent when a channel is renamed, moved or has its topic changed. Contains the
+/// whole updated channel.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::ChannelUpdate", code = 4)]
+pub struct ChannelUpdate {
+	/// The id of the channel
+	pub id: i64,
+	/// The name of the channel
+	pub name: String,
+	/// The topic of the channel
+	pub description: Option<String>,
+	/// The type of the channel
+	#[serde(rename = "type")]
+	pub kind: ChannelType,
+	/// The sorting position of the channel
+	pub position: i64,
+	/// The id of the category the channel is in
+	pub parent_id: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::ChannelDelete", code = 5)]
+pub struct ChannelDelete {
+	/// The id of the channel
+	pub id: i64,
+	/// The id of the category the channel was in
+	pub parent_id: Option<i64>,
+}
+
+impl ChannelDelete {
+	pub fn new(id: i64, parent_id: Option<i64>) -> Self {
+		Self { id, parent_id }
+	}
+}
+
+/// Sent when a thread is started from a message.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::ThreadCreate", code = 11)]
+pub struct ThreadCreate {
+	/// The id of the thread
+	pub id: i64,
+	/// The name of the thread
+	pub name: String,
+	/// The id of the channel the thread was started in
+	pub parent_id: Option<i64>,
+	/// The id of the message the thread was started from
+	pub starter_message_id: Option<i64>,
+	/// The ids of the users who joined the thread
+	pub member_ids: Snowflakes,
+	/// Whether the thread is archived
+	pub archived: bool,
+	/// Minutes of inactivity after which the thread is archived
+	pub auto_archive_duration: u64,
+}
+
+/// Sent when a thread is renamed, archived, unarchived or gains or loses a
+/// member. Contains the whole updated thread.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::ThreadUpdate", code = 12)]
+pub struct ThreadUpdate {
+	/// The id of the thread
+	pub id: i64,
+	/// The name of the thread
+	pub name: String,
+	/// The id of the channel the thread was started in
+	pub parent_id: Option<i64>,
+	/// The id of the message the thread was started from
+	pub starter_message_id: Option<i64>,
+	/// The ids of the users who joined the thread
+	pub member_ids: Snowflakes,
+	/// Whether the thread is archived
+	pub archived: bool,
+	/// Minutes of inactivity after which the thread is archived
+	pub auto_archive_duration: u64,
+}
+
+/// Sent when a user starts typing in a channel. The indicator lasts until the
+/// matching `TypingStop`, or until the user sends a message.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::TypingStart", code = 6)]
+pub struct TypingStart {
+	/// The id of the channel
+	pub channel_id: i64,
+	/// The id of the user who is typing
+	pub user_id: i64,
+	/// Unix timestamp for when the user started typing
+	pub timestamp: usize,
+}
+
+/// Sent when a typing indicator expires without being refreshed.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::TypingStop", code = 7)]
+pub struct TypingStop {
+	/// The id of the channel
+	pub channel_id: i64,
+	/// The id of the user who stopped typing
+	pub user_id: i64,
+}
+
+/// Sent when a user comes online, goes offline or changes their status.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::PresenceUpdate", code = 8)]
+pub struct PresenceUpdate {
+	/// The id of the user
+	pub user_id: i64,
+	/// The new status of the user
+	pub status: Status,
+	/// The custom status of the user
+	pub custom_status: Option<String>,
+}
+
+impl From<Presence> for PresenceUpdate {
+	fn from(presence: Presence) -> Self {
+		Self {
+			user_id: presence.user_id,
+			status: presence.status,
+			custom_status: presence.custom_status,
+		}
+	}
+}
+
+/// Sent to all of a user's sessions when they read a channel on one of them.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::MessageAck", code = 9)]
+pub struct MessageAck {
+	/// The id of the channel
+	pub channel_id: i64,
+	/// The id of the last message the user has read
+	pub message_id: Option<i64>,
+	/// Number of messages sent after the acknowledged one
+	pub unread_count: u64,
+	/// Number of unread messages which mention the user
+	pub mention_count: u64,
+}
+
+impl From<ReadState> for MessageAck {
+	fn from(state: ReadState) -> Self {
+		Self {
+			channel_id: state.channel_id,
+			message_id: state.last_message_id,
+			unread_count: state.unread_count,
+			mention_count: state.mention_count,
+		}
+	}
+}
+
+/// Sent when a message is pinned or unpinned.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::ChannelPinsUpdate", code = 10)]
+pub struct ChannelPinsUpdate {
+	/// The id of the channel
+	pub channel_id: i64,
+	/// The id of the message which was pinned or unpinned
+	pub message_id: i64,
+	/// Whether the message is now pinned
+	pub pinned: bool,
+}
+
+/// Sent when a user joins a guild through an invite.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::GuildMemberAdd", code = 13)]
+pub struct GuildMemberAdd {
+	/// The id of the guild
+	pub guild_id: i64,
+	/// The user who joined
+	pub user: User,
+	/// Unix timestamp for when the user joined
+	pub joined_at: usize,
+}
+
+/// Sent when a user leaves a guild, is kicked or is banned.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::GuildMemberRemove", code = 14)]
+pub struct GuildMemberRemove {
+	/// The id of the guild
+	pub guild_id: i64,
+	/// The id of the user who was removed
+	pub user_id: i64,
+}
+
+/// Sent when a member is timed out or their timeout is lifted.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::GuildMemberUpdate", code = 15)]
+pub struct GuildMemberUpdate {
+	/// The id of the guild
+	pub guild_id: i64,
+	/// The id of the member
+	pub user_id: i64,
+	/// Unix timestamp until which the member may not send messages
+	pub communication_disabled_until: Option<usize>,
+}
+
+/// Sent when a user is banned from a guild. The reason is only shown to
+/// moderators, through the guild's bans and audit log.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::GuildBanAdd", code = 16)]
+pub struct GuildBanAdd {
+	/// The id of the guild
+	pub guild_id: i64,
+	/// The id of the banned user
+	pub user_id: i64,
+}
+
+/// Sent when a user's ban is lifted.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::GuildBanRemove", code = 17)]
+pub struct GuildBanRemove {
+	/// The id of the guild
+	pub guild_id: i64,
+	/// The id of the user
+	pub user_id: i64,
+}
+
+/// Sent to the client as soon as it connects, telling it how often to send a
+/// Heartbeat.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::Hello", code = 18)]
+pub struct Hello {
+	/// Milliseconds between the client's Heartbeats
+	pub heartbeat_interval: u64,
+}
+
+/// Sent in response to a client's Heartbeat.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::HeartbeatAck", code = 20)]
+pub struct HeartbeatAck;
+
+/// Sent in response to RequestGuildMembers. Large responses are split into
+/// several chunks.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::GuildMembersChunk", code = 22)]
+pub struct GuildMembersChunk {
+	/// The id of the guild, or null for every user
+	pub guild_id: Option<i64>,
+	/// The members in this chunk
+	pub members: Vec<User>,
+	/// Presences of the members who are online, if they were requested
+	pub presences: Vec<Presence>,
+	/// Index of this chunk, starting at 0
+	pub chunk_index: usize,
+	/// Number of chunks sent for the request
+	pub chunk_count: usize,
+	/// Requested user ids which are not members
+	pub not_found: Vec<i64>,
+	/// The nonce sent with the request
+	pub nonce: Option<String>,
+}
+
+/// A member shown in a member list, with their status.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MemberListItem {
+	pub user: User,
+	pub status: Status,
+}
+
+/// Sent when a session subscribes to a member list. Online members come
+/// first, changes to their presence follow as PresenceUpdate events.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::MemberListUpdate", code = 24)]
+pub struct MemberListUpdate {
+	/// The id of the guild, or null for every user
+	pub guild_id: Option<i64>,
+	/// Number of members in the guild
+	pub member_count: usize,
+	/// Number of members who are online
+	pub online_count: usize,
+	/// The first members of the list
+	pub members: Vec<MemberListItem>,
+}
+
+/// Sent when a session cannot be resumed. The client has to identify again.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::InvalidSession", code = 26)]
+pub struct InvalidSession {
+	/// Whether the client could resume the session later on
+	pub resumable: bool,
+}
+
+/// Sent when a user joins, leaves or moves between voice channels, or mutes
+/// or deafens themselves.
+#[derive(Serialize, Deserialize, Debug, Clone, HasOpcode)]
+#[opcode(value = "Opcode::VoiceStateUpdate", code = 27)]
+pub struct VoiceStateUpdate {
+	/// The id of the user
+	pub user_id: i64,
+	/// The id of the voice channel, or null if the user left
+	pub channel_id: Option<i64>,
+	/// Whether the user muted themselves
+	pub self_mute: bool,
+	/// Whether the user deafened themselves
+	pub self_deaf: bool,
+}
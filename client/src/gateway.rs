@@ -0,0 +1,317 @@
+use crate::{
+	close_code::CloseCode,
+	commands::{GatewayCommand, Identify, Resume},
+	error::Error,
+	events::{
+		ChannelCreate, ChannelDelete, ChannelPinsUpdate, ChannelUpdate,
+		GuildBanAdd, GuildBanRemove, GuildMemberAdd, GuildMemberRemove,
+		GuildMemberUpdate, GuildMembersChunk, HasOpcode, Hello,
+		MemberListUpdate, MessageAck, MessageCreate, Opcode, PresenceUpdate,
+		Ready, ThreadCreate, ThreadUpdate, TypingStart, TypingStop,
+		VoiceStateUpdate,
+	},
+};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::{
+	net::TcpStream,
+	time::{self, Instant, Interval},
+};
+use tokio_tungstenite::{
+	connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream,
+};
+
+/// Version of the gateway the client speaks, which selects the shape of the
+/// payloads it is sent
+const GATEWAY_VERSION: u8 = 1;
+
+/// How many times in a row the client tries to reconnect before giving up
+const MAX_RECONNECT_ATTEMPTS: u32 = 5;
+
+/// How long the client waits before its first attempt to reconnect, doubled
+/// after every failed attempt
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Envelope of every payload sent by the gateway.
+#[derive(Deserialize)]
+struct RawPayload {
+	op: u8,
+	#[serde(default)]
+	d: serde_json::Value,
+	s: Option<u64>,
+}
+
+macro_rules! gateway_events {
+	($($name:ident),* $(,)?) => {
+		/// An event dispatched by the gateway.
+		#[derive(Debug, Clone)]
+		pub enum GatewayEvent {
+			$($name($name),)*
+			/// An event this version of the client does not know
+			Unknown { op: u8, d: serde_json::Value },
+		}
+
+		impl GatewayEvent {
+			fn decode(
+				op: u8, d: serde_json::Value,
+			) -> serde_json::Result<Self> {
+				$(
+					if op == <$name as HasOpcode>::CODE {
+						return serde_json::from_value(d).map(GatewayEvent::$name);
+					}
+				)*
+
+				Ok(GatewayEvent::Unknown { op, d })
+			}
+		}
+	};
+}
+
+gateway_events!(
+	Ready,
+	MessageCreate,
+	ChannelCreate,
+	ChannelUpdate,
+	ChannelDelete,
+	TypingStart,
+	TypingStop,
+	PresenceUpdate,
+	MessageAck,
+	ChannelPinsUpdate,
+	ThreadCreate,
+	ThreadUpdate,
+	GuildMemberAdd,
+	GuildMemberRemove,
+	GuildMemberUpdate,
+	GuildBanAdd,
+	GuildBanRemove,
+	GuildMembersChunk,
+	MemberListUpdate,
+	VoiceStateUpdate,
+);
+
+/// Connection to the gateway. Heartbeats are sent while waiting for events,
+/// and the connection is resumed, or identified again, when it is lost.
+pub struct Gateway {
+	/// URL of the gateway, such as `wss://shiki.example.com/gateway`
+	url: String,
+	identify: Identify,
+	socket: Socket,
+	heartbeat: Interval,
+	/// Whether the last Heartbeat was acknowledged
+	acked: bool,
+	/// ID of the session, known once Ready was received
+	session_id: Option<usize>,
+	/// Sequence number of the last event received
+	seq: Option<u64>,
+}
+
+impl Gateway {
+	/// Connects to the gateway and identifies with the token.
+	pub async fn connect(url: &str, token: &str) -> Result<Self, Error> {
+		Self::connect_with(url, Identify::new(token)).await
+	}
+
+	/// Connects to the gateway and identifies with `identify`. The version of
+	/// the gateway the client speaks is added to the query of `url`.
+	pub async fn connect_with(
+		url: &str, identify: Identify,
+	) -> Result<Self, Error> {
+		let separator = if url.contains('?') { '&' } else { '?' };
+		let url = format!("{url}{separator}v={GATEWAY_VERSION}");
+		let (socket, heartbeat) = Self::open(&url).await?;
+		let mut gateway = Gateway {
+			url,
+			identify,
+			socket,
+			heartbeat,
+			acked: true,
+			session_id: None,
+			seq: None,
+		};
+
+		gateway.send_identify().await?;
+
+		Ok(gateway)
+	}
+
+	/// ID of the session, once Ready was received.
+	pub fn session_id(&self) -> Option<usize> {
+		self.session_id
+	}
+
+	/// Opens a connection and waits for Hello.
+	async fn open(url: &str) -> Result<(Socket, Interval), Error> {
+		let (mut socket, _) = connect_async(url).await?;
+
+		let hello = loop {
+			match socket.next().await {
+				Some(Ok(Message::Text(text))) => {
+					let payload: RawPayload = serde_json::from_str(&text)?;
+
+					if payload.op != Hello::CODE {
+						return Err(Error::UnexpectedPayload);
+					}
+
+					break serde_json::from_value::<Hello>(payload.d)?;
+				}
+				Some(Ok(Message::Close(_))) | None => {
+					return Err(Error::UnexpectedPayload)
+				}
+				Some(Ok(_)) => continue,
+				Some(Err(err)) => return Err(err.into()),
+			}
+		};
+
+		let period = Duration::from_millis(hello.heartbeat_interval);
+		let heartbeat = time::interval_at(Instant::now() + period, period);
+
+		Ok((socket, heartbeat))
+	}
+
+	async fn send_identify(&mut self) -> Result<(), Error> {
+		let identify = GatewayCommand::Identify(self.identify.clone());
+		self.send(&identify).await
+	}
+
+	/// Sends a command to the gateway.
+	pub async fn send(
+		&mut self, command: &GatewayCommand,
+	) -> Result<(), Error> {
+		let payload = serde_json::to_string(command)?;
+		self.socket.send(Message::Text(payload)).await?;
+		Ok(())
+	}
+
+	/// Opens a new connection after the last one was lost, and resumes the
+	/// session if there was one.
+	async fn reconnect(&mut self) -> Result<(), Error> {
+		let mut delay = RECONNECT_DELAY;
+		let mut attempts = 0;
+
+		let (socket, heartbeat) = loop {
+			time::sleep(delay).await;
+
+			match Self::open(&self.url).await {
+				Ok(opened) => break opened,
+				Err(err) if attempts + 1 >= MAX_RECONNECT_ATTEMPTS => {
+					return Err(err)
+				}
+				Err(err) => {
+					log::warn!("Failed to reconnect to the gateway: {}", err);
+					attempts += 1;
+					delay *= 2;
+				}
+			}
+		};
+
+		self.socket = socket;
+		self.heartbeat = heartbeat;
+		self.acked = true;
+
+		match (self.session_id, self.seq) {
+			(Some(session_id), Some(seq)) => {
+				let resume = GatewayCommand::Resume(Resume {
+					token: self.identify.token.clone(),
+					session_id,
+					seq,
+				});
+				self.send(&resume).await
+			}
+			_ => self.send_identify().await,
+		}
+	}
+
+	/// Waits for the next event, sending heartbeats and reconnecting in the
+	/// meantime. Fails if the gateway closes the connection for a reason
+	/// reconnecting would not fix, or if it cannot reconnect.
+	pub async fn next_event(&mut self) -> Result<GatewayEvent, Error> {
+		loop {
+			let message = tokio::select! {
+				_ = self.heartbeat.tick() => {
+					if !self.acked {
+						log::warn!("Gateway did not acknowledge the heartbeat");
+						self.reconnect().await?;
+						continue;
+					}
+
+					self.acked = false;
+					let heartbeat = GatewayCommand::Heartbeat(self.seq);
+
+					if let Err(err) = self.send(&heartbeat).await {
+						log::warn!("Failed to send heartbeat: {}", err);
+						self.reconnect().await?;
+					}
+
+					continue;
+				}
+				message = self.socket.next() => message,
+			};
+
+			let text = match message {
+				Some(Ok(Message::Text(text))) => text,
+				Some(Ok(Message::Close(frame))) => {
+					let (code, reason) = frame
+						.map(|frame| {
+							(u16::from(frame.code), frame.reason.into())
+						})
+						.unwrap_or_default();
+
+					if CloseCode::try_from(code)
+						.is_ok_and(|close_code| !close_code.reconnect())
+					{
+						return Err(Error::Closed { code, reason });
+					}
+
+					log::debug!("Gateway closed with {}: {}", code, reason);
+					self.reconnect().await?;
+					continue;
+				}
+				// Pings are answered by tungstenite
+				Some(Ok(_)) => continue,
+				Some(Err(err)) => {
+					log::warn!("Gateway connection failed: {}", err);
+					self.reconnect().await?;
+					continue;
+				}
+				None => {
+					self.reconnect().await?;
+					continue;
+				}
+			};
+
+			let payload: RawPayload = serde_json::from_str(&text)?;
+
+			if payload.s.is_some() {
+				self.seq = payload.s;
+			}
+
+			match payload.op {
+				op if op == Opcode::HeartbeatAck as u8 => self.acked = true,
+				op if op == Opcode::InvalidSession as u8 => {
+					self.session_id = None;
+					self.seq = None;
+					self.send_identify().await?;
+				}
+				op => {
+					let event = GatewayEvent::decode(op, payload.d)?;
+
+					if let GatewayEvent::Ready(ready) = &event {
+						self.session_id = Some(ready.session_id);
+					}
+
+					return Ok(event);
+				}
+			}
+		}
+	}
+
+	/// Closes the connection.
+	pub async fn close(mut self) -> Result<(), Error> {
+		self.socket.close(None).await?;
+		Ok(())
+	}
+}
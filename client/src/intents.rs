@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+/// Bitfield of the groups of events a session subscribes to, sent with
+/// Identify.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Intents(pub u64);
+
+impl Intents {
+	/// Channel, thread and pin events.
+	pub const GUILDS: Self = Self(1 << 0);
+	/// Members joining, leaving, being timed out and being banned.
+	pub const GUILD_MEMBERS: Self = Self(1 << 1);
+	/// Messages sent in channels.
+	pub const GUILD_MESSAGES: Self = Self(1 << 2);
+	/// Messages sent in direct messages. Reserved, there are no direct
+	/// message channels yet.
+	pub const DIRECT_MESSAGES: Self = Self(1 << 3);
+	pub const TYPING: Self = Self(1 << 4);
+	/// Presence updates, and the presences sent with Ready.
+	pub const PRESENCES: Self = Self(1 << 5);
+	/// Reserved, there are no reactions yet.
+	pub const REACTIONS: Self = Self(1 << 6);
+	/// Users joining, leaving and moving between voice channels.
+	pub const GUILD_VOICE_STATES: Self = Self(1 << 7);
+
+	/// Intents which expose every member of a guild. Bots have to be allowed
+	/// to use them, users always are.
+	pub const PRIVILEGED: Self =
+		Self(Self::GUILD_MEMBERS.0 | Self::PRESENCES.0);
+
+	/// Every intent. Sessions which do not send intents receive everything.
+	pub const ALL: Self = Self(
+		Self::GUILDS.0
+			| Self::GUILD_MEMBERS.0
+			| Self::GUILD_MESSAGES.0
+			| Self::DIRECT_MESSAGES.0
+			| Self::TYPING.0
+			| Self::PRESENCES.0
+			| Self::REACTIONS.0
+			| Self::GUILD_VOICE_STATES.0,
+	);
+
+	/// Whether all of the intents in `other` are set.
+	pub fn contains(self, other: Self) -> bool {
+		self.0 & other.0 == other.0
+	}
+
+	/// Whether any of the intents in `other` are set.
+	pub fn intersects(self, other: Self) -> bool {
+		self.0 & other.0 != 0
+	}
+
+	/// Whether the bitfield only contains known intents.
+	pub fn is_valid(self) -> bool {
+		Self::ALL.contains(self)
+	}
+}
+
+impl Default for Intents {
+	fn default() -> Self {
+		Self::ALL
+	}
+}
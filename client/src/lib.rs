@@ -0,0 +1,17 @@
+//! Client for the shiki REST API and gateway, for bots and other programs
+//! talking to a shiki server. The models, events and commands are the ones the
+//! server uses, so both stay in sync.
+
+pub mod close_code;
+pub mod commands;
+mod error;
+pub mod events;
+pub mod gateway;
+pub mod intents;
+pub mod model;
+pub mod rest;
+
+pub use commands::{GatewayCommand, Identify};
+pub use error::Error;
+pub use gateway::{Gateway, GatewayEvent};
+pub use rest::Client;
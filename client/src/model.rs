@@ -0,0 +1,429 @@
+use serde::{Deserialize, Serialize};
+use std::ops::{Deref, DerefMut};
+
+#[cfg(feature = "redis")]
+use redis::{FromRedisValue, RedisResult, RedisWrite, ToRedisArgs};
+
+// This file contains the models shared by the server and its clients, as they
+// are sent over the REST API and the gateway.
+
+/// A list of ids. Stored in Redis as a single comma separated field, so it can
+/// be part of a hash.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[serde(transparent)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Snowflakes(pub Vec<i64>);
+
+impl Deref for Snowflakes {
+	type Target = Vec<i64>;
+
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
+impl DerefMut for Snowflakes {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.0
+	}
+}
+
+impl From<Vec<i64>> for Snowflakes {
+	fn from(ids: Vec<i64>) -> Self {
+		Self(ids)
+	}
+}
+
+#[cfg(feature = "redis")]
+impl ToRedisArgs for Snowflakes {
+	fn write_redis_args<W>(&self, out: &mut W)
+	where
+		W: ?Sized + RedisWrite,
+	{
+		self.0
+			.iter()
+			.map(i64::to_string)
+			.collect::<Vec<_>>()
+			.join(",")
+			.write_redis_args(out)
+	}
+}
+
+#[cfg(feature = "redis")]
+impl FromRedisValue for Snowflakes {
+	fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+		String::from_redis_value(v)?
+			.split(',')
+			.filter(|id| !id.is_empty())
+			.map(|id| {
+				id.parse().map_err(|_| {
+					(redis::ErrorKind::TypeError, "Invalid snowflake").into()
+				})
+			})
+			.collect::<RedisResult<Vec<_>>>()
+			.map(Self)
+	}
+}
+
+/// The kind of a channel. Stored as its discriminant in Redis.
+#[derive(
+	Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, Default,
+)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum ChannelType {
+	/// A regular text channel.
+	#[default]
+	Text,
+	/// A category which other channels can be grouped under.
+	Category,
+	/// A discussion started from a message, nested under a text channel.
+	Thread,
+}
+
+#[cfg(feature = "redis")]
+impl ToRedisArgs for ChannelType {
+	fn write_redis_args<W>(&self, out: &mut W)
+	where
+		W: ?Sized + RedisWrite,
+	{
+		(*self as u8).write_redis_args(out)
+	}
+}
+
+#[cfg(feature = "redis")]
+impl FromRedisValue for ChannelType {
+	fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+		match u8::from_redis_value(v)? {
+			0 => Ok(ChannelType::Text),
+			1 => Ok(ChannelType::Category),
+			2 => Ok(ChannelType::Thread),
+			_ => {
+				Err((redis::ErrorKind::TypeError, "Invalid channel type")
+					.into())
+			}
+		}
+	}
+}
+
+/// The online status of a user.
+#[derive(
+	Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, Default,
+)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum Status {
+	#[default]
+	Online,
+	Idle,
+	/// Do not disturb.
+	Dnd,
+	/// Shown as offline to other users.
+	Invisible,
+	Offline,
+}
+
+#[cfg(feature = "redis")]
+impl ToRedisArgs for Status {
+	fn write_redis_args<W>(&self, out: &mut W)
+	where
+		W: ?Sized + RedisWrite,
+	{
+		(*self as u8).write_redis_args(out)
+	}
+}
+
+#[cfg(feature = "redis")]
+impl FromRedisValue for Status {
+	fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+		match u8::from_redis_value(v)? {
+			0 => Ok(Status::Online),
+			1 => Ok(Status::Idle),
+			2 => Ok(Status::Dnd),
+			3 => Ok(Status::Invisible),
+			4 => Ok(Status::Offline),
+			_ => Err((redis::ErrorKind::TypeError, "Invalid status").into()),
+		}
+	}
+}
+
+/// The presence of a user, which is shared by all of their sessions.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[cfg_attr(
+	feature = "redis",
+	derive(redis_derive::ToRedisArgs, redis_derive::FromRedisValue)
+)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Presence {
+	/// The id of the user
+	pub user_id: i64,
+	/// The status of the user
+	pub status: Status,
+	/// Custom status text set by the user
+	pub custom_status: Option<String>,
+}
+
+impl Presence {
+	pub fn new(user_id: i64, status: Status) -> Self {
+		Presence { user_id, status, custom_status: None }
+	}
+
+	/// The presence as seen by other users, which hides invisible users.
+	pub fn public(&self) -> Self {
+		if self.status == Status::Invisible {
+			Presence::new(self.user_id, Status::Offline)
+		} else {
+			self.clone()
+		}
+	}
+}
+
+/// A user as other users see them.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct User {
+	pub id: i64,
+	pub username: String,
+	pub joined: usize,
+	/// Avatar URL
+	pub avatar: Option<String>,
+}
+
+/// A channel, a category or a thread.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Channel {
+	/// Channel ID
+	pub id: i64,
+	/// Guild ID
+	pub guild_id: Option<i64>,
+	/// Channel name
+	pub name: String,
+	/// Channel topic
+	pub description: Option<String>,
+	/// Channel type
+	#[serde(rename = "type")]
+	pub kind: ChannelType,
+	/// Sorting position
+	pub position: i64,
+	/// ID of the parent category, or of the channel a thread was started in
+	pub parent_id: Option<i64>,
+	/// ID of the message a thread was started from
+	pub starter_message_id: Option<i64>,
+	/// IDs of the users who joined a thread
+	pub member_ids: Snowflakes,
+	/// Whether a thread is archived
+	pub archived: bool,
+	/// Minutes of inactivity after which a thread is archived
+	pub auto_archive_duration: u64,
+}
+
+/// A community which users join through invites.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Guild {
+	/// ID of the guild
+	pub id: i64,
+	/// Guild name
+	pub name: String,
+	/// ID of the guild's owner
+	pub owner_id: i64,
+}
+
+/// How much of a channel a user has read.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ReadState {
+	/// Channel ID
+	pub channel_id: i64,
+	/// ID of the last message the user has acknowledged
+	pub last_message_id: Option<i64>,
+	/// Number of messages sent after the last acknowledged one
+	pub unread_count: u64,
+	/// Number of unread messages which mention the user
+	pub mention_count: u64,
+}
+
+/// A message with its author.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct GetMessage {
+	/// The id of the message
+	pub id: i64,
+	/// The id of the channel the message was sent in
+	pub channel_id: i64,
+	/// The content of the message
+	pub content: String,
+	/// Unix timestamp for when the message was created
+	pub created_at: usize,
+	/// User who sent the message
+	pub author: User,
+	/// The ids of the users mentioned in the message
+	pub mentions: Snowflakes,
+	/// The ids of the roles mentioned in the message
+	pub mention_roles: Snowflakes,
+	/// Whether the message mentions everyone
+	pub mention_everyone: bool,
+	/// Whether the message is pinned, new messages are not
+	#[serde(default)]
+	pub pinned: bool,
+}
+
+/// A message as it is stored, referring to its author by id.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub struct Message {
+	/// The id of the message
+	pub id: i64,
+	/// The id of the channel the message was sent in
+	pub channel_id: i64,
+	/// The id of the user who sent the message
+	pub author_id: i64,
+	/// The content of the message
+	pub content: String,
+	/// Unix timestamp for when the message was created
+	pub created_at: usize,
+	/// The ids of the users mentioned in the message
+	#[serde(default)]
+	pub mentions: Snowflakes,
+	/// The ids of the roles mentioned in the message
+	#[serde(default)]
+	pub mention_roles: Snowflakes,
+	/// Whether the message mentions everyone
+	#[serde(default)]
+	pub mention_everyone: bool,
+	/// Whether the message is pinned in its channel
+	#[serde(default)]
+	pub pinned: bool,
+}
+
+/// Credentials of a user who registered or logged in.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserResponse {
+	pub user_id: i64,
+	pub token: String,
+}
+
+/// A code which lets users join a guild and channel.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub struct Invite {
+	/// The invite code
+	pub code: String,
+	/// The id of the guild the invite is for
+	pub guild_id: Option<i64>,
+	/// The id of the channel the invite is for
+	pub channel_id: i64,
+	/// The id of the user who created the invite
+	pub inviter_id: i64,
+	/// Unix timestamp for when the invite was created
+	pub created_at: usize,
+	/// Unix timestamp after which the invite can no longer be used, if any
+	pub expires_at: Option<usize>,
+	/// How many times the invite can be used, 0 for unlimited
+	pub max_uses: u64,
+	/// How many times the invite has been used
+	pub uses: u64,
+}
+
+/// What users see of an invite before accepting it.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct InvitePreview {
+	pub code: String,
+	pub guild: Option<Guild>,
+	pub channel: Channel,
+	pub inviter: User,
+	/// Number of users in the guild
+	pub member_count: Option<u64>,
+	pub expires_at: Option<usize>,
+	pub max_uses: u64,
+	pub uses: u64,
+}
+
+/// A named set of permissions which can be given to users.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub struct Role {
+	/// The id of the role
+	pub id: i64,
+	/// The name of the role
+	pub name: String,
+	/// Bitfield of the permissions granted by the role
+	pub permissions: u64,
+	/// Unix timestamp for when the role was created
+	pub created_at: usize,
+}
+
+/// A user who is banned from a guild.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Ban {
+	/// The id of the guild
+	pub guild_id: i64,
+	/// The id of the banned user
+	pub user_id: i64,
+	/// Why the user was banned
+	pub reason: Option<String>,
+	/// The id of the user who issued the ban
+	pub moderator_id: i64,
+	/// Unix timestamp for when the user was banned
+	pub created_at: usize,
+}
+
+/// The kind of action recorded in an audit log entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum AuditLogAction {
+	ChannelCreate,
+	ChannelUpdate,
+	ChannelDelete,
+	RoleCreate,
+	/// A role was given to or taken from a user.
+	MemberRoleUpdate,
+	InviteCreate,
+	InviteDelete,
+	MessagePin,
+	MessageUnpin,
+	Kick,
+	Ban,
+	Unban,
+	Timeout,
+}
+
+/// A field which was changed by an action.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AuditLogChange {
+	/// The name of the field
+	pub key: String,
+	/// The value before the action, missing if the field was added
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub old_value: Option<serde_json::Value>,
+	/// The value after the action, missing if the field was removed
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub new_value: Option<serde_json::Value>,
+}
+
+/// A privileged action taken by a user.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct AuditLogEntry {
+	/// The id of the entry
+	pub id: i64,
+	/// The id of the guild the action was taken in, if any
+	pub guild_id: Option<i64>,
+	/// The id of the user who took the action
+	pub user_id: i64,
+	/// The id of the user, channel or role the action affected
+	pub target_id: Option<i64>,
+	/// What was done
+	pub action: AuditLogAction,
+	/// The fields the action changed
+	#[serde(default)]
+	pub changes: Vec<AuditLogChange>,
+	/// Why it was done
+	pub reason: Option<String>,
+	/// Unix timestamp for when the action was taken
+	pub created_at: usize,
+}
@@ -0,0 +1,561 @@
+use crate::{
+	error::Error,
+	model::{
+		AuditLogAction, AuditLogEntry, Ban, Channel, ChannelType, GetMessage,
+		Guild, Invite, InvitePreview, Message, Presence, ReadState, Role,
+		Status, User, UserResponse,
+	},
+};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use reqwest::{Method, RequestBuilder};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Header the reason for an action is sent in, to be recorded in the audit
+/// log.
+pub const REASON_HEADER: &str = "X-Audit-Log-Reason";
+
+/// Characters percent-encoded in the reason header on top of the non-ASCII
+/// ones, which header values cannot hold.
+const REASON_ENCODE_SET: &AsciiSet = &CONTROLS.add(b'%');
+
+/// Body of `POST /api/v1/channels`.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CreateChannel {
+	pub name: String,
+	/// The channel topic
+	pub description: Option<String>,
+	/// The type of channel to create
+	#[serde(rename = "type")]
+	pub kind: ChannelType,
+	/// Id of the category to create the channel in
+	pub parent_id: Option<i64>,
+	/// Sorting position of the channel
+	pub position: i64,
+	/// Id of the guild to create the channel in
+	pub guild_id: Option<i64>,
+}
+
+/// Body of `PATCH /api/v1/channels/{channel_id}`. Fields which are not set are
+/// left unchanged.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ModifyChannel {
+	/// Channel name
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub name: Option<String>,
+	/// Channel topic, `Some(None)` clears it
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub description: Option<Option<String>>,
+	/// Sorting position of the channel
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub position: Option<i64>,
+	/// Id of the parent category, `Some(None)` removes the channel from its
+	/// category
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub parent_id: Option<Option<i64>>,
+	/// Whether a thread is archived
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub archived: Option<bool>,
+	/// Minutes of inactivity after which a thread is archived
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub auto_archive_duration: Option<u64>,
+}
+
+/// Query of `GET /api/v1/channels/{channel_id}/messages`.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct GetMessages {
+	/// Get messages before this message ID
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub before: Option<i64>,
+	/// Get messages after this message ID
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub after: Option<i64>,
+	/// Max number of messages to return (1-100), 50 unless specified
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub limit: Option<i64>,
+}
+
+/// Body of `POST /api/v1/channels/{channel_id}/messages/{message_id}/threads`.
+#[derive(Serialize, Debug, Clone)]
+pub struct CreateThread {
+	pub name: String,
+	/// Minutes of inactivity after which the thread is archived
+	pub auto_archive_duration: u64,
+}
+
+/// Body of `POST /api/v1/channels/{channel_id}/invites`.
+#[derive(Serialize, Debug, Clone)]
+pub struct CreateInvite {
+	/// Seconds after which the invite expires, 0 never expires
+	pub max_age: u64,
+	/// How many times the invite can be used, 0 for unlimited
+	pub max_uses: u64,
+}
+
+/// Query of `GET /api/v1/guilds/{guild_id}/audit-logs` and
+/// `GET /api/v1/audit-logs`.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct AuditLogFilter {
+	/// Only entries of actions taken by this user
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub user_id: Option<i64>,
+	/// Only entries of actions which affected this user, channel or role
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub target_id: Option<i64>,
+	/// Only entries of this kind of action
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub action: Option<AuditLogAction>,
+	/// Only entries before this entry ID
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub before: Option<i64>,
+	/// Max number of entries to return (1-100), 50 unless specified
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub limit: Option<i64>,
+}
+
+/// Body of `PUT /api/v1/guilds/{guild_id}/bans/{user_id}`.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CreateBan {
+	/// Why the user is banned
+	pub reason: Option<String>,
+	/// Number of days of messages of the user to delete (0-7)
+	pub delete_message_days: u64,
+}
+
+/// Query of `GET /api/v1/users/@me/mentions`.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct GetMentions {
+	/// Get mentions before this message ID
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub before: Option<i64>,
+	/// Max number of messages to return (1-100), 25 unless specified
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub limit: Option<i64>,
+}
+
+/// Body of `PATCH /api/v1/users/@me`. Fields which are not set are left
+/// unchanged.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ModifyUser {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub username: Option<String>,
+	/// Avatar URL
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub avatar: Option<String>,
+}
+
+/// Client for the REST API. Every route but `register` and `login` needs the
+/// token of a user, which `login` sets.
+#[derive(Debug, Clone)]
+pub struct Client {
+	http: reqwest::Client,
+	/// URL of the server, such as `https://shiki.example.com`
+	base_url: String,
+	token: Option<String>,
+	/// Reason sent with the next requests, for the audit log
+	reason: Option<String>,
+}
+
+impl Client {
+	pub fn new(base_url: &str) -> Self {
+		Client {
+			http: reqwest::Client::new(),
+			base_url: base_url.trim_end_matches('/').to_string(),
+			token: None,
+			reason: None,
+		}
+	}
+
+	/// A client which authenticates as the user the token belongs to.
+	pub fn with_token(mut self, token: &str) -> Self {
+		self.token = Some(token.to_string());
+		self
+	}
+
+	/// A copy of the client which records `reason` in the audit log for the
+	/// actions it takes.
+	pub fn with_reason(&self, reason: &str) -> Self {
+		Client { reason: Some(reason.to_string()), ..self.clone() }
+	}
+
+	/// The token the client authenticates with, if it has one.
+	pub fn token(&self) -> Option<&str> {
+		self.token.as_deref()
+	}
+
+	fn request(&self, method: Method, path: &str) -> RequestBuilder {
+		let mut builder =
+			self.http.request(method, format!("{}{}", self.base_url, path));
+
+		if let Some(token) = &self.token {
+			builder = builder.bearer_auth(token);
+		}
+
+		if let Some(reason) = &self.reason {
+			builder = builder.header(
+				REASON_HEADER,
+				utf8_percent_encode(reason, REASON_ENCODE_SET).to_string(),
+			);
+		}
+
+		builder
+	}
+
+	async fn send(builder: RequestBuilder) -> Result<reqwest::Response, Error> {
+		let res = builder.send().await?;
+		let status = res.status();
+
+		if status.is_success() {
+			Ok(res)
+		} else {
+			let body = res.text().await.unwrap_or_default();
+			Err(Error::Status { status, body })
+		}
+	}
+
+	async fn json<T: DeserializeOwned>(
+		builder: RequestBuilder,
+	) -> Result<T, Error> {
+		Ok(Self::send(builder).await?.json().await?)
+	}
+
+	async fn empty(builder: RequestBuilder) -> Result<(), Error> {
+		Self::send(builder).await.map(|_| ())
+	}
+
+	/// Creates an account.
+	pub async fn register(
+		&self, email: &str, username: &str, password: &str,
+	) -> Result<UserResponse, Error> {
+		let body = serde_json::json!({
+			"email": email,
+			"username": username,
+			"password": password,
+		});
+
+		Self::json(self.request(Method::POST, "/auth/v1/register").json(&body))
+			.await
+	}
+
+	/// Logs in, the client then authenticates with the token of the user.
+	pub async fn login(
+		&mut self, email: &str, password: &str,
+	) -> Result<UserResponse, Error> {
+		let body = serde_json::json!({ "email": email, "password": password });
+		let res: UserResponse = Self::json(
+			self.request(Method::POST, "/auth/v1/login").json(&body),
+		)
+		.await?;
+
+		self.token = Some(res.token.clone());
+
+		Ok(res)
+	}
+
+	/// Number of visitors of the server, as text.
+	pub async fn count(&self) -> Result<String, Error> {
+		Ok(Self::send(self.request(Method::GET, "/api/v1/count"))
+			.await?
+			.text()
+			.await?)
+	}
+
+	pub async fn channels(&self) -> Result<Vec<Channel>, Error> {
+		Self::json(self.request(Method::GET, "/api/v1/channels")).await
+	}
+
+	pub async fn create_channel(
+		&self, channel: &CreateChannel,
+	) -> Result<Channel, Error> {
+		Self::json(self.request(Method::POST, "/api/v1/channels").json(channel))
+			.await
+	}
+
+	pub async fn modify_channel(
+		&self, channel_id: i64, changes: &ModifyChannel,
+	) -> Result<Channel, Error> {
+		let path = format!("/api/v1/channels/{}", channel_id);
+
+		Self::json(self.request(Method::PATCH, &path).json(changes)).await
+	}
+
+	/// Deletes a channel, returning it.
+	pub async fn delete_channel(
+		&self, channel_id: i64,
+	) -> Result<Channel, Error> {
+		let path = format!("/api/v1/channels/{}", channel_id);
+
+		Self::json(self.request(Method::DELETE, &path)).await
+	}
+
+	pub async fn join_channel(
+		&self, channel_id: i64,
+	) -> Result<Channel, Error> {
+		let path = format!("/api/v1/channels/{}/join", channel_id);
+
+		Self::json(self.request(Method::POST, &path)).await
+	}
+
+	pub async fn messages(
+		&self, channel_id: i64, query: &GetMessages,
+	) -> Result<Vec<GetMessage>, Error> {
+		let path = format!("/api/v1/channels/{}/messages", channel_id);
+
+		Self::json(self.request(Method::GET, &path).query(query)).await
+	}
+
+	pub async fn create_message(
+		&self, channel_id: i64, content: &str,
+	) -> Result<GetMessage, Error> {
+		let path = format!("/api/v1/channels/{}/messages", channel_id);
+		let body = serde_json::json!({ "content": content });
+
+		Self::json(self.request(Method::POST, &path).json(&body)).await
+	}
+
+	/// Starts a thread from a message.
+	pub async fn create_thread(
+		&self, channel_id: i64, message_id: i64, thread: &CreateThread,
+	) -> Result<Channel, Error> {
+		let path = format!(
+			"/api/v1/channels/{}/messages/{}/threads",
+			channel_id, message_id
+		);
+
+		Self::json(self.request(Method::POST, &path).json(thread)).await
+	}
+
+	pub async fn thread_members(
+		&self, thread_id: i64,
+	) -> Result<Vec<User>, Error> {
+		let path = format!("/api/v1/channels/{}/thread-members", thread_id);
+
+		Self::json(self.request(Method::GET, &path)).await
+	}
+
+	pub async fn join_thread(&self, thread_id: i64) -> Result<(), Error> {
+		let path = format!("/api/v1/channels/{}/thread-members/@me", thread_id);
+
+		Self::empty(self.request(Method::PUT, &path)).await
+	}
+
+	pub async fn leave_thread(&self, thread_id: i64) -> Result<(), Error> {
+		let path = format!("/api/v1/channels/{}/thread-members/@me", thread_id);
+
+		Self::empty(self.request(Method::DELETE, &path)).await
+	}
+
+	pub async fn create_guild(&self, name: &str) -> Result<Guild, Error> {
+		let body = serde_json::json!({ "name": name });
+
+		Self::json(self.request(Method::POST, "/api/v1/guilds").json(&body))
+			.await
+	}
+
+	pub async fn guild(&self, guild_id: i64) -> Result<Guild, Error> {
+		let path = format!("/api/v1/guilds/{}", guild_id);
+
+		Self::json(self.request(Method::GET, &path)).await
+	}
+
+	pub async fn create_invite(
+		&self, channel_id: i64, invite: &CreateInvite,
+	) -> Result<Invite, Error> {
+		let path = format!("/api/v1/channels/{}/invites", channel_id);
+
+		Self::json(self.request(Method::POST, &path).json(invite)).await
+	}
+
+	/// What an invite leads to, without accepting it.
+	pub async fn invite(&self, code: &str) -> Result<InvitePreview, Error> {
+		let path = format!("/api/v1/invites/{}", code);
+
+		Self::json(self.request(Method::GET, &path)).await
+	}
+
+	/// Accepts an invite, returning the channel it leads to.
+	pub async fn accept_invite(&self, code: &str) -> Result<Channel, Error> {
+		let path = format!("/api/v1/invites/{}", code);
+
+		Self::json(self.request(Method::POST, &path)).await
+	}
+
+	pub async fn delete_invite(&self, code: &str) -> Result<(), Error> {
+		let path = format!("/api/v1/invites/{}", code);
+
+		Self::empty(self.request(Method::DELETE, &path)).await
+	}
+
+	pub async fn kick_member(
+		&self, guild_id: i64, user_id: i64,
+	) -> Result<(), Error> {
+		let path = format!("/api/v1/guilds/{}/members/{}", guild_id, user_id);
+
+		Self::empty(self.request(Method::DELETE, &path)).await
+	}
+
+	/// Times a member out until `until`, or lifts their timeout if None.
+	pub async fn timeout_member(
+		&self, guild_id: i64, user_id: i64, until: Option<usize>,
+	) -> Result<(), Error> {
+		let path = format!("/api/v1/guilds/{}/members/{}", guild_id, user_id);
+		let body = serde_json::json!({ "communication_disabled_until": until });
+
+		Self::empty(self.request(Method::PATCH, &path).json(&body)).await
+	}
+
+	pub async fn audit_logs(
+		&self, guild_id: i64, filter: &AuditLogFilter,
+	) -> Result<Vec<AuditLogEntry>, Error> {
+		let path = format!("/api/v1/guilds/{}/audit-logs", guild_id);
+
+		Self::json(self.request(Method::GET, &path).query(filter)).await
+	}
+
+	/// Entries of the actions taken outside of any guild, such as changes to
+	/// roles. Only administrators can read them.
+	pub async fn instance_audit_logs(
+		&self, filter: &AuditLogFilter,
+	) -> Result<Vec<AuditLogEntry>, Error> {
+		let request = self.request(Method::GET, "/api/v1/audit-logs");
+
+		Self::json(request.query(filter)).await
+	}
+
+	pub async fn bans(&self, guild_id: i64) -> Result<Vec<Ban>, Error> {
+		let path = format!("/api/v1/guilds/{}/bans", guild_id);
+
+		Self::json(self.request(Method::GET, &path)).await
+	}
+
+	pub async fn ban_member(
+		&self, guild_id: i64, user_id: i64, ban: &CreateBan,
+	) -> Result<(), Error> {
+		let path = format!("/api/v1/guilds/{}/bans/{}", guild_id, user_id);
+
+		Self::empty(self.request(Method::PUT, &path).json(ban)).await
+	}
+
+	pub async fn unban_member(
+		&self, guild_id: i64, user_id: i64,
+	) -> Result<(), Error> {
+		let path = format!("/api/v1/guilds/{}/bans/{}", guild_id, user_id);
+
+		Self::empty(self.request(Method::DELETE, &path)).await
+	}
+
+	pub async fn pins(&self, channel_id: i64) -> Result<Vec<Message>, Error> {
+		let path = format!("/api/v1/channels/{}/pins", channel_id);
+
+		Self::json(self.request(Method::GET, &path)).await
+	}
+
+	pub async fn pin_message(
+		&self, channel_id: i64, message_id: i64,
+	) -> Result<(), Error> {
+		let path =
+			format!("/api/v1/channels/{}/pins/{}", channel_id, message_id);
+
+		Self::empty(self.request(Method::PUT, &path)).await
+	}
+
+	pub async fn unpin_message(
+		&self, channel_id: i64, message_id: i64,
+	) -> Result<(), Error> {
+		let path =
+			format!("/api/v1/channels/{}/pins/{}", channel_id, message_id);
+
+		Self::empty(self.request(Method::DELETE, &path)).await
+	}
+
+	/// Marks a channel as read up to a message.
+	pub async fn ack_message(
+		&self, channel_id: i64, message_id: i64,
+	) -> Result<ReadState, Error> {
+		let path = format!(
+			"/api/v1/channels/{}/messages/{}/ack",
+			channel_id, message_id
+		);
+
+		Self::json(self.request(Method::POST, &path)).await
+	}
+
+	/// Shows the user as typing in a channel.
+	pub async fn trigger_typing(&self, channel_id: i64) -> Result<(), Error> {
+		let path = format!("/api/v1/channels/{}/typing", channel_id);
+
+		Self::empty(self.request(Method::POST, &path)).await
+	}
+
+	pub async fn modify_presence(
+		&self, status: Status, custom_status: Option<&str>,
+	) -> Result<Presence, Error> {
+		let body = serde_json::json!({
+			"status": status,
+			"custom_status": custom_status,
+		});
+
+		Self::json(
+			self.request(Method::PATCH, "/api/v1/users/@me/presence")
+				.json(&body),
+		)
+		.await
+	}
+
+	pub async fn presence(&self, user_id: i64) -> Result<Presence, Error> {
+		let path = format!("/api/v1/users/{}/presence", user_id);
+
+		Self::json(self.request(Method::GET, &path)).await
+	}
+
+	/// Recent messages which mention the user, newest first.
+	pub async fn mentions(
+		&self, query: &GetMentions,
+	) -> Result<Vec<Message>, Error> {
+		Self::json(
+			self.request(Method::GET, "/api/v1/users/@me/mentions")
+				.query(query),
+		)
+		.await
+	}
+
+	pub async fn roles(&self) -> Result<Vec<Role>, Error> {
+		Self::json(self.request(Method::GET, "/api/v1/roles")).await
+	}
+
+	pub async fn create_role(
+		&self, name: &str, permissions: u64,
+	) -> Result<Role, Error> {
+		let body = serde_json::json!({
+			"name": name,
+			"permissions": permissions,
+		});
+
+		Self::json(self.request(Method::POST, "/api/v1/roles").json(&body))
+			.await
+	}
+
+	pub async fn add_role_member(
+		&self, role_id: i64, user_id: i64,
+	) -> Result<(), Error> {
+		let path = format!("/api/v1/roles/{}/members/{}", role_id, user_id);
+
+		Self::empty(self.request(Method::PUT, &path)).await
+	}
+
+	pub async fn remove_role_member(
+		&self, role_id: i64, user_id: i64,
+	) -> Result<(), Error> {
+		let path = format!("/api/v1/roles/{}/members/{}", role_id, user_id);
+
+		Self::empty(self.request(Method::DELETE, &path)).await
+	}
+
+	pub async fn modify_user(
+		&self, changes: &ModifyUser,
+	) -> Result<User, Error> {
+		Self::json(
+			self.request(Method::PATCH, "/api/v1/users/@me").json(changes),
+		)
+		.await
+	}
+}
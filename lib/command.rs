@@ -0,0 +1,132 @@
+use crate::opcode::{parse_attr, OpcodeAttr};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use std::collections::HashMap;
+
+/// Generates `opcode()`, `accepts()`, `Serialize` and `Deserialize` for an
+/// enum of the commands clients send. Every variant wraps the type of the
+/// command's data and is tagged with `#[opcode(value = "Opcode::X", code = 7)]`.
+/// Payloads are `{"op": code, "d": data}`.
+pub fn expand(ast: &syn::DeriveInput) -> TokenStream {
+	let data = match &ast.data {
+		syn::Data::Enum(data) => data,
+		_ => {
+			return syn::Error::new(
+				Span::call_site(),
+				"GatewayCommand can only be derived for enums",
+			)
+			.to_compile_error()
+		}
+	};
+
+	let name = &ast.ident;
+	let mut seen = HashMap::new();
+	let mut opcode_arms = Vec::new();
+	let mut serialize_arms = Vec::new();
+	let mut deserialize_arms = Vec::new();
+	let mut codes = Vec::new();
+	let mut assertions = Vec::new();
+
+	for variant in &data.variants {
+		let ident = &variant.ident;
+
+		match &variant.fields {
+			syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => (),
+			_ => {
+				return syn::Error::new_spanned(
+					variant,
+					"Expect a variant with a single field",
+				)
+				.to_compile_error()
+			}
+		}
+
+		let attr = match parse_attr(&variant.attrs, ident.span()) {
+			Ok(attr) => attr,
+			Err(err) => return err.to_compile_error(),
+		};
+		let OpcodeAttr { value, code, code_span } = &attr;
+
+		if let Some(other) = seen.insert(*code, ident) {
+			return syn::Error::new(
+				*code_span,
+				format!("opcode code {code} is already used by {other}"),
+			)
+			.to_compile_error();
+		}
+
+		opcode_arms.push(quote! { #name::#ident(_) => #value });
+		serialize_arms.push(quote! {
+			#name::#ident(d) => serde::Serialize::serialize(
+				&Envelope { op: #value, d },
+				serializer,
+			)
+		});
+		deserialize_arms.push(quote! {
+			#code => serde_json::from_value(envelope.d)
+				.map(#name::#ident)
+				.map_err(serde::de::Error::custom)
+		});
+		let code = proc_macro2::Literal::u8_unsuffixed(*code);
+		codes.push(quote! { #code });
+		assertions.push(attr.assert_matches(ident));
+	}
+
+	quote! {
+		impl #name {
+			pub fn opcode(&self) -> Opcode {
+				match self {
+					#(#opcode_arms,)*
+				}
+			}
+
+			/// Whether clients can send the opcode with this code.
+			pub fn accepts(code: u64) -> bool {
+				matches!(code, #(#codes)|*)
+			}
+		}
+
+		impl serde::Serialize for #name {
+			fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+			where
+				S: serde::Serializer,
+			{
+				#[derive(serde::Serialize)]
+				struct Envelope<'a, T> {
+					op: Opcode,
+					d: &'a T,
+				}
+
+				match self {
+					#(#serialize_arms,)*
+				}
+			}
+		}
+
+		impl<'de> serde::Deserialize<'de> for #name {
+			fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+			where
+				D: serde::Deserializer<'de>,
+			{
+				#[derive(serde::Deserialize)]
+				struct Envelope {
+					op: u8,
+					#[serde(default)]
+					d: serde_json::Value,
+				}
+
+				let envelope = Envelope::deserialize(deserializer)?;
+
+				match envelope.op {
+					#(#deserialize_arms,)*
+					op => Err(serde::de::Error::custom(format_args!(
+						"unknown opcode {}",
+						op
+					))),
+				}
+			}
+		}
+
+		#(#assertions)*
+	}
+}
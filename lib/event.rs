@@ -0,0 +1,186 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+
+const EVENT_ATTR: &str = "event";
+
+/// Generates `opcode()`, `name()`, `envelope()` and `Serialize` for an enum of
+/// events, along with the `{Name}Payload` envelope events are sent to clients
+/// in. Every variant wraps a single type implementing `HasOpcode`, except for
+/// those marked `#[event(internal)]`, which are never sent to clients as an
+/// event of their own. Two variants sharing an opcode fail to compile.
+pub fn expand(ast: &syn::DeriveInput) -> TokenStream {
+	let data = match &ast.data {
+		syn::Data::Enum(data) => data,
+		_ => {
+			return syn::Error::new(
+				Span::call_site(),
+				"GatewayEvent can only be derived for enums",
+			)
+			.to_compile_error()
+		}
+	};
+
+	let name = &ast.ident;
+	let payload = format_ident!("{}Payload", name);
+	let mut opcode_arms = Vec::new();
+	let mut name_arms = Vec::new();
+	let mut serialize_arms = Vec::new();
+	let mut codes = Vec::new();
+
+	for variant in &data.variants {
+		let ident = &variant.ident;
+		let ty = match &variant.fields {
+			syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+				&fields.unnamed[0].ty
+			}
+			_ => {
+				return syn::Error::new_spanned(
+					variant,
+					"Expect a variant with a single field",
+				)
+				.to_compile_error()
+			}
+		};
+
+		let internal = match is_internal(&variant.attrs) {
+			Ok(internal) => internal,
+			Err(err) => return err.to_compile_error(),
+		};
+
+		if internal {
+			opcode_arms.push(quote! { #name::#ident(_) => Opcode::Custom });
+		} else {
+			opcode_arms.push(
+				quote! { #name::#ident(_) => <#ty as HasOpcode>::opcode() },
+			);
+			codes.push(quote! { <#ty as HasOpcode>::CODE });
+		}
+
+		let event_name = screaming_snake_case(&ident.to_string());
+		name_arms.push(quote! { #name::#ident(_) => #event_name });
+
+		serialize_arms.push(quote! {
+			#name::#ident(inner) => serde::Serialize::serialize(inner, serializer)
+		});
+	}
+
+	let duplicate_message = format!("two variants of {name} share an opcode");
+
+	quote! {
+		impl #name {
+			pub fn opcode(&self) -> Opcode {
+				match self {
+					#(#opcode_arms,)*
+				}
+			}
+
+			/// Name of the event, such as `MESSAGE_CREATE`.
+			pub fn name(&self) -> &'static str {
+				match self {
+					#(#name_arms,)*
+				}
+			}
+
+			/// Wraps the event in the envelope it is sent to clients in, with
+			/// its sequence number. The name of the event is only sent if
+			/// `named` is true.
+			pub fn envelope(
+				&self, seq: Option<u64>, named: bool,
+			) -> #payload<'_> {
+				#payload { event: self, seq, named }
+			}
+		}
+
+		/// Envelope of an event sent to clients, serialized as
+		/// `{"op": code, "d": data, "s": seq, "t": name}`.
+		pub struct #payload<'a> {
+			event: &'a #name,
+			seq: Option<u64>,
+			named: bool,
+		}
+
+		impl serde::Serialize for #payload<'_> {
+			fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+			where
+				S: serde::Serializer,
+			{
+				use serde::ser::SerializeStruct;
+
+				let len = if self.named { 4 } else { 3 };
+				let mut state =
+					serializer.serialize_struct(stringify!(#payload), len)?;
+
+				state.serialize_field("op", &self.event.opcode())?;
+				state.serialize_field("d", self.event)?;
+				state.serialize_field("s", &self.seq)?;
+
+				if self.named {
+					state.serialize_field("t", self.event.name())?;
+				} else {
+					state.skip_field("t")?;
+				}
+
+				state.end()
+			}
+		}
+
+		impl serde::Serialize for #name {
+			fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+			where
+				S: serde::Serializer,
+			{
+				match self {
+					#(#serialize_arms,)*
+				}
+			}
+		}
+
+		const _: () = {
+			let codes: &[u8] = &[#(#codes),*];
+			let mut i = 0;
+
+			while i < codes.len() {
+				let mut j = i + 1;
+
+				while j < codes.len() {
+					assert!(codes[i] != codes[j], #duplicate_message);
+					j += 1;
+				}
+
+				i += 1;
+			}
+		};
+	}
+}
+
+/// Converts a variant name such as `MessageCreate` to `MESSAGE_CREATE`.
+fn screaming_snake_case(ident: &str) -> String {
+	let mut name = String::new();
+
+	for (i, c) in ident.chars().enumerate() {
+		if c.is_uppercase() && i > 0 {
+			name.push('_');
+		}
+
+		name.push(c.to_ascii_uppercase());
+	}
+
+	name
+}
+
+fn is_internal(attrs: &[syn::Attribute]) -> syn::Result<bool> {
+	let mut internal = false;
+
+	for attr in attrs.iter().filter(|attr| attr.path().is_ident(EVENT_ATTR)) {
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("internal") {
+				internal = true;
+				Ok(())
+			} else {
+				Err(meta.error("Expect `internal`"))
+			}
+		})?;
+	}
+
+	Ok(internal)
+}
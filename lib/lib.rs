@@ -1,3 +1,5 @@
+mod command;
+mod event;
 mod opcode;
 
 use syn::DeriveInput;
@@ -10,3 +12,21 @@ pub fn opcode_derive_opcode(
 
 	opcode::expand(&ast).into()
 }
+
+#[proc_macro_derive(GatewayEvent, attributes(event))]
+pub fn event_derive_gateway_event(
+	input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	let ast: DeriveInput = syn::parse(input).unwrap();
+
+	event::expand(&ast).into()
+}
+
+#[proc_macro_derive(GatewayCommand, attributes(opcode))]
+pub fn command_derive_gateway_command(
+	input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+	let ast: DeriveInput = syn::parse(input).unwrap();
+
+	command::expand(&ast).into()
+}
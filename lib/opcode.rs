@@ -1,84 +1,115 @@
 use proc_macro2::{Span, TokenStream};
-use quote::{quote, ToTokens};
+use quote::quote;
+use syn::{punctuated::Punctuated, Token};
 
-const OPCODE_ATTR: &str = "opcode";
+pub const OPCODE_ATTR: &str = "opcode";
+
+/// Contents of an `#[opcode(value = "Opcode::X", code = 7)]` attribute.
+pub struct OpcodeAttr {
+	/// Path of the `Opcode` variant
+	pub value: syn::Path,
+	/// Number the opcode is sent as
+	pub code: u8,
+	/// Span of the code, for errors about it
+	pub code_span: Span,
+}
+
+impl OpcodeAttr {
+	/// Fails to compile if `code` is not the discriminant of `value`, so the
+	/// numbers written next to events cannot drift from the `Opcode` enum.
+	pub fn assert_matches(&self, name: &syn::Ident) -> TokenStream {
+		let OpcodeAttr { value, code, .. } = self;
+		let message = format!(
+			"code of {} does not match {}",
+			name,
+			quote!(#value).to_string().replace(' ', "")
+		);
+
+		quote! {
+			const _: () = assert!(#value as u8 == #code, #message);
+		}
+	}
+}
 
 pub fn expand(ast: &syn::DeriveInput) -> TokenStream {
-	let item_value = match get_attribute_value_multiple(ast, OPCODE_ATTR) {
-		Ok(val) => match val.len() {
-			1 => val[0].clone(),
-			_ => {
-				return syn::Error::new(
-					Span::call_site(),
-					format!(
-						"#[{}(value)] takes 1 parameters, given {}",
-						OPCODE_ATTR,
-						val.len()
-					),
-				)
-				.to_compile_error()
-			}
-		},
+	let attr = match parse_attr(&ast.attrs, Span::call_site()) {
+		Ok(attr) => attr,
 		Err(err) => return err.to_compile_error(),
 	};
 
 	let name = &ast.ident;
 	let (impl_generics, ty_generics, where_clause) =
 		ast.generics.split_for_impl();
-
-	let item_value = item_value
-		.map(ToTokens::into_token_stream)
-		.unwrap_or_else(|| quote! { () });
+	let OpcodeAttr { value, code, .. } = &attr;
+	let assertion = attr.assert_matches(name);
 
 	quote! {
 		impl #impl_generics HasOpcode for #name #ty_generics #where_clause {
+			const CODE: u8 = #code;
+
 			fn opcode() -> Opcode {
-				#item_value
+				#value
 			}
 		}
+
+		#assertion
 	}
 }
 
-fn get_attribute_value_multiple(
-	ast: &syn::DeriveInput, name: &str,
-) -> syn::Result<Vec<Option<syn::Type>>> {
-	let attr = ast
-		.attrs
+pub fn parse_attr(
+	attrs: &[syn::Attribute], span: Span,
+) -> syn::Result<OpcodeAttr> {
+	let attr = attrs
 		.iter()
-		.find_map(|attr| {
-			if attr.path().is_ident(name) {
-				attr.parse_args().ok()
-			} else {
-				None
-			}
-		})
+		.find(|attr| attr.path().is_ident(OPCODE_ATTR))
 		.ok_or_else(|| {
 			syn::Error::new(
-				Span::call_site(),
-				format!("Expect an attribute `{name}`"),
+				span,
+				format!("Expect an attribute `{OPCODE_ATTR}`"),
 			)
 		})?;
+	let args = attr.parse_args_with(
+		Punctuated::<syn::MetaNameValue, Token![,]>::parse_terminated,
+	)?;
+	let mut value = None;
+	let mut code = None;
 
-	match attr {
-		syn::Meta::NameValue(ref nv) => match nv.path.get_ident() {
-			Some(ident) if ident == "value" => {
-				if let syn::Expr::Lit(syn::ExprLit {
-					lit: syn::Lit::Str(lit),
-					..
-				}) = nv.value.clone()
-				{
-					if let Ok(ty) = syn::parse_str::<syn::Type>(&lit.value()) {
-						return Ok(vec![Some(ty)]);
-					}
-				}
-				Err(syn::Error::new_spanned(&nv.value, "Expect type"))
+	for nv in args {
+		let lit = match &nv.value {
+			syn::Expr::Lit(syn::ExprLit { lit, .. }) => lit,
+			_ => {
+				return Err(syn::Error::new_spanned(
+					&nv.value,
+					"Expect a literal",
+				))
 			}
-			_ => Err(syn::Error::new_spanned(
-				&nv.value,
-				r#"Expect `value = "TYPE"`"#,
-			)),
-		},
+		};
 
-		_ => Err(syn::Error::new_spanned(&attr, "Expect a string literal")),
+		match (nv.path.get_ident(), lit) {
+			(Some(ident), syn::Lit::Str(lit)) if ident == "value" => {
+				value = Some(lit.parse::<syn::Path>()?);
+			}
+			(Some(ident), syn::Lit::Int(lit)) if ident == "code" => {
+				code = Some((lit.base10_parse::<u8>()?, lit.span()));
+			}
+			_ => {
+				return Err(syn::Error::new_spanned(
+					&nv,
+					r#"Expect `value = "Opcode::VARIANT"` or `code = NUMBER`"#,
+				))
+			}
+		}
+	}
+
+	match (value, code) {
+		(Some(value), Some((code, code_span))) => {
+			Ok(OpcodeAttr { value, code, code_span })
+		}
+		_ => Err(syn::Error::new_spanned(
+			attr,
+			format!(
+				r#"#[{OPCODE_ATTR}] takes `value = "Opcode::VARIANT"` and `code = NUMBER`"#
+			),
+		)),
 	}
 }
@@ -0,0 +1,12 @@
+use derives::GatewayCommand;
+use shiki_client::commands::TypingStart;
+
+#[derive(GatewayCommand)]
+enum Command {
+	#[opcode(value = "Opcode::TypingStart", code = 6)]
+	TypingStart(TypingStart),
+	#[opcode(value = "Opcode::TypingStart", code = 6)]
+	AlsoTypingStart(TypingStart),
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use derives::GatewayEvent;
+use shiki_client::events::{HasOpcode, Opcode, TypingStart, TypingStop};
+
+#[derive(GatewayEvent)]
+enum Event {
+	TypingStart(TypingStart),
+	TypingStop(TypingStop),
+	AlsoTypingStart(TypingStart),
+}
+
+fn main() {}
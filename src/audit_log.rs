@@ -0,0 +1,93 @@
+use crate::{
+	models::{AuditLogChange, AuditLogEntry},
+	redis::RedisFetcher,
+};
+use actix_web::FromRequest;
+use futures_util::lock::Mutex;
+use percent_encoding::percent_decode;
+use serde::Serialize;
+use serde_json::Value;
+use snowflake::SnowflakeIdGenerator;
+use std::{collections::BTreeSet, future::ready};
+
+/// Name of the header moderators can explain their actions with.
+pub const REASON_HEADER: &str = "X-Audit-Log-Reason";
+
+/// Reasons longer than this are cut off.
+pub const MAX_REASON_LENGTH: usize = 512;
+
+/// The reason given for an action through the `X-Audit-Log-Reason` header,
+/// percent-decoded.
+#[derive(Debug, Clone, Default)]
+pub struct Reason(pub Option<String>);
+
+impl FromRequest for Reason {
+	type Error = actix_web::Error;
+	type Future = std::future::Ready<Result<Self, Self::Error>>;
+
+	fn from_request(
+		req: &actix_web::HttpRequest, _: &mut actix_web::dev::Payload,
+	) -> Self::Future {
+		let header = match req.headers().get(REASON_HEADER) {
+			Some(header) => header,
+			None => return ready(Ok(Reason(None))),
+		};
+
+		// Header values can only hold ASCII safely, so clients percent-encode
+		// reasons. Raw UTF-8 is accepted too, and invalid bytes are replaced.
+		let reason = percent_decode(header.as_bytes()).decode_utf8_lossy();
+		let reason = reason.trim();
+
+		if reason.is_empty() {
+			return ready(Ok(Reason(None)));
+		}
+
+		ready(Ok(Reason(Some(
+			reason.chars().take(MAX_REASON_LENGTH).collect(),
+		))))
+	}
+}
+
+/// Lists the fields which differ between two versions of an object. A missing
+/// `before` records a creation, a missing `after` records a deletion.
+pub fn diff<T: Serialize>(
+	before: Option<&T>, after: Option<&T>,
+) -> Vec<AuditLogChange> {
+	let before = to_fields(before);
+	let after = to_fields(after);
+
+	before
+		.keys()
+		.chain(after.keys())
+		.collect::<BTreeSet<_>>()
+		.into_iter()
+		.filter(|key| before.get(*key) != after.get(*key))
+		.map(|key| AuditLogChange {
+			key: key.clone(),
+			old_value: before.get(key).cloned(),
+			new_value: after.get(key).cloned(),
+		})
+		.collect()
+}
+
+fn to_fields<T: Serialize>(
+	value: Option<&T>,
+) -> serde_json::Map<String, Value> {
+	match value.map(serde_json::to_value) {
+		Some(Ok(Value::Object(fields))) => fields,
+		_ => serde_json::Map::new(),
+	}
+}
+
+/// Records an action in the audit log. Failures are logged rather than
+/// failing the action, which has already been taken.
+pub async fn record(
+	fetcher: &RedisFetcher, snowflake_gen: &Mutex<SnowflakeIdGenerator>,
+	mut entry: AuditLogEntry,
+) {
+	entry.id = snowflake_gen.lock().await.real_time_generate();
+
+	if let Err(err) = fetcher.insert_audit_log(entry).await {
+		log::error!("Failed to record audit log entry: {:?}", err);
+	}
+}
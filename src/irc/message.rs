@@ -0,0 +1,183 @@
+// Numeric replies of RFC 1459 and RFC 2812 which the server sends.
+pub const RPL_WELCOME: &str = "001";
+pub const RPL_YOURHOST: &str = "002";
+pub const RPL_CREATED: &str = "003";
+pub const RPL_MYINFO: &str = "004";
+pub const RPL_UMODEIS: &str = "221";
+pub const RPL_ENDOFWHO: &str = "315";
+pub const RPL_LISTSTART: &str = "321";
+pub const RPL_LIST: &str = "322";
+pub const RPL_LISTEND: &str = "323";
+pub const RPL_CHANNELMODEIS: &str = "324";
+pub const RPL_NOTOPIC: &str = "331";
+pub const RPL_TOPIC: &str = "332";
+pub const RPL_NAMREPLY: &str = "353";
+pub const RPL_ENDOFNAMES: &str = "366";
+pub const ERR_NOSUCHCHANNEL: &str = "403";
+pub const ERR_CANNOTSENDTOCHAN: &str = "404";
+pub const ERR_NORECIPIENT: &str = "411";
+pub const ERR_NOTEXTTOSEND: &str = "412";
+pub const ERR_UNKNOWNCOMMAND: &str = "421";
+pub const ERR_NOMOTD: &str = "422";
+pub const ERR_NOTONCHANNEL: &str = "442";
+pub const ERR_NOTREGISTERED: &str = "451";
+pub const ERR_NEEDMOREPARAMS: &str = "461";
+pub const ERR_ALREADYREGISTRED: &str = "462";
+pub const ERR_PASSWDMISMATCH: &str = "464";
+pub const ERR_CHANOPRIVSNEEDED: &str = "482";
+
+/// A line sent by an IRC client, such as `PRIVMSG #general :hello there`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IrcMessage {
+	/// The command, in upper case
+	pub command: String,
+	/// The parameters, including the trailing one
+	pub params: Vec<String>,
+}
+
+impl IrcMessage {
+	/// Parses a line, returning None if it is empty. Tags and prefixes sent by
+	/// clients are ignored.
+	pub fn parse(line: &str) -> Option<Self> {
+		let mut line = line.trim_end_matches(['\r', '\n']);
+
+		if line.starts_with('@') {
+			line = line.split_once(' ')?.1.trim_start();
+		}
+
+		if line.starts_with(':') {
+			line = line.split_once(' ')?.1.trim_start();
+		}
+
+		let (middle, trailing) = match line.split_once(" :") {
+			Some((middle, trailing)) => (middle, Some(trailing)),
+			None => (line, None),
+		};
+		let mut words = middle.split(' ').filter(|word| !word.is_empty());
+		let command = words.next()?.to_ascii_uppercase();
+		let params = words
+			.map(str::to_owned)
+			.chain(trailing.map(str::to_owned))
+			.collect();
+
+		Some(IrcMessage { command, params })
+	}
+
+	/// The parameter at `index`, if it was sent.
+	pub fn param(&self, index: usize) -> Option<&str> {
+		self.params.get(index).map(String::as_str)
+	}
+}
+
+/// Turns a shiki username into a valid nickname. Characters IRC does not
+/// allow in nicknames are replaced with underscores.
+pub fn nickname(username: &str) -> String {
+	let nick = username
+		.chars()
+		.map(|c| match c {
+			' ' | ',' | '*' | '?' | '!' | '@' | '.' | ':' | '#' | '&' => '_',
+			c if c.is_control() => '_',
+			c => c,
+		})
+		.collect::<String>();
+
+	if nick.is_empty() || nick.starts_with(|c: char| c.is_ascii_digit()) {
+		format!("_{nick}")
+	} else {
+		nick
+	}
+}
+
+/// Turns a shiki channel name into an IRC channel name, such as
+/// `#general-chat`.
+pub fn channel_name(name: &str) -> String {
+	let name = name
+		.chars()
+		.map(|c| match c {
+			' ' | ',' | '\x07' => '-',
+			c if c.is_control() => '-',
+			c => c.to_ascii_lowercase(),
+		})
+		.collect::<String>();
+
+	format!("#{name}")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(line: &str) -> (String, Vec<String>) {
+		let message = IrcMessage::parse(line).unwrap();
+		(message.command, message.params)
+	}
+
+	#[test]
+	fn trailing_param() {
+		let (command, params) = parse("privmsg #general :hello there\r\n");
+
+		assert_eq!(command, "PRIVMSG");
+		assert_eq!(params, ["#general", "hello there"]);
+	}
+
+	#[test]
+	fn trailing_param_keeps_colons() {
+		let (_, params) = parse("PRIVMSG #general ::) see: this");
+
+		assert_eq!(params, ["#general", ":) see: this"]);
+	}
+
+	#[test]
+	fn empty_trailing_param() {
+		let (_, params) = parse("TOPIC #general :");
+
+		assert_eq!(params, ["#general", ""]);
+	}
+
+	#[test]
+	fn repeated_spaces_between_params() {
+		let (command, params) = parse("MODE  #general   +t");
+
+		assert_eq!(command, "MODE");
+		assert_eq!(params, ["#general", "+t"]);
+	}
+
+	#[test]
+	fn prefix_and_tags_are_ignored() {
+		assert_eq!(parse(":nick!user@host JOIN #general").1, ["#general"]);
+		assert_eq!(
+			parse("@time=now :nick PRIVMSG #general :hi"),
+			parse("PRIVMSG #general :hi")
+		);
+	}
+
+	#[test]
+	fn empty_lines() {
+		for line in ["", "\r\n", "   ", ":nick", "@time=now", ":nick  "] {
+			assert_eq!(IrcMessage::parse(line), None, "{line:?}");
+		}
+	}
+
+	#[test]
+	fn param_by_index() {
+		let message = IrcMessage::parse("USER name 0 * :Real Name").unwrap();
+
+		assert_eq!(message.param(0), Some("name"));
+		assert_eq!(message.param(3), Some("Real Name"));
+		assert_eq!(message.param(4), None);
+	}
+
+	#[test]
+	fn nicknames() {
+		assert_eq!(nickname("some user"), "some_user");
+		assert_eq!(nickname("a@b.c"), "a_b_c");
+		assert_eq!(nickname("1337"), "_1337");
+		assert_eq!(nickname(""), "_");
+	}
+
+	#[test]
+	fn channel_names() {
+		assert_eq!(channel_name("General Chat"), "#general-chat");
+		assert_eq!(channel_name("a,b"), "#a-b");
+	}
+}
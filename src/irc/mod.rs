@@ -0,0 +1,34 @@
+pub mod message;
+pub mod session;
+
+use crate::{redis::RedisFetcher, ws::server::ShikiServer};
+use actix::Addr;
+use futures_util::lock::Mutex;
+use session::IrcSession;
+use snowflake::SnowflakeIdGenerator;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+/// Name the server introduces itself with to IRC clients
+pub const SERVER_NAME: &str = "shiki";
+
+/// Accepts IRC clients, each of them is handled by an `IrcSession`.
+pub async fn listen(
+	listener: TcpListener, addr: Addr<ShikiServer>, fetcher: RedisFetcher,
+	snowflake_gen: Arc<Mutex<SnowflakeIdGenerator>>,
+) {
+	loop {
+		match listener.accept().await {
+			Ok((stream, peer)) => {
+				log::debug!("IRC client connected from {}", peer);
+				IrcSession::start(
+					stream,
+					addr.clone(),
+					fetcher.clone(),
+					snowflake_gen.clone(),
+				);
+			}
+			Err(e) => log::error!("Failed to accept IRC client: {}", e),
+		}
+	}
+}
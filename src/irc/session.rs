@@ -0,0 +1,976 @@
+use super::{
+	message::{self, channel_name, nickname, IrcMessage},
+	SERVER_NAME,
+};
+use crate::{
+	audit_log, mentions,
+	models::{self, AuditLogAction, AuditLogEntry, ChannelType, Message},
+	permissions::{self, Permissions},
+	redis::{ModifyChannel, RedisFetcher},
+	routes::middleware::{self, CREATE_MESSAGE_BUCKET},
+	utils,
+	ws::{
+		commands,
+		events::Event,
+		intents::Intents,
+		server::{self, ShikiServer},
+	},
+};
+use actix::prelude::*;
+use chrono::Utc;
+use futures::{channel::mpsc, stream, Stream, StreamExt};
+use futures_util::lock::Mutex;
+use shiki_client::model::Channel;
+use snowflake::SnowflakeIdGenerator;
+use std::{
+	collections::{HashMap, HashSet},
+	sync::Arc,
+	time::{Duration, Instant},
+};
+use tokio::{
+	io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+	net::{tcp::OwnedReadHalf, TcpStream},
+};
+
+/// How often the client is pinged
+const PING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long before lack of client response causes a timeout
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long clients have to send PASS, NICK and USER
+const REGISTRATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest line read from a client, in bytes. Longer lines are split.
+const MAX_LINE_LENGTH: u64 = 4096;
+
+/// Nicknames sent in a single NAMES reply line
+const NAMES_PER_LINE: usize = 20;
+
+/// A channel the user can see.
+#[derive(Debug, Clone)]
+struct IrcChannel {
+	id: i64,
+	guild_id: Option<i64>,
+	name: String,
+	topic: Option<String>,
+}
+
+impl From<&Channel> for IrcChannel {
+	fn from(channel: &Channel) -> Self {
+		IrcChannel {
+			id: channel.id,
+			guild_id: channel.guild_id,
+			name: channel.name.clone(),
+			topic: channel.description.clone(),
+		}
+	}
+}
+
+/// Connection of an IRC client. Once the client registered with a shiki token
+/// as its password, it is connected to `ShikiServer` like a gateway session,
+/// and the events of the channels it joined are relayed to it.
+pub struct IrcSession {
+	/// unique session id, once registered
+	session_id: Option<usize>,
+	/// When the client last sent a line
+	hb: Instant,
+	/// Chat server
+	addr: Addr<ShikiServer>,
+	fetcher: RedisFetcher,
+	snowflake_gen: Arc<Mutex<SnowflakeIdGenerator>>,
+	/// Lines written to the client
+	tx: mpsc::UnboundedSender<String>,
+	/// Token sent with PASS
+	password: Option<String>,
+	/// Nickname sent with NICK, replaced with the user's once registered
+	nick: String,
+	/// Whether USER was sent
+	sent_user: bool,
+	/// The authenticated user
+	user: Option<models::User>,
+	/// Channels the user can see, by ID
+	channels: HashMap<i64, IrcChannel>,
+	/// Channels the client joined
+	joined: HashSet<i64>,
+	/// Nicknames collected for NAMES replies, by channel ID
+	names: HashMap<i64, Vec<String>>,
+}
+
+impl IrcSession {
+	/// Starts a session reading from and writing to `stream`.
+	pub fn start(
+		stream: TcpStream, addr: Addr<ShikiServer>, fetcher: RedisFetcher,
+		snowflake_gen: Arc<Mutex<SnowflakeIdGenerator>>,
+	) -> Addr<Self> {
+		let (read, mut write) = stream.into_split();
+		let (tx, mut rx) = mpsc::unbounded::<String>();
+
+		actix::spawn(async move {
+			while let Some(line) = rx.next().await {
+				if write.write_all(line.as_bytes()).await.is_err() {
+					break;
+				}
+			}
+		});
+
+		IrcSession::create(|ctx| {
+			ctx.add_stream(read_lines(read));
+
+			IrcSession {
+				session_id: None,
+				hb: Instant::now(),
+				addr,
+				fetcher,
+				snowflake_gen,
+				tx,
+				password: None,
+				nick: "*".to_owned(),
+				sent_user: false,
+				user: None,
+				channels: HashMap::new(),
+				joined: HashSet::new(),
+				names: HashMap::new(),
+			}
+		})
+	}
+
+	/// helper method that pings the client every PING_INTERVAL, and
+	/// disconnects it if it did not send anything for CLIENT_TIMEOUT.
+	fn heartbeat(&self, ctx: &mut Context<Self>) {
+		ctx.run_interval(PING_INTERVAL, |act, ctx| {
+			if Instant::now().duration_since(act.hb) > CLIENT_TIMEOUT {
+				log::debug!("IRC client heartbeat failed, disconnecting!");
+				return act.quit(ctx, "Ping timeout");
+			}
+
+			act.send(format!("PING :{SERVER_NAME}"));
+		});
+	}
+
+	fn check_registration(&self, ctx: &mut Context<Self>) {
+		ctx.run_later(REGISTRATION_TIMEOUT, |act, ctx| {
+			if act.user.is_none() {
+				act.quit(ctx, "Registration timeout");
+			}
+		});
+	}
+
+	fn send(&self, line: String) {
+		let _ = self.tx.unbounded_send(line + "\r\n");
+	}
+
+	/// Sends a numeric reply, addressed to the client's nickname.
+	fn reply(&self, numeric: &str, text: &str) {
+		self.send(format!(":{SERVER_NAME} {numeric} {} {text}", self.nick));
+	}
+
+	fn quit(&self, ctx: &mut Context<Self>, reason: &str) {
+		self.send(format!("ERROR :Closing link ({reason})"));
+		ctx.stop();
+	}
+
+	/// The prefix of lines the user is the source of.
+	fn prefix(&self) -> String {
+		let id = self.user.as_ref().map_or(0, |user| user.id);
+
+		format!("{}!{}@{SERVER_NAME}", self.nick, id)
+	}
+
+	/// The name a channel is shown as. Channels whose names are shared with
+	/// older channels get their ID appended.
+	fn irc_name(&self, channel: &IrcChannel) -> String {
+		let name = channel_name(&channel.name);
+		let shadowed = self.channels.values().any(|other| {
+			other.id < channel.id && channel_name(&other.name) == name
+		});
+
+		if shadowed {
+			format!("{name}-{}", channel.id)
+		} else {
+			name
+		}
+	}
+
+	fn find_channel(&self, name: &str) -> Option<&IrcChannel> {
+		self.channels
+			.values()
+			.find(|channel| self.irc_name(channel).eq_ignore_ascii_case(name))
+	}
+
+	/// Validates the token sent with PASS once NICK and USER were sent too.
+	fn try_register(&mut self, ctx: &mut Context<Self>) {
+		if self.nick == "*" || !self.sent_user {
+			return;
+		}
+
+		let token = match self.password.clone() {
+			Some(token) => token,
+			None => {
+				self.reply(
+					message::ERR_PASSWDMISMATCH,
+					":Send your shiki token with PASS",
+				);
+				return self.quit(ctx, "Bad password");
+			}
+		};
+
+		utils::validate_token(self.fetcher.clone(), token)
+			.into_actor(self)
+			.then(|res, act, ctx| {
+				match res {
+					Ok(Some(user)) => act.register(user, ctx),
+					Ok(None) => {
+						act.reply(
+							message::ERR_PASSWDMISMATCH,
+							":Password incorrect",
+						);
+						act.quit(ctx, "Bad password");
+					}
+					Err(e) => {
+						log::error!("Failed to validate token: {}", e);
+						act.quit(ctx, "Something went wrong");
+					}
+				}
+				fut::ready(())
+			})
+			.wait(ctx);
+	}
+
+	/// Connects the session to the chat server as the user. The client is
+	/// welcomed once the Ready event arrives.
+	fn register(&mut self, user: models::User, ctx: &mut Context<Self>) {
+		// Nicknames follow usernames, so messages are attributed correctly.
+		let nick = nickname(&user.username);
+
+		if nick != self.nick {
+			self.send(format!(":{} NICK {nick}", self.nick));
+			self.nick = nick;
+		}
+
+		self.user = Some(user);
+
+		self.addr
+			.send(server::Connect { addr: ctx.address().recipient() })
+			.into_actor(self)
+			.then(|res, act, ctx| {
+				match (res, act.password.clone()) {
+					(Ok(id), Some(token)) => {
+						act.session_id = Some(id);
+						act.addr.do_send(server::Identify {
+							id,
+							token,
+							intents: Intents::default(),
+						});
+					}
+					// something is wrong with chat server
+					_ => ctx.stop(),
+				}
+				fut::ready(())
+			})
+			.wait(ctx);
+	}
+
+	fn welcome(&self) {
+		let nick = &self.nick;
+		let version = env!("CARGO_PKG_VERSION");
+
+		self.reply(message::RPL_WELCOME, &format!(":Welcome to shiki, {nick}"));
+		self.reply(
+			message::RPL_YOURHOST,
+			&format!(":Your host is {SERVER_NAME}, running version {version}"),
+		);
+		self.reply(
+			message::RPL_CREATED,
+			":This server speaks a subset of the IRC client protocol",
+		);
+		self.reply(
+			message::RPL_MYINFO,
+			&format!("{SERVER_NAME} {version} i nt"),
+		);
+		self.reply(message::ERR_NOMOTD, ":MOTD File is missing");
+	}
+
+	fn handle_message(&mut self, msg: IrcMessage, ctx: &mut Context<Self>) {
+		let registered = self.user.is_some();
+
+		match msg.command.as_str() {
+			"CAP" => match msg.param(0) {
+				// There are no capabilities to negotiate.
+				Some("LS") | Some("LIST") => {
+					self.send(format!(
+						":{SERVER_NAME} CAP * {} :",
+						msg.params[0]
+					));
+				}
+				Some("REQ") => self.send(format!(
+					":{SERVER_NAME} CAP * NAK :{}",
+					msg.param(1).unwrap_or_default()
+				)),
+				_ => (),
+			},
+			"PING" => self.send(format!(
+				":{SERVER_NAME} PONG {SERVER_NAME} :{}",
+				msg.param(0).unwrap_or(SERVER_NAME)
+			)),
+			"PONG" => (),
+			"QUIT" => self.quit(ctx, msg.param(0).unwrap_or("Quit")),
+			"PASS" | "USER" if registered => {
+				self.reply(
+					message::ERR_ALREADYREGISTRED,
+					":You may not reregister",
+				);
+			}
+			"PASS" => self.password = msg.param(0).map(str::to_owned),
+			// Nicknames follow usernames once registered.
+			"NICK" if registered => (),
+			"NICK" => match msg.param(0) {
+				Some(nick) => {
+					self.nick = nick.to_owned();
+					self.try_register(ctx);
+				}
+				None => self.need_more_params(&msg),
+			},
+			"USER" => {
+				if msg.params.len() < 4 {
+					return self.need_more_params(&msg);
+				}
+
+				self.sent_user = true;
+				self.try_register(ctx);
+			}
+			_ if !registered => {
+				self.reply(
+					message::ERR_NOTREGISTERED,
+					":You have not registered",
+				);
+			}
+			"JOIN" => self.join(&msg),
+			"PART" => self.part(&msg),
+			"PRIVMSG" => self.privmsg(&msg, ctx),
+			"NAMES" => self.names(&msg),
+			"LIST" => self.list(),
+			"TOPIC" => self.topic(&msg, ctx),
+			"MODE" => match msg.param(0) {
+				Some(target) if target.starts_with('#') => self.reply(
+					message::RPL_CHANNELMODEIS,
+					&format!("{target} +nt"),
+				),
+				Some(_) => self.reply(message::RPL_UMODEIS, "+"),
+				None => self.need_more_params(&msg),
+			},
+			"WHO" => self.reply(
+				message::RPL_ENDOFWHO,
+				&format!("{} :End of WHO list", msg.param(0).unwrap_or("*")),
+			),
+			command => self.reply(
+				message::ERR_UNKNOWNCOMMAND,
+				&format!("{command} :Unknown command"),
+			),
+		}
+	}
+
+	fn need_more_params(&self, msg: &IrcMessage) {
+		self.reply(
+			message::ERR_NEEDMOREPARAMS,
+			&format!("{} :Not enough parameters", msg.command),
+		);
+	}
+
+	fn join(&mut self, msg: &IrcMessage) {
+		let names = match msg.param(0) {
+			Some("0") => {
+				let joined = self.joined.iter().copied().collect::<Vec<_>>();
+
+				for id in joined {
+					self.leave(id, "Left all channels");
+				}
+
+				return;
+			}
+			Some(names) => names,
+			None => return self.need_more_params(msg),
+		};
+
+		for name in names.split(',') {
+			let channel = match self.find_channel(name) {
+				Some(channel) => channel.clone(),
+				None => {
+					self.reply(
+						message::ERR_NOSUCHCHANNEL,
+						&format!("{name} :No such channel"),
+					);
+					continue;
+				}
+			};
+
+			if !self.joined.insert(channel.id) {
+				continue;
+			}
+
+			self.send(format!(
+				":{} JOIN {}",
+				self.prefix(),
+				self.irc_name(&channel)
+			));
+			self.send_topic(&channel);
+			self.request_names(&channel);
+		}
+	}
+
+	fn leave(&mut self, id: i64, reason: &str) {
+		if !self.joined.remove(&id) {
+			return;
+		}
+
+		if let Some(channel) = self.channels.get(&id) {
+			self.send(format!(
+				":{} PART {} :{reason}",
+				self.prefix(),
+				self.irc_name(channel)
+			));
+		}
+	}
+
+	/// Finds a channel the client joined, replying with an error if it did
+	/// not.
+	fn find_joined(&self, name: &str) -> Option<IrcChannel> {
+		match self.find_channel(name) {
+			Some(channel) if self.joined.contains(&channel.id) => {
+				Some(channel.clone())
+			}
+			Some(_) => {
+				self.reply(
+					message::ERR_NOTONCHANNEL,
+					&format!("{name} :You're not on that channel"),
+				);
+				None
+			}
+			None => {
+				self.reply(
+					message::ERR_NOSUCHCHANNEL,
+					&format!("{name} :No such channel"),
+				);
+				None
+			}
+		}
+	}
+
+	fn part(&mut self, msg: &IrcMessage) {
+		let names = match msg.param(0) {
+			Some(names) => names,
+			None => return self.need_more_params(msg),
+		};
+		let reason = msg.param(1).unwrap_or("Leaving");
+
+		for name in names.split(',') {
+			if let Some(channel) = self.find_joined(name) {
+				self.leave(channel.id, reason);
+			}
+		}
+	}
+
+	fn send_topic(&self, channel: &IrcChannel) {
+		let name = self.irc_name(channel);
+
+		match &channel.topic {
+			Some(topic) if !topic.is_empty() => {
+				self.reply(message::RPL_TOPIC, &format!("{name} :{topic}"));
+			}
+			_ => self.reply(
+				message::RPL_NOTOPIC,
+				&format!("{name} :No topic is set"),
+			),
+		}
+	}
+
+	/// Asks the chat server for the members who can see a channel. They are
+	/// sent as a NAMES reply once every chunk arrived.
+	fn request_names(&mut self, channel: &IrcChannel) {
+		let session_id = match self.session_id {
+			Some(session_id) => session_id,
+			None => return,
+		};
+
+		self.names.insert(channel.id, Vec::new());
+		self.addr.do_send(server::RequestGuildMembers {
+			id: session_id,
+			request: commands::RequestGuildMembers {
+				guild_id: channel.guild_id,
+				limit: 1000,
+				nonce: Some(channel.id.to_string()),
+				..Default::default()
+			},
+		});
+	}
+
+	fn names(&mut self, msg: &IrcMessage) {
+		let names = match msg.param(0) {
+			Some(names) => names,
+			None => {
+				return self
+					.reply(message::RPL_ENDOFNAMES, "* :End of /NAMES list")
+			}
+		};
+
+		for name in names.split(',') {
+			match self.find_channel(name).cloned() {
+				Some(channel) => self.request_names(&channel),
+				None => self.reply(
+					message::RPL_ENDOFNAMES,
+					&format!("{name} :End of /NAMES list"),
+				),
+			}
+		}
+	}
+
+	fn list(&self) {
+		let mut channels = self
+			.channels
+			.values()
+			.map(|channel| (self.irc_name(channel), channel))
+			.collect::<Vec<_>>();
+
+		channels.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+		self.reply(message::RPL_LISTSTART, "Channel :Users  Name");
+
+		for (name, channel) in channels {
+			let topic = channel.topic.as_deref().unwrap_or_default();
+
+			// Member counts are not tracked per channel.
+			self.reply(message::RPL_LIST, &format!("{name} 0 :{topic}"));
+		}
+
+		self.reply(message::RPL_LISTEND, ":End of /LIST");
+	}
+
+	fn privmsg(&mut self, msg: &IrcMessage, ctx: &mut Context<Self>) {
+		let target = match msg.param(0) {
+			Some(target) => target,
+			None => {
+				return self.reply(
+					message::ERR_NORECIPIENT,
+					":No recipient given (PRIVMSG)",
+				)
+			}
+		};
+		let content = match msg.param(1) {
+			Some(content) if !content.is_empty() => content.to_owned(),
+			_ => {
+				return self
+					.reply(message::ERR_NOTEXTTOSEND, ":No text to send")
+			}
+		};
+
+		// There are no direct messages, only channels can be messaged.
+		let channel = match self.find_channel(target) {
+			Some(channel) if self.joined.contains(&channel.id) => channel,
+			Some(_) => {
+				return self.reply(
+					message::ERR_CANNOTSENDTOCHAN,
+					&format!("{target} :Cannot send to channel"),
+				)
+			}
+			None => {
+				return self.reply(
+					message::ERR_NOSUCHCHANNEL,
+					&format!("{target} :No such channel"),
+				)
+			}
+		};
+		let channel_id = channel.id;
+		let target = target.to_owned();
+
+		post_message(
+			self.fetcher.clone(),
+			self.snowflake_gen.clone(),
+			self.addr.clone(),
+			self.user.clone().unwrap(),
+			channel_id,
+			content,
+		)
+		.into_actor(self)
+		.then(move |res, act, _| {
+			match res {
+				Ok(Posted::Sent) => (),
+				Ok(Posted::Refused) => act.reply(
+					message::ERR_CANNOTSENDTOCHAN,
+					&format!("{target} :Cannot send to channel"),
+				),
+				Ok(Posted::RateLimited) => act.send(format!(
+					":{SERVER_NAME} NOTICE {} :Message to {target} dropped, \
+					 you are sending messages too quickly",
+					act.nick
+				)),
+				Err(e) => {
+					log::error!("Failed to send message: {:?}", e);
+					act.send(format!(
+						":{SERVER_NAME} NOTICE {} :Something went wrong",
+						act.nick
+					));
+				}
+			}
+			fut::ready(())
+		})
+		.spawn(ctx);
+	}
+
+	fn topic(&mut self, msg: &IrcMessage, ctx: &mut Context<Self>) {
+		let name = match msg.param(0) {
+			Some(name) => name,
+			None => return self.need_more_params(msg),
+		};
+		let channel = match self.find_channel(name) {
+			Some(channel) => channel.clone(),
+			None => {
+				return self.reply(
+					message::ERR_NOSUCHCHANNEL,
+					&format!("{name} :No such channel"),
+				)
+			}
+		};
+		let topic = match msg.param(1) {
+			Some(topic) => topic.to_owned(),
+			None => return self.send_topic(&channel),
+		};
+
+		set_topic(
+			self.fetcher.clone(),
+			self.snowflake_gen.clone(),
+			self.addr.clone(),
+			self.user.clone().unwrap(),
+			channel.id,
+			topic.clone(),
+		)
+		.into_actor(self)
+		.then(move |res, act, _| {
+			let name = act.irc_name(&channel);
+
+			match res {
+				Ok(true) => {
+					if let Some(channel) = act.channels.get_mut(&channel.id) {
+						channel.topic = Some(topic.clone());
+					}
+
+					act.send(format!(
+						":{} TOPIC {name} :{topic}",
+						act.prefix()
+					));
+				}
+				Ok(false) => act.reply(
+					message::ERR_CHANOPRIVSNEEDED,
+					&format!("{name} :You're not channel operator"),
+				),
+				Err(e) => {
+					log::error!("Failed to set topic: {:?}", e);
+					act.send(format!(
+						":{SERVER_NAME} NOTICE {} :Something went wrong",
+						act.nick
+					));
+				}
+			}
+			fut::ready(())
+		})
+		.spawn(ctx);
+	}
+
+	/// Sends the nicknames collected for a channel.
+	fn send_names(&mut self, channel_id: i64) {
+		let nicks = self.names.remove(&channel_id).unwrap_or_default();
+		let name = match self.channels.get(&channel_id) {
+			Some(channel) => self.irc_name(channel),
+			None => return,
+		};
+
+		for nicks in nicks.chunks(NAMES_PER_LINE) {
+			self.reply(
+				message::RPL_NAMREPLY,
+				&format!("= {name} :{}", nicks.join(" ")),
+			);
+		}
+
+		self.reply(
+			message::RPL_ENDOFNAMES,
+			&format!("{name} :End of /NAMES list"),
+		);
+	}
+}
+
+/// Reads lines from a client until it disconnects.
+fn read_lines(read: OwnedReadHalf) -> impl Stream<Item = String> {
+	stream::unfold(BufReader::new(read), |mut reader| async move {
+		let mut line = Vec::new();
+
+		match (&mut reader)
+			.take(MAX_LINE_LENGTH)
+			.read_until(b'\n', &mut line)
+			.await
+		{
+			Ok(0) | Err(_) => None,
+			Ok(_) => {
+				Some((String::from_utf8_lossy(&line).into_owned(), reader))
+			}
+		}
+	})
+}
+
+/// Outcome of posting a message from IRC.
+enum Posted {
+	Sent,
+	/// The channel does not exist or the user may not send messages in it.
+	Refused,
+	/// The user is sending messages too quickly.
+	RateLimited,
+}
+
+/// Posts a message to a channel the same way the REST API does, counting it
+/// against the same rate limit.
+async fn post_message(
+	fetcher: RedisFetcher, snowflake_gen: Arc<Mutex<SnowflakeIdGenerator>>,
+	srv: Addr<ShikiServer>, user: models::User, channel_id: i64,
+	content: String,
+) -> anyhow::Result<Posted> {
+	let mut channel = match fetcher.fetch_channel(channel_id).await? {
+		Some(channel) => channel,
+		None => return Ok(Posted::Refused),
+	};
+	let permissions =
+		permissions::in_channel(&fetcher, &user, &channel).await?;
+
+	if !permissions.contains(Permissions::SEND_MESSAGES) {
+		return Ok(Posted::Refused);
+	}
+
+	if !middleware::take_user_token(&fetcher, &CREATE_MESSAGE_BUCKET, user.id)
+		.await
+	{
+		return Ok(Posted::RateLimited);
+	}
+
+	let mut data = server::CreateMessage {
+		id: snowflake_gen.lock().await.real_time_generate(),
+		channel_id,
+		content,
+		author: server::User::from(user.clone()),
+		created_at: Utc::now().timestamp() as usize,
+		mentions: Default::default(),
+		mention_roles: Default::default(),
+		mention_everyone: false,
+		mentioned_users: HashSet::new(),
+	};
+
+	mentions::resolve(&fetcher, &mut data, permissions).await?;
+
+	// Sending a message in a thread unarchives it and makes the author one of
+	// its members.
+	if channel.kind == ChannelType::Thread {
+		let changed =
+			channel.archived || !channel.member_ids.contains(&user.id);
+
+		fetcher.touch_thread(&mut channel, user.id).await?;
+
+		if changed {
+			srv.do_send(server::UpdateChannel { channel: channel.into() });
+		}
+	}
+
+	fetcher.insert_message(Message::from(data.clone())).await?;
+
+	match srv.send(data).await? {
+		Some(_) => Ok(Posted::Sent),
+		None => Ok(Posted::Refused),
+	}
+}
+
+/// Sets the topic of a channel the same way the REST API does, an empty topic
+/// clears it. Returns false if the channel does not exist or the user may not
+/// manage it.
+async fn set_topic(
+	fetcher: RedisFetcher, snowflake_gen: Arc<Mutex<SnowflakeIdGenerator>>,
+	srv: Addr<ShikiServer>, user: models::User, channel_id: i64, topic: String,
+) -> anyhow::Result<bool> {
+	let mut channel = match fetcher.fetch_channel(channel_id).await? {
+		Some(channel) => channel,
+		None => return Ok(false),
+	};
+	let permissions =
+		permissions::in_channel(&fetcher, &user, &channel).await?;
+
+	if !permissions.contains(Permissions::MANAGE_CHANNELS) {
+		return Ok(false);
+	}
+
+	let before = channel.clone();
+	let data = ModifyChannel {
+		name: None,
+		description: Some(Some(topic).filter(|topic| !topic.is_empty())),
+		position: None,
+		parent_id: None,
+		archived: None,
+		auto_archive_duration: None,
+	};
+
+	fetcher.modify_channel(&mut channel, data).await?;
+
+	audit_log::record(
+		&fetcher,
+		&snowflake_gen,
+		AuditLogEntry {
+			target_id: Some(channel.id),
+			changes: audit_log::diff(Some(&before), Some(&channel)),
+			..AuditLogEntry::new(
+				channel.guild_id,
+				user.id,
+				AuditLogAction::ChannelUpdate,
+			)
+		},
+	)
+	.await;
+
+	srv.do_send(server::UpdateChannel { channel: channel.into() });
+
+	Ok(true)
+}
+
+impl Actor for IrcSession {
+	type Context = Context<Self>;
+
+	fn started(&mut self, ctx: &mut Self::Context) {
+		self.heartbeat(ctx);
+		self.check_registration(ctx);
+	}
+
+	fn stopping(&mut self, _: &mut Self::Context) -> Running {
+		// notify chat server
+		if let Some(id) = self.session_id {
+			self.addr.do_send(server::Disconnect { id });
+		}
+
+		Running::Stop
+	}
+}
+
+/// Lines sent by the client
+impl StreamHandler<String> for IrcSession {
+	fn handle(&mut self, line: String, ctx: &mut Self::Context) {
+		self.hb = Instant::now();
+
+		if let Some(msg) = IrcMessage::parse(&line) {
+			log::debug!("IRC client sent {:?}", msg);
+			self.handle_message(msg, ctx);
+		}
+	}
+}
+
+/// Events from the chat server, relayed for the channels the client joined
+impl Handler<Event> for IrcSession {
+	type Result = ();
+
+	fn handle(&mut self, msg: Event, ctx: &mut Self::Context) {
+		match msg {
+			Event::Close(code) => self.quit(ctx, code.description()),
+
+			Event::Ready(ready) => {
+				self.channels = ready
+					.channels
+					.iter()
+					.filter(|channel| channel.kind != ChannelType::Category)
+					.map(|channel| (channel.id, IrcChannel::from(channel)))
+					.collect();
+				self.welcome();
+			}
+
+			Event::ChannelUpdate(update) => {
+				let channel = match self.channels.get(&update.id) {
+					Some(channel) => channel.clone(),
+					None => return,
+				};
+				let joined = self.joined.contains(&update.id);
+
+				if joined && channel.name != update.name {
+					self.leave(update.id, "Channel renamed");
+				}
+
+				let updated = IrcChannel {
+					name: update.name,
+					topic: update.description,
+					..channel.clone()
+				};
+
+				self.channels.insert(update.id, updated.clone());
+
+				if joined && channel.name != updated.name {
+					self.joined.insert(update.id);
+					self.send(format!(
+						":{} JOIN {}",
+						self.prefix(),
+						self.irc_name(&updated)
+					));
+					self.send_topic(&updated);
+				} else if joined && channel.topic != updated.topic {
+					self.send(format!(
+						":{SERVER_NAME} TOPIC {} :{}",
+						self.irc_name(&updated),
+						updated.topic.as_deref().unwrap_or_default()
+					));
+				}
+			}
+
+			Event::ChannelDelete(delete) => {
+				self.leave(delete.id, "Channel deleted");
+				self.channels.remove(&delete.id);
+			}
+
+			Event::MessageCreate(message) => {
+				let own = self.user.as_ref().map(|user| user.id);
+
+				if !self.joined.contains(&message.channel_id)
+					|| own == Some(message.author.id)
+				{
+					return;
+				}
+
+				let name = match self.channels.get(&message.channel_id) {
+					Some(channel) => self.irc_name(channel),
+					None => return,
+				};
+				let prefix = format!(
+					"{}!{}@{SERVER_NAME}",
+					nickname(&message.author.username),
+					message.author.id
+				);
+
+				for line in message.content.lines().filter(|l| !l.is_empty()) {
+					self.send(format!(":{prefix} PRIVMSG {name} :{line}"));
+				}
+			}
+
+			Event::GuildMembersChunk(chunk) => {
+				let channel_id = match chunk
+					.nonce
+					.as_deref()
+					.and_then(|nonce| nonce.parse::<i64>().ok())
+				{
+					Some(channel_id) => channel_id,
+					None => return,
+				};
+
+				if let Some(names) = self.names.get_mut(&channel_id) {
+					names.extend(
+						chunk
+							.members
+							.iter()
+							.map(|user| nickname(&user.username)),
+					);
+				}
+
+				if chunk.chunk_index + 1 == chunk.chunk_count {
+					self.send_names(channel_id);
+				}
+			}
+
+			_ => (),
+		}
+	}
+}
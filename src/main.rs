@@ -1,4 +1,7 @@
-use crate::{redis::RedisFetcher, ws::server::ShikiServer};
+use crate::{
+	redis::RedisFetcher,
+	ws::{server::ShikiServer, stream::Streams},
+};
 use actix::*;
 use actix_cors::Cors;
 use actix_session::{
@@ -27,17 +30,23 @@ use std::{
 	sync::{atomic::AtomicUsize, Arc},
 	time::{Duration, UNIX_EPOCH},
 };
+use tokio::net::TcpListener;
 use webrtc_unreliable::Server;
 
+mod audit_log;
 mod errors;
+mod irc;
+mod mentions;
 mod models;
 mod opus;
 mod opusfile;
+mod permissions;
 mod redis;
 mod routes;
 mod rtc;
 mod speexdsp;
 mod utils;
+mod version;
 mod ws;
 
 #[actix_web::main]
@@ -101,10 +110,27 @@ async fn main() -> std::io::Result<()> {
 		.expect("RTC_PUBLIC_ADDR must be set")
 		.parse::<SocketAddr>()
 		.expect("RTC_PUBLIC_ADDR must be a valid socket address");
+	let streams = Arc::new(Streams::default());
 	let webrtc_server = Server::new(listen_socket, public_addr).await?;
 	let session_endpoint =
 		web::Data::new(Mutex::new(webrtc_server.session_endpoint()));
 
+	// The IRC front-end is only started if an address to listen on is given.
+	if let Ok(irc_addr) = env::var("IRC_ADDR") {
+		let irc_addr = irc_addr
+			.parse::<SocketAddr>()
+			.expect("IRC_ADDR must be a valid socket address");
+		let listener = TcpListener::bind(irc_addr).await?;
+
+		log::info!("starting IRC server at {}", irc_addr);
+		actix::spawn(irc::listen(
+			listener,
+			server.clone(),
+			redis_fetcher.clone(),
+			snowflake_gen.clone(),
+		));
+	}
+
 	log::info!("starting HTTP server at http://localhost:8080");
 
 	let http_fut = HttpServer::new(move || {
@@ -112,19 +138,37 @@ async fn main() -> std::io::Result<()> {
 			.allowed_origin(
 				&env::var("CLIENT_URL").expect("CLIENT_URL must be set"),
 			)
-			.allowed_methods(vec!["GET", "POST", "PATCH", "DELETE"])
+			.allowed_methods(vec!["GET", "POST", "PUT", "PATCH", "DELETE"])
 			.allowed_headers(vec![
 				http::header::AUTHORIZATION,
 				http::header::ACCEPT,
 			])
+			.allowed_header(http::header::HeaderName::from_static(
+				"last-event-id",
+			))
 			.allowed_header(http::header::CONTENT_TYPE)
-			.expose_headers(&[actix_web::http::header::CONTENT_DISPOSITION])
+			.allowed_header(audit_log::REASON_HEADER)
+			.expose_headers(vec![
+				actix_web::http::header::CONTENT_DISPOSITION,
+				http::header::RETRY_AFTER,
+				http::header::HeaderName::from_static("x-ratelimit-limit"),
+				http::header::HeaderName::from_static("x-ratelimit-remaining"),
+				http::header::HeaderName::from_static("x-ratelimit-reset"),
+				http::header::HeaderName::from_static(
+					"x-ratelimit-reset-after",
+				),
+				http::header::HeaderName::from_static("x-ratelimit-bucket"),
+				http::header::HeaderName::from_static("x-ratelimit-global"),
+				http::header::HeaderName::from_static("deprecation"),
+				http::header::HeaderName::from_static("sunset"),
+			])
 			.supports_credentials()
 			.max_age(3600);
 
 		App::new()
 			.app_data(web::Data::from(app_state.clone()))
 			.app_data(web::Data::new(server.clone()))
+			.app_data(web::Data::from(streams.clone()))
 			.app_data(session_endpoint.clone())
 			.app_data(web::Data::new(db.clone()))
 			.app_data(web::Data::new(redis_fetcher.clone()))
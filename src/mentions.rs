@@ -0,0 +1,183 @@
+use crate::{
+	models::Snowflakes, permissions::Permissions, redis::RedisFetcher,
+	ws::server::CreateMessage,
+};
+
+/// The mentions found in the content of a message, before they are validated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Mentions {
+	/// Users mentioned with `<@id>`.
+	pub users: Vec<i64>,
+	/// Roles mentioned with `<@&id>`.
+	pub roles: Vec<i64>,
+	/// Whether `@everyone` was used.
+	pub everyone: bool,
+}
+
+/// The most mentions of each kind a single message may contain.
+pub const MAX_MENTIONS: usize = 50;
+
+/// Whether `c` joins a `@everyone` next to it to the surrounding text, as the
+/// characters of a word, an email address or inline code do.
+fn is_joining(c: char) -> bool {
+	c.is_alphanumeric() || matches!(c, '_' | '@' | '`')
+}
+
+/// Whether `@everyone` appears in `content` as a token of its own, rather than
+/// as part of `foo@everyone.com`, `@everyones` or `` `@everyone` ``.
+fn mentions_everyone(content: &str) -> bool {
+	const TOKEN: &str = "@everyone";
+
+	content.match_indices(TOKEN).any(|(start, _)| {
+		let before = content[..start].chars().next_back();
+		let after = content[start + TOKEN.len()..].chars().next();
+
+		!before.is_some_and(is_joining) && !after.is_some_and(is_joining)
+	})
+}
+
+/// Extracts the `<@id>`, `<@&id>` and `@everyone` tokens from `content`.
+/// Repeated mentions are only reported once.
+pub fn parse(content: &str) -> Mentions {
+	let mut mentions =
+		Mentions { everyone: mentions_everyone(content), ..Default::default() };
+	let mut rest = content;
+
+	while let Some(start) = rest.find("<@") {
+		rest = &rest[start + 2..];
+
+		let (is_role, body) = match rest.strip_prefix('&') {
+			Some(body) => (true, body),
+			None => (false, rest),
+		};
+
+		let end = match body.find('>') {
+			Some(end) => end,
+			None => break,
+		};
+
+		let id = match body[..end].parse::<i64>() {
+			Ok(id) => id,
+			Err(_) => continue,
+		};
+
+		let ids =
+			if is_role { &mut mentions.roles } else { &mut mentions.users };
+
+		if !ids.contains(&id) && ids.len() < MAX_MENTIONS {
+			ids.push(id);
+		}
+
+		rest = &body[end + 1..];
+	}
+
+	mentions
+}
+
+/// Fills in the mentions of a message from its content. Mentions of users and
+/// roles which do not exist are dropped, and `@everyone` only counts if the
+/// author is allowed to use it.
+pub async fn resolve(
+	fetcher: &RedisFetcher, data: &mut CreateMessage, permissions: Permissions,
+) -> anyhow::Result<()> {
+	let parsed = parse(&data.content);
+
+	data.mentions = if parsed.users.is_empty() {
+		Snowflakes::default()
+	} else {
+		fetcher
+			.fetch_users(Some(&parsed.users))
+			.await?
+			.into_iter()
+			.map(|user| user.id)
+			.collect::<Vec<_>>()
+			.into()
+	};
+
+	data.mention_roles = if parsed.roles.is_empty() {
+		Snowflakes::default()
+	} else {
+		fetcher
+			.fetch_roles(Some(&parsed.roles))
+			.await?
+			.into_iter()
+			.map(|role| role.id)
+			.collect::<Vec<_>>()
+			.into()
+	};
+
+	data.mention_everyone =
+		parsed.everyone && permissions.contains(Permissions::MENTION_EVERYONE);
+
+	data.mentioned_users = data.mentions.iter().copied().collect();
+	data.mentioned_users
+		.extend(fetcher.fetch_role_members(&data.mention_roles).await?);
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn everyone_as_token() {
+		for content in
+			["@everyone", "hi @everyone!", "(@everyone)", "look, @everyone"]
+		{
+			assert!(parse(content).everyone, "{content}");
+		}
+	}
+
+	#[test]
+	fn everyone_inside_other_text() {
+		for content in [
+			"foo@everyone.com",
+			"`@everyone`",
+			"@everyones",
+			"@@everyone",
+			"@_everyone",
+		] {
+			assert!(!parse(content).everyone, "{content}");
+		}
+	}
+
+	#[test]
+	fn everyone_after_joined_occurrence() {
+		assert!(parse("foo@everyone.com @everyone").everyone);
+	}
+
+	#[test]
+	fn users_and_roles() {
+		let mentions = parse("<@123> and <@&456>, again <@123> <@&456>");
+
+		assert_eq!(mentions.users, [123]);
+		assert_eq!(mentions.roles, [456]);
+		assert!(!mentions.everyone);
+	}
+
+	#[test]
+	fn malformed_mentions_are_skipped() {
+		let mentions = parse("<@abc> <@&> <@> <@ 1> <@<@7> <@&8>");
+
+		assert_eq!(mentions.users, [7]);
+		assert_eq!(mentions.roles, [8]);
+	}
+
+	#[test]
+	fn unclosed_mention_ends_parsing() {
+		let mentions = parse("<@1> <@12 and <@&3");
+
+		assert_eq!(mentions.users, [1]);
+		assert!(mentions.roles.is_empty());
+	}
+
+	#[test]
+	fn mentions_are_capped() {
+		let content = (0..MAX_MENTIONS as i64 + 10)
+			.map(|id| format!("<@{id}>"))
+			.collect::<String>();
+
+		assert_eq!(parse(&content).users.len(), MAX_MENTIONS);
+	}
+}
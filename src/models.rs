@@ -1,10 +1,17 @@
-use crate::ws::server::CreateMessage;
+use crate::{permissions::Permissions, ws::server::CreateMessage};
 use actix_web::{FromRequest, HttpMessage};
 use chrono::Utc;
 use deadpool_redis::redis;
+use rand::{distributions::Alphanumeric, Rng};
 use redis_derive::{FromRedisValue, ToRedisArgs};
 use serde::{Deserialize, Serialize};
 use std::future::ready;
+use utoipa::ToSchema;
+
+pub use shiki_client::model::{
+	AuditLogAction, AuditLogChange, Ban, ChannelType, Presence, Snowflakes,
+	Status,
+};
 
 #[derive(
 	Clone,
@@ -28,6 +35,34 @@ pub struct Channel {
 	pub created_at: usize,
 	/// The id of the user who created the channel.
 	pub owner_id: i64,
+	/// The type of the channel.
+	#[serde(default, rename = "type")]
+	pub kind: ChannelType,
+	/// Sorting position of the channel.
+	#[serde(default)]
+	pub position: i64,
+	/// The id of the guild the channel belongs to.
+	#[serde(default)]
+	pub guild_id: Option<i64>,
+	/// The id of the category this channel is in, or of the channel a thread
+	/// was started in.
+	#[serde(default)]
+	pub parent_id: Option<i64>,
+	/// The id of the message a thread was started from.
+	#[serde(default)]
+	pub starter_message_id: Option<i64>,
+	/// The ids of the users who joined a thread.
+	#[serde(default)]
+	pub member_ids: Snowflakes,
+	/// Whether a thread has been archived.
+	#[serde(default)]
+	pub archived: bool,
+	/// Minutes of inactivity after which a thread is archived.
+	#[serde(default)]
+	pub auto_archive_duration: u64,
+	/// Unix timestamp of the last message sent in a thread.
+	#[serde(default)]
+	pub last_activity_at: usize,
 }
 
 impl Channel {
@@ -40,10 +75,179 @@ impl Channel {
 			description,
 			created_at: Utc::now().timestamp() as usize,
 			owner_id,
+			kind: ChannelType::Text,
+			position: 0,
+			guild_id: None,
+			parent_id: None,
+			starter_message_id: None,
+			member_ids: Snowflakes::default(),
+			archived: false,
+			auto_archive_duration: 0,
+			last_activity_at: 0,
 		}
 	}
 }
 
+/// A community which users join through invites.
+#[derive(
+	Clone,
+	Debug,
+	PartialEq,
+	Eq,
+	Deserialize,
+	Serialize,
+	Default,
+	ToRedisArgs,
+	FromRedisValue,
+)]
+pub struct Guild {
+	/// The id of the guild
+	pub id: i64,
+	/// The name of the guild
+	pub name: String,
+	/// The id of the user who created the guild
+	pub owner_id: i64,
+	/// Unix timestamp for when the guild was created
+	pub created_at: usize,
+}
+
+impl Guild {
+	pub fn new(id: i64, name: &str, owner_id: i64) -> Self {
+		Guild {
+			id,
+			name: name.to_string(),
+			owner_id,
+			created_at: Utc::now().timestamp() as usize,
+		}
+	}
+}
+
+/// A user's membership of a guild.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub struct Member {
+	/// The id of the guild
+	pub guild_id: i64,
+	/// The id of the user
+	pub user_id: i64,
+	/// Unix timestamp for when the user joined the guild
+	pub joined_at: usize,
+	/// Unix timestamp until which the user may not send messages
+	#[serde(default)]
+	pub communication_disabled_until: Option<usize>,
+}
+
+impl Member {
+	pub fn new(guild_id: i64, user_id: i64) -> Self {
+		Member {
+			guild_id,
+			user_id,
+			joined_at: Utc::now().timestamp() as usize,
+			communication_disabled_until: None,
+		}
+	}
+
+	/// Whether the member is timed out at `now`.
+	pub fn is_timed_out(&self, now: usize) -> bool {
+		self.communication_disabled_until.is_some_and(|until| until > now)
+	}
+}
+
+/// A privileged action taken by a user.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct AuditLogEntry {
+	/// The id of the entry
+	pub id: i64,
+	/// The id of the guild the action was taken in, if any
+	pub guild_id: Option<i64>,
+	/// The id of the user who took the action
+	pub user_id: i64,
+	/// The id of the user, channel or role the action affected
+	pub target_id: Option<i64>,
+	/// What was done
+	pub action: AuditLogAction,
+	/// The fields the action changed
+	#[serde(default)]
+	pub changes: Vec<AuditLogChange>,
+	/// Why it was done
+	pub reason: Option<String>,
+	/// Unix timestamp for when the action was taken
+	pub created_at: usize,
+}
+
+impl AuditLogEntry {
+	/// Creates an entry without an id, one is given to it once it is recorded.
+	pub fn new(
+		guild_id: Option<i64>, user_id: i64, action: AuditLogAction,
+	) -> Self {
+		AuditLogEntry {
+			id: 0,
+			guild_id,
+			user_id,
+			target_id: None,
+			action,
+			changes: Vec::new(),
+			reason: None,
+			created_at: Utc::now().timestamp() as usize,
+		}
+	}
+}
+
+/// A code which lets users join a guild and channel.
+#[derive(
+	Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default, ToSchema,
+)]
+pub struct Invite {
+	/// The invite code
+	pub code: String,
+	/// The id of the guild the invite is for
+	pub guild_id: Option<i64>,
+	/// The id of the channel the invite is for
+	pub channel_id: i64,
+	/// The id of the user who created the invite
+	pub inviter_id: i64,
+	/// Unix timestamp for when the invite was created
+	pub created_at: usize,
+	/// Unix timestamp after which the invite can no longer be used, if any
+	pub expires_at: Option<usize>,
+	/// How many times the invite can be used, 0 for unlimited
+	pub max_uses: u64,
+	/// How many times the invite has been used
+	pub uses: u64,
+}
+
+impl Invite {
+	/// Length of generated invite codes.
+	const CODE_LENGTH: usize = 8;
+
+	/// Creates an invite with a random code. A `max_age` of 0 never expires.
+	pub fn new(
+		channel: &Channel, inviter_id: i64, max_age: u64, max_uses: u64,
+	) -> Self {
+		let created_at = Utc::now().timestamp() as usize;
+		let code = rand::thread_rng()
+			.sample_iter(&Alphanumeric)
+			.take(Self::CODE_LENGTH)
+			.map(char::from)
+			.collect();
+
+		Invite {
+			code,
+			guild_id: channel.guild_id,
+			channel_id: channel.id,
+			inviter_id,
+			created_at,
+			expires_at: (max_age > 0).then(|| created_at + max_age as usize),
+			max_uses,
+			uses: 0,
+		}
+	}
+
+	/// Whether the invite has expired at `now`.
+	pub fn is_expired(&self, now: usize) -> bool {
+		self.expires_at.is_some_and(|expires_at| expires_at <= now)
+	}
+}
+
 #[derive(
 	Clone,
 	Debug,
@@ -66,6 +270,18 @@ pub struct Message {
 	pub content: String,
 	/// Unix timestamp for when the message was created
 	pub created_at: usize,
+	/// The ids of the users mentioned in the message
+	#[serde(default)]
+	pub mentions: Snowflakes,
+	/// The ids of the roles mentioned in the message
+	#[serde(default)]
+	pub mention_roles: Snowflakes,
+	/// Whether the message mentions everyone
+	#[serde(default)]
+	pub mention_everyone: bool,
+	/// Whether the message is pinned in its channel
+	#[serde(default)]
+	pub pinned: bool,
 }
 
 impl Message {
@@ -78,16 +294,81 @@ impl Message {
 			author_id,
 			content: content.to_string(),
 			created_at: Utc::now().timestamp() as usize,
+			mentions: Snowflakes::default(),
+			mention_roles: Snowflakes::default(),
+			mention_everyone: false,
+			pinned: false,
 		}
 	}
 }
 
 impl From<CreateMessage> for Message {
 	fn from(msg: CreateMessage) -> Self {
-		Self::new(msg.id, msg.channel_id, msg.author.id, &msg.content)
+		Message {
+			mentions: msg.mentions.clone(),
+			mention_roles: msg.mention_roles.clone(),
+			mention_everyone: msg.mention_everyone,
+			..Self::new(msg.id, msg.channel_id, msg.author.id, &msg.content)
+		}
+	}
+}
+
+/// A named set of permissions which can be given to users.
+#[derive(
+	Clone,
+	Debug,
+	PartialEq,
+	Eq,
+	Deserialize,
+	Serialize,
+	Default,
+	ToRedisArgs,
+	FromRedisValue,
+	ToSchema,
+)]
+pub struct Role {
+	/// The id of the role
+	pub id: i64,
+	/// The name of the role
+	pub name: String,
+	/// The permissions granted by the role
+	pub permissions: Permissions,
+	/// Unix timestamp for when the role was created
+	pub created_at: usize,
+}
+
+impl Role {
+	pub fn new(id: i64, name: &str, permissions: Permissions) -> Self {
+		Role {
+			id,
+			name: name.to_string(),
+			permissions,
+			created_at: Utc::now().timestamp() as usize,
+		}
 	}
 }
 
+/// The last message a user has read in a channel.
+#[derive(
+	Clone,
+	Debug,
+	PartialEq,
+	Eq,
+	Deserialize,
+	Serialize,
+	Default,
+	ToRedisArgs,
+	FromRedisValue,
+)]
+pub struct ReadState {
+	/// The id of the user
+	pub user_id: i64,
+	/// The id of the channel
+	pub channel_id: i64,
+	/// The id of the last message the user has acknowledged
+	pub last_message_id: i64,
+}
+
 #[derive(
 	Clone,
 	Debug,
@@ -109,6 +390,15 @@ pub struct User {
 	/// Unix timestamp for when user was created.
 	pub created_at: usize,
 	pub avatar: Option<String>,
+	/// The ids of the roles the user has.
+	#[serde(default)]
+	pub roles: Snowflakes,
+	/// Whether the account is a bot.
+	#[serde(default)]
+	pub bot: bool,
+	/// Whether a bot may identify with privileged intents.
+	#[serde(default)]
+	pub privileged_intents: bool,
 }
 
 impl User {
@@ -121,6 +411,9 @@ impl User {
 			token: uuid::Uuid::new_v4().to_string(),
 			created_at: Utc::now().timestamp() as usize,
 			avatar: None,
+			roles: Snowflakes::default(),
+			bot: false,
+			privileged_intents: false,
 		}
 	}
 }
@@ -0,0 +1,139 @@
+use crate::{
+	models::{self, Channel},
+	redis::RedisFetcher,
+};
+use anyhow::Result;
+use chrono::Utc;
+use deadpool_redis::redis::{
+	self, FromRedisValue, RedisResult, RedisWrite, ToRedisArgs,
+};
+use serde::{Deserialize, Serialize};
+use std::ops::{BitOr, BitOrAssign};
+use utoipa::ToSchema;
+
+/// Bitfield of the actions a user is allowed to take.
+#[derive(
+	Clone,
+	Copy,
+	Debug,
+	PartialEq,
+	Eq,
+	Hash,
+	Default,
+	Deserialize,
+	Serialize,
+	ToSchema,
+)]
+#[serde(transparent)]
+pub struct Permissions(pub u64);
+
+impl Permissions {
+	/// Grants every permission. The first user of an instance is given a role
+	/// with it when they register.
+	pub const ADMINISTRATOR: Self = Self(1 << 0);
+	pub const MANAGE_CHANNELS: Self = Self(1 << 1);
+	pub const MANAGE_ROLES: Self = Self(1 << 2);
+	/// Allows `@everyone` to notify every user.
+	pub const MENTION_EVERYONE: Self = Self(1 << 3);
+	pub const SEND_MESSAGES: Self = Self(1 << 4);
+	/// Allows pinning and removing the messages of other users.
+	pub const MANAGE_MESSAGES: Self = Self(1 << 5);
+	pub const CREATE_INVITE: Self = Self(1 << 6);
+	pub const KICK_MEMBERS: Self = Self(1 << 7);
+	pub const BAN_MEMBERS: Self = Self(1 << 8);
+	/// Allows timing out other members.
+	pub const MODERATE_MEMBERS: Self = Self(1 << 9);
+	pub const VIEW_AUDIT_LOG: Self = Self(1 << 10);
+
+	pub const ALL: Self = Self(u64::MAX);
+
+	/// Permissions every user has, regardless of their roles.
+	pub const DEFAULT: Self =
+		Self(Self::SEND_MESSAGES.0 | Self::CREATE_INVITE.0);
+
+	pub const NONE: Self = Self(0);
+
+	/// Whether all of the permissions in `other` are granted.
+	pub fn contains(self, other: Self) -> bool {
+		self.0 & Self::ADMINISTRATOR.0 != 0 || self.0 & other.0 == other.0
+	}
+}
+
+impl BitOr for Permissions {
+	type Output = Self;
+
+	fn bitor(self, rhs: Self) -> Self::Output {
+		Self(self.0 | rhs.0)
+	}
+}
+
+impl BitOrAssign for Permissions {
+	fn bitor_assign(&mut self, rhs: Self) {
+		self.0 |= rhs.0;
+	}
+}
+
+impl ToRedisArgs for Permissions {
+	fn write_redis_args<W>(&self, out: &mut W)
+	where
+		W: ?Sized + RedisWrite,
+	{
+		self.0.write_redis_args(out)
+	}
+}
+
+impl FromRedisValue for Permissions {
+	fn from_redis_value(v: &redis::Value) -> RedisResult<Self> {
+		u64::from_redis_value(v).map(Self)
+	}
+}
+
+/// Computes the permissions a user has from their roles.
+pub async fn base(
+	fetcher: &RedisFetcher, user: &models::User,
+) -> Result<Permissions> {
+	let roles = fetcher.fetch_roles(Some(&user.roles)).await?;
+
+	Ok(roles
+		.into_iter()
+		.fold(Permissions::DEFAULT, |acc, role| acc | role.permissions))
+}
+
+/// Computes the permissions a user has in a channel. The permissions of the
+/// channel's guild apply, and the owner of a channel is allowed to do anything
+/// in it unless they are shut out of the guild.
+pub async fn in_channel(
+	fetcher: &RedisFetcher, user: &models::User, channel: &Channel,
+) -> Result<Permissions> {
+	let permissions = match channel.guild_id {
+		Some(guild_id) => match fetcher.fetch_guild(guild_id).await? {
+			Some(guild) => in_guild(fetcher, user, &guild).await?,
+			None => Permissions::NONE,
+		},
+		None => base(fetcher, user).await?,
+	};
+
+	if channel.owner_id == user.id && permissions != Permissions::NONE {
+		return Ok(Permissions::ALL);
+	}
+
+	Ok(permissions)
+}
+
+/// Computes the permissions a user has in a guild. The owner of a guild is
+/// allowed to do anything in it. Users outside of the guild and members who
+/// are timed out are not allowed to do anything.
+pub async fn in_guild(
+	fetcher: &RedisFetcher, user: &models::User, guild: &models::Guild,
+) -> Result<Permissions> {
+	if guild.owner_id == user.id {
+		return Ok(Permissions::ALL);
+	}
+
+	let now = Utc::now().timestamp() as usize;
+
+	match fetcher.fetch_member(guild.id, user.id).await? {
+		Some(member) if !member.is_timed_out(now) => base(fetcher, user).await,
+		_ => Ok(Permissions::NONE),
+	}
+}
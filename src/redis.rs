@@ -2,17 +2,31 @@
 
 // Methods which abstract the fetching of our data from redis, all data should be fetched first on redis, and fallback to the database.
 use crate::{
-	models,
-	routes::{CHANNEL_COLL_NAME, DB_NAME, MESSAGE_COLL_NAME, USER_COLL_NAME},
+	models::{self, AuditLogAction, Status},
+	routes::{
+		AUDIT_LOG_COLL_NAME, BAN_COLL_NAME, CHANNEL_COLL_NAME, DB_NAME,
+		GUILD_COLL_NAME, INVITE_COLL_NAME, MEMBER_COLL_NAME, MESSAGE_COLL_NAME,
+		READ_STATE_COLL_NAME, ROLE_COLL_NAME, USER_COLL_NAME,
+	},
+	utils,
 };
 use anyhow::Result;
+use chrono::Utc;
 use deadpool_redis::{
-	redis::{AsyncCommands, FromRedisValue, ToRedisArgs},
+	redis::{self, AsyncCommands, FromRedisValue, ToRedisArgs},
 	Connection, Pool,
 };
 use futures_util::TryStreamExt;
-use mongodb::{bson::doc, Client};
+use mongodb::{
+	bson::{self, doc, Bson, Document},
+	options::{
+		FindOneAndUpdateOptions, FindOptions, ReplaceOptions, ReturnDocument,
+	},
+	Client,
+};
 use serde::Deserialize;
+use std::{collections::HashMap, time::Duration};
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
 async fn get_value<T>(conn: &mut Connection, key: &str) -> Result<T>
@@ -34,6 +48,98 @@ where
 		.map_err(|e| anyhow::anyhow!(e))
 }
 
+/// Escapes the characters which have a meaning in a regular expression.
+fn escape_regex(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+
+	for c in text.chars() {
+		if "\\.+*?()|[]{}^$#&-~".contains(c) {
+			escaped.push('\\');
+		}
+
+		escaped.push(c);
+	}
+
+	escaped
+}
+
+/// Refills a token bucket for the time since it was last used, then takes a
+/// token from it if there is one. Returns whether a token was taken, the
+/// tokens left, and the milliseconds until the bucket is full and until the
+/// next token is available.
+const TOKEN_BUCKET_SCRIPT: &str = r"
+local capacity = tonumber(ARGV[1])
+local period = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'updated_at')
+local tokens = tonumber(state[1]) or capacity
+local updated_at = tonumber(state[2]) or now
+local rate = capacity / period
+
+tokens = math.min(capacity, tokens + math.max(0, now - updated_at) * rate)
+
+local allowed = 0
+local retry_after = 0
+
+if tokens >= 1 then
+	tokens = tokens - 1
+	allowed = 1
+else
+	retry_after = math.ceil((1 - tokens) / rate)
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'updated_at', now)
+redis.call('PEXPIRE', KEYS[1], period)
+
+return {allowed, math.floor(tokens), math.ceil((capacity - tokens) / rate), retry_after}
+";
+
+/// The state of a rate limit bucket after a request was counted against it.
+#[derive(Debug, Clone, Copy)]
+pub struct BucketState {
+	/// Whether the request is allowed
+	pub allowed: bool,
+	/// Requests left before the bucket is empty
+	pub remaining: u64,
+	/// Time until the bucket is full again
+	pub reset_after: Duration,
+	/// Time until the next request is allowed, zero if it is allowed now
+	pub retry_after: Duration,
+}
+
+/// Conditions matching the messages which mention a user.
+fn mentions_query(user: &models::User) -> Vec<Bson> {
+	vec![
+		doc! {"mentions": user.id}.into(),
+		doc! {"mention_roles": {"$in": user.roles.as_slice()}}.into(),
+		doc! {"mention_everyone": true}.into(),
+	]
+}
+
+/// Aggregation expression of whether a message mentions a user, matching the
+/// same messages as [`mentions_query`].
+fn mentions_expr(user: &models::User) -> Document {
+	doc! {
+		"$or": [
+			{"$in": [user.id, {"$ifNull": ["$mentions", []]}]},
+			{
+				"$gt": [
+					{
+						"$size": {
+							"$setIntersection": [
+								{"$ifNull": ["$mention_roles", []]},
+								user.roles.as_slice(),
+							]
+						}
+					},
+					0,
+				]
+			},
+			{"$eq": ["$mention_everyone", true]},
+		]
+	}
+}
+
 #[derive(Clone)]
 pub struct RedisFetcher {
 	client: Client,
@@ -85,7 +191,7 @@ impl RedisFetcher {
 			}
 
 			if ids_to_fetch.is_empty() {
-				return Ok(vec![]);
+				return Ok(channels);
 			}
 
 			doc = Some(doc! {"id": {"$in": ids_to_fetch}});
@@ -116,6 +222,12 @@ impl RedisFetcher {
 		}
 	}
 
+	pub async fn fetch_channel(
+		&self, id: i64,
+	) -> Result<Option<models::Channel>> {
+		Ok(self.fetch_channels(Some(&[id])).await?.pop())
+	}
+
 	pub async fn fetch_messages(
 		&self, ids: &[i64],
 	) -> Result<Vec<models::Message>> {
@@ -143,7 +255,7 @@ impl RedisFetcher {
 		let res = self
 			.client
 			.database(DB_NAME)
-			.collection::<models::Message>(USER_COLL_NAME)
+			.collection::<models::Message>(MESSAGE_COLL_NAME)
 			.find(doc! {"id": {"$in": ids_to_fetch}}, None)
 			.await;
 
@@ -164,6 +276,12 @@ impl RedisFetcher {
 		}
 	}
 
+	pub async fn fetch_message(
+		&self, id: i64,
+	) -> Result<Option<models::Message>> {
+		Ok(self.fetch_messages(&[id]).await?.pop())
+	}
+
 	pub async fn fetch_user(
 		&self, id: FetchUserId,
 	) -> Result<Option<models::User>> {
@@ -282,101 +400,1266 @@ impl RedisFetcher {
 		Ok(users)
 	}
 
-	pub async fn insert_channel(&self, channel: models::Channel) -> Result<()> {
+	pub async fn fetch_roles(
+		&self, ids: Option<&[i64]>,
+	) -> Result<Vec<models::Role>> {
 		let mut conn = self.create_connection().await?;
+		let mut doc = None;
+		let mut roles = Vec::new();
 
-		set_value(&mut conn, &format!("channel_{}", channel.id), &channel)
+		if let Some(ids) = ids {
+			let mut ids_to_fetch = Vec::new();
+
+			for id in ids {
+				if let Ok(role) =
+					get_value::<models::Role>(&mut conn, &format!("role_{id}"))
+						.await
+				{
+					roles.push(role);
+				} else {
+					ids_to_fetch.push(id);
+				}
+			}
+
+			if ids_to_fetch.is_empty() {
+				return Ok(roles);
+			}
+
+			doc = Some(doc! {"id": {"$in": ids_to_fetch}});
+		}
+
+		let db_roles = self
+			.client
+			.database(DB_NAME)
+			.collection::<models::Role>(ROLE_COLL_NAME)
+			.find(doc, None)
+			.await?
+			.try_collect::<Vec<_>>()
 			.await?;
 
+		for role in db_roles {
+			set_value(&mut conn, &format!("role_{}", role.id), &role).await?;
+			roles.push(role);
+		}
+
+		Ok(roles)
+	}
+
+	/// Fetches the ids of the users which have any of the given roles.
+	pub async fn fetch_role_members(
+		&self, role_ids: &[i64],
+	) -> Result<Vec<i64>> {
+		if role_ids.is_empty() {
+			return Ok(Vec::new());
+		}
+
+		Ok(self
+			.client
+			.database(DB_NAME)
+			.collection::<models::User>(USER_COLL_NAME)
+			.find(doc! {"roles": {"$in": role_ids}}, None)
+			.await?
+			.try_collect::<Vec<_>>()
+			.await?
+			.into_iter()
+			.map(|user| user.id)
+			.collect())
+	}
+
+	pub async fn insert_role(&self, role: models::Role) -> Result<()> {
+		let mut conn = self.create_connection().await?;
+
+		set_value(&mut conn, &format!("role_{}", role.id), &role).await?;
+
 		self.client
 			.database(DB_NAME)
-			.collection::<models::Channel>(CHANNEL_COLL_NAME)
-			.insert_one(channel, None)
+			.collection::<models::Role>(ROLE_COLL_NAME)
+			.insert_one(role, None)
 			.await
 			.map(|_| ())
 			.map_err(|e| anyhow::anyhow!(e))
 	}
 
-	pub async fn insert_message(&self, message: models::Message) -> Result<()> {
+	/// Gives a role to a user, or takes it away if `add` is false.
+	pub async fn modify_user_roles(
+		&self, user_id: i64, role_id: i64, add: bool,
+	) -> Result<()> {
 		let mut conn = self.create_connection().await?;
+		let update = if add {
+			doc! {"$addToSet": {"roles": role_id}}
+		} else {
+			doc! {"$pull": {"roles": role_id}}
+		};
 
-		set_value(&mut conn, &format!("message_{}", message.id), &message)
+		self.client
+			.database(DB_NAME)
+			.collection::<models::User>(USER_COLL_NAME)
+			.update_one(doc! {"id": user_id}, update, None)
+			.await?;
+
+		conn.del::<_, ()>(format!("user_{user_id}"))
+			.await
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	pub async fn fetch_guild(&self, id: i64) -> Result<Option<models::Guild>> {
+		let mut conn = self.create_connection().await?;
+		let key = format!("guild_{id}");
+
+		if let Ok(guild) = get_value::<models::Guild>(&mut conn, &key).await {
+			return Ok(Some(guild));
+		}
+
+		let guild = self
+			.client
+			.database(DB_NAME)
+			.collection::<models::Guild>(GUILD_COLL_NAME)
+			.find_one(doc! {"id": id}, None)
+			.await?;
+
+		if let Some(guild) = &guild {
+			set_value(&mut conn, &key, guild).await?;
+		}
+
+		Ok(guild)
+	}
+
+	/// Creates a guild with its owner as the first member.
+	pub async fn insert_guild(&self, guild: models::Guild) -> Result<()> {
+		let mut conn = self.create_connection().await?;
+
+		set_value(&mut conn, &format!("guild_{}", guild.id), &guild).await?;
+
+		self.insert_member(models::Member::new(guild.id, guild.owner_id))
 			.await?;
 
 		self.client
 			.database(DB_NAME)
-			.collection::<models::Message>(MESSAGE_COLL_NAME)
-			.insert_one(message, None)
+			.collection::<models::Guild>(GUILD_COLL_NAME)
+			.insert_one(guild, None)
 			.await
 			.map(|_| ())
 			.map_err(|e| anyhow::anyhow!(e))
 	}
 
-	pub async fn modify_user(
-		&self, user: &mut models::User, data: ModifyUser,
-	) -> Result<()> {
-		let id = user.id;
-		let mut conn = self.create_connection().await?;
+	pub async fn fetch_member(
+		&self, guild_id: i64, user_id: i64,
+	) -> Result<Option<models::Member>> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Member>(MEMBER_COLL_NAME)
+			.find_one(doc! {"guild_id": guild_id, "user_id": user_id}, None)
+			.await
+			.map_err(|e| anyhow::anyhow!(e))
+	}
 
-		if let Some(ref username) = data.username {
-			user.username = username.clone();
+	pub async fn count_users(&self) -> Result<u64> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::User>(USER_COLL_NAME)
+			.count_documents(None, None)
+			.await
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	/// IDs of every member of a guild.
+	pub async fn fetch_member_ids(&self, guild_id: i64) -> Result<Vec<i64>> {
+		Ok(self
+			.client
+			.database(DB_NAME)
+			.collection::<models::Member>(MEMBER_COLL_NAME)
+			.find(doc! {"guild_id": guild_id}, None)
+			.await?
+			.map_ok(|member| member.user_id)
+			.try_collect()
+			.await?)
+	}
+
+	/// Ids of the guilds the user is a member of.
+	pub async fn fetch_user_guild_ids(&self, user_id: i64) -> Result<Vec<i64>> {
+		Ok(self
+			.client
+			.database(DB_NAME)
+			.collection::<models::Member>(MEMBER_COLL_NAME)
+			.find(doc! {"user_id": user_id}, None)
+			.await?
+			.map_ok(|member| member.guild_id)
+			.try_collect()
+			.await?)
+	}
+
+	/// Guilds the user is a member of.
+	pub async fn fetch_user_guilds(
+		&self, user_id: i64,
+	) -> Result<Vec<models::Guild>> {
+		let guild_ids = self.fetch_user_guild_ids(user_id).await?;
+
+		Ok(self
+			.client
+			.database(DB_NAME)
+			.collection::<models::Guild>(GUILD_COLL_NAME)
+			.find(doc! {"id": {"$in": guild_ids}}, None)
+			.await?
+			.try_collect()
+			.await?)
+	}
+
+	/// Users ordered by ID, optionally restricted to `ids` and to usernames
+	/// starting with `prefix`, case insensitively. Pages start after the
+	/// user with the ID `after`.
+	pub async fn search_users(
+		&self, ids: Option<&[i64]>, prefix: Option<&str>, after: i64,
+		limit: i64,
+	) -> Result<Vec<models::User>> {
+		let mut filter = doc! {"id": {"$gt": after}};
+
+		if let Some(ids) = ids {
+			filter.insert("id", doc! {"$gt": after, "$in": ids});
 		}
 
-		if let Some(ref avatar) = data.avatar {
-			user.avatar = Some(avatar.clone());
+		if let Some(prefix) = prefix {
+			let pattern = format!("^{}", escape_regex(prefix));
+
+			filter
+				.insert("username", doc! {"$regex": pattern, "$options": "i"});
 		}
 
-		if get_value::<models::User>(&mut conn, &format!("user_{}", user.id))
+		Ok(self
+			.client
+			.database(DB_NAME)
+			.collection::<models::User>(USER_COLL_NAME)
+			.find(
+				filter,
+				FindOptions::builder()
+					.sort(doc! {"id": 1})
+					.limit(limit)
+					.build(),
+			)
+			.await?
+			.try_collect()
+			.await?)
+	}
+
+	pub async fn count_members(&self, guild_id: i64) -> Result<u64> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Member>(MEMBER_COLL_NAME)
+			.count_documents(doc! {"guild_id": guild_id}, None)
 			.await
-			.is_ok()
-		{
-			let mut fields: Vec<(String, String)> = vec![];
+			.map_err(|e| anyhow::anyhow!(e))
+	}
 
-			if let Some(ref username) = data.username {
-				fields.push(("username".to_string(), username.clone()));
-			}
+	pub async fn insert_member(&self, member: models::Member) -> Result<()> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Member>(MEMBER_COLL_NAME)
+			.insert_one(member, None)
+			.await
+			.map(|_| ())
+			.map_err(|e| anyhow::anyhow!(e))
+	}
 
-			if let Some(ref avatar) = data.avatar {
-				fields.push(("avatar".to_string(), avatar.clone()));
-			}
+	/// Removes a user from a guild, returning whether they were a member.
+	pub async fn remove_member(
+		&self, guild_id: i64, user_id: i64,
+	) -> Result<bool> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Member>(MEMBER_COLL_NAME)
+			.delete_one(doc! {"guild_id": guild_id, "user_id": user_id}, None)
+			.await
+			.map(|res| res.deleted_count > 0)
+			.map_err(|e| anyhow::anyhow!(e))
+	}
 
-			conn.hset_multiple::<_, _, _, ()>(
-				format!("user_{id}"),
-				fields.as_slice(),
+	/// Times out a member until the given timestamp, or lifts their timeout.
+	/// Returns whether the user is a member.
+	pub async fn set_member_timeout(
+		&self, guild_id: i64, user_id: i64, until: Option<usize>,
+	) -> Result<bool> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Member>(MEMBER_COLL_NAME)
+			.update_one(
+				doc! {"guild_id": guild_id, "user_id": user_id},
+				doc! {"$set": {
+					"communication_disabled_until": until.map(|until| until as i64),
+				}},
+				None,
 			)
 			.await
-			.map(|_| {
-				log::debug!("modified user {id} in cache");
-			})
-			.map_err(|e| anyhow::anyhow!(e))?;
-		}
+			.map(|res| res.matched_count > 0)
+			.map_err(|e| anyhow::anyhow!(e))
+	}
 
-		let mut fields = doc! {};
+	pub async fn fetch_ban(
+		&self, guild_id: i64, user_id: i64,
+	) -> Result<Option<models::Ban>> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Ban>(BAN_COLL_NAME)
+			.find_one(doc! {"guild_id": guild_id, "user_id": user_id}, None)
+			.await
+			.map_err(|e| anyhow::anyhow!(e))
+	}
 
-		if let Some(username) = data.username {
-			fields.insert("username", username);
-		}
+	pub async fn fetch_bans(&self, guild_id: i64) -> Result<Vec<models::Ban>> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Ban>(BAN_COLL_NAME)
+			.find(doc! {"guild_id": guild_id}, None)
+			.await?
+			.try_collect()
+			.await
+			.map_err(|e| anyhow::anyhow!(e))
+	}
 
-		if let Some(avatar) = data.avatar {
-			fields.insert("avatar", avatar);
-		}
+	/// Fetches the ids of the guilds a user is banned from.
+	pub async fn fetch_user_bans(&self, user_id: i64) -> Result<Vec<i64>> {
+		Ok(self
+			.client
+			.database(DB_NAME)
+			.collection::<models::Ban>(BAN_COLL_NAME)
+			.find(doc! {"user_id": user_id}, None)
+			.await?
+			.try_collect::<Vec<_>>()
+			.await?
+			.into_iter()
+			.map(|ban| ban.guild_id)
+			.collect())
+	}
 
+	/// Bans a user, replacing any previous ban.
+	pub async fn insert_ban(&self, ban: models::Ban) -> Result<()> {
 		self.client
 			.database(DB_NAME)
-			.collection::<models::User>(USER_COLL_NAME)
-			.update_one(doc! {"id": id}, doc! {"$set": fields}, None)
+			.collection::<models::Ban>(BAN_COLL_NAME)
+			.replace_one(
+				doc! {"guild_id": ban.guild_id, "user_id": ban.user_id},
+				ban,
+				ReplaceOptions::builder().upsert(true).build(),
+			)
 			.await
-			.map(|_| {
-				log::debug!("modified user {} in db", id);
-			})
+			.map(|_| ())
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	/// Lifts a ban, returning whether the user was banned.
+	pub async fn delete_ban(
+		&self, guild_id: i64, user_id: i64,
+	) -> Result<bool> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Ban>(BAN_COLL_NAME)
+			.delete_one(doc! {"guild_id": guild_id, "user_id": user_id}, None)
+			.await
+			.map(|res| res.deleted_count > 0)
 			.map_err(|e| anyhow::anyhow!(e))
 	}
+
+	/// Fetches the ids of the channels in a guild.
+	pub async fn fetch_guild_channels(
+		&self, guild_id: i64,
+	) -> Result<Vec<i64>> {
+		Ok(self
+			.client
+			.database(DB_NAME)
+			.collection::<models::Channel>(CHANNEL_COLL_NAME)
+			.find(doc! {"guild_id": guild_id}, None)
+			.await?
+			.try_collect::<Vec<_>>()
+			.await?
+			.into_iter()
+			.map(|channel| channel.id)
+			.collect())
+	}
+
+	/// Fetches the ids of the channels a user can see, which are those outside
+	/// of any guild and those of the guilds they are a member of and not
+	/// banned from.
+	pub async fn fetch_visible_channels(
+		&self, user_id: i64,
+	) -> Result<Vec<i64>> {
+		let banned = self.fetch_user_bans(user_id).await?;
+		let guild_ids = self
+			.fetch_user_guild_ids(user_id)
+			.await?
+			.into_iter()
+			.filter(|id| !banned.contains(id))
+			.collect::<Vec<_>>();
+
+		Ok(self
+			.client
+			.database(DB_NAME)
+			.collection::<models::Channel>(CHANNEL_COLL_NAME)
+			.find(
+				doc! {
+					"$or": [
+						{"guild_id": null},
+						{"guild_id": {"$in": guild_ids}},
+					]
+				},
+				None,
+			)
+			.await?
+			.map_ok(|channel| channel.id)
+			.try_collect()
+			.await?)
+	}
+
+	/// Deletes the messages a user sent in the given channels since a
+	/// timestamp.
+	pub async fn delete_user_messages(
+		&self, user_id: i64, channel_ids: &[i64], since: usize,
+	) -> Result<u64> {
+		let mut conn = self.create_connection().await?;
+		let collection = self
+			.client
+			.database(DB_NAME)
+			.collection::<models::Message>(MESSAGE_COLL_NAME);
+		let query = doc! {
+			"author_id": user_id,
+			"channel_id": {"$in": channel_ids},
+			"created_at": {"$gte": since as i64},
+		};
+		let keys = collection
+			.find(query.clone(), None)
+			.await?
+			.try_collect::<Vec<_>>()
+			.await?
+			.into_iter()
+			.map(|message| format!("message_{}", message.id))
+			.collect::<Vec<_>>();
+
+		let res = collection.delete_many(query, None).await?;
+
+		if !keys.is_empty() {
+			conn.del::<_, ()>(keys).await?;
+		}
+
+		log::debug!("deleted {} messages of user {user_id}", res.deleted_count);
+		Ok(res.deleted_count)
+	}
+
+	pub async fn insert_audit_log(
+		&self, entry: models::AuditLogEntry,
+	) -> Result<()> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::AuditLogEntry>(AUDIT_LOG_COLL_NAME)
+			.insert_one(entry, None)
+			.await
+			.map(|_| ())
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	/// Counts a request against a token bucket which holds `capacity` tokens
+	/// and refills completely over `period`.
+	pub async fn take_token(
+		&self, key: &str, capacity: u64, period: Duration,
+	) -> Result<BucketState> {
+		let mut conn = self.create_connection().await?;
+		let now = Utc::now().timestamp_millis();
+		let (allowed, remaining, reset_after, retry_after): (
+			u8,
+			u64,
+			u64,
+			u64,
+		) = redis::Script::new(TOKEN_BUCKET_SCRIPT)
+			.key(key)
+			.arg(capacity)
+			.arg(period.as_millis() as u64)
+			.arg(now)
+			.invoke_async(&mut conn)
+			.await?;
+
+		Ok(BucketState {
+			allowed: allowed == 1,
+			remaining,
+			reset_after: Duration::from_millis(reset_after),
+			retry_after: Duration::from_millis(retry_after),
+		})
+	}
+
+	/// Fetches the audit log of a guild, newest first.
+	pub async fn fetch_audit_logs(
+		&self, guild_id: Option<i64>, filter: &AuditLogFilter,
+	) -> Result<Vec<models::AuditLogEntry>> {
+		let mut query = doc! {"guild_id": guild_id};
+
+		if let Some(user_id) = filter.user_id {
+			query.insert("user_id", user_id);
+		}
+
+		if let Some(target_id) = filter.target_id {
+			query.insert("target_id", target_id);
+		}
+
+		if let Some(action) = filter.action {
+			query.insert("action", mongodb::bson::to_bson(&action)?);
+		}
+
+		if let Some(before) = filter.before {
+			query.insert("id", doc! {"$lt": before});
+		}
+
+		self.client
+			.database(DB_NAME)
+			.collection::<models::AuditLogEntry>(AUDIT_LOG_COLL_NAME)
+			.find(
+				query,
+				FindOptions::builder()
+					.sort(doc! {"id": -1})
+					.limit(filter.limit)
+					.build(),
+			)
+			.await?
+			.try_collect()
+			.await
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	/// Fetches an invite along with its current number of uses.
+	pub async fn fetch_invite(
+		&self, code: &str,
+	) -> Result<Option<models::Invite>> {
+		let mut conn = self.create_connection().await?;
+		let invite = self
+			.client
+			.database(DB_NAME)
+			.collection::<models::Invite>(INVITE_COLL_NAME)
+			.find_one(doc! {"code": code}, None)
+			.await?;
+
+		match invite {
+			Some(mut invite) => {
+				if let Some(uses) = conn
+					.get::<_, Option<u64>>(format!("invite_uses_{code}"))
+					.await?
+				{
+					invite.uses = uses;
+				}
+
+				Ok(Some(invite))
+			}
+			None => Ok(None),
+		}
+	}
+
+	pub async fn insert_invite(&self, invite: models::Invite) -> Result<()> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Invite>(INVITE_COLL_NAME)
+			.insert_one(invite, None)
+			.await
+			.map(|_| ())
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	/// Counts a use of an invite. Returns false without counting it if the
+	/// invite has no uses left.
+	pub async fn use_invite(&self, invite: &models::Invite) -> Result<bool> {
+		let mut conn = self.create_connection().await?;
+		let key = format!("invite_uses_{}", invite.code);
+
+		// The counter is seeded from the database in case Redis lost it.
+		conn.set_nx::<_, _, ()>(&key, invite.uses).await?;
+
+		let uses = conn.incr::<_, _, u64>(&key, 1).await?;
+
+		if invite.max_uses > 0 && uses > invite.max_uses {
+			conn.decr::<_, _, ()>(&key, 1).await?;
+			return Ok(false);
+		}
+
+		if let Some(expires_at) = invite.expires_at {
+			conn.expire_at::<_, ()>(&key, expires_at).await?;
+		}
+
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Invite>(INVITE_COLL_NAME)
+			.update_one(
+				doc! {"code": &invite.code},
+				doc! {"$inc": {"uses": 1}},
+				None,
+			)
+			.await?;
+
+		Ok(true)
+	}
+
+	pub async fn delete_invite(&self, code: &str) -> Result<()> {
+		let mut conn = self.create_connection().await?;
+
+		conn.del::<_, ()>(format!("invite_uses_{code}")).await?;
+
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Invite>(INVITE_COLL_NAME)
+			.delete_one(doc! {"code": code}, None)
+			.await
+			.map(|_| {
+				log::debug!("deleted invite {code} from db");
+			})
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	pub async fn insert_channel(&self, channel: models::Channel) -> Result<()> {
+		let mut conn = self.create_connection().await?;
+
+		set_value(&mut conn, &format!("channel_{}", channel.id), &channel)
+			.await?;
+
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Channel>(CHANNEL_COLL_NAME)
+			.insert_one(channel, None)
+			.await
+			.map(|_| ())
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	pub async fn modify_channel(
+		&self, channel: &mut models::Channel, data: ModifyChannel,
+	) -> Result<()> {
+		let id = channel.id;
+		let mut conn = self.create_connection().await?;
+		let mut fields = doc! {};
+
+		if let Some(name) = data.name {
+			channel.name = name.clone();
+			fields.insert("name", name);
+		}
+
+		if let Some(description) = data.description {
+			channel.description = description.clone();
+			fields.insert("description", description);
+		}
+
+		if let Some(position) = data.position {
+			channel.position = position;
+			fields.insert("position", position);
+		}
+
+		if let Some(parent_id) = data.parent_id {
+			channel.parent_id = parent_id;
+			fields.insert("parent_id", parent_id);
+		}
+
+		if let Some(auto_archive_duration) = data.auto_archive_duration {
+			channel.auto_archive_duration = auto_archive_duration;
+			fields
+				.insert("auto_archive_duration", auto_archive_duration as i64);
+		}
+
+		if let Some(archived) = data.archived {
+			// Unarchiving restarts the inactivity timer, otherwise the thread
+			// would be archived again straight away.
+			if channel.archived && !archived {
+				channel.last_activity_at = Utc::now().timestamp() as usize;
+				fields.insert(
+					"last_activity_at",
+					channel.last_activity_at as i64,
+				);
+			}
+
+			channel.archived = archived;
+			fields.insert("archived", archived);
+		}
+
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Channel>(CHANNEL_COLL_NAME)
+			.update_one(doc! {"id": id}, doc! {"$set": fields}, None)
+			.await?;
+
+		// The cached hash is dropped rather than patched, the next fetch will
+		// repopulate it from the database. It is only dropped once the
+		// database is updated, or a fetch in between would cache the old
+		// channel again.
+		conn.del::<_, ()>(format!("channel_{id}")).await?;
+
+		log::debug!("modified channel {id} in db");
+		Ok(())
+	}
+
+	/// Deletes a channel along with its threads, returning the ids of the
+	/// deleted threads. Channels in a deleted category are moved out of it.
+	pub async fn delete_channel(&self, id: i64) -> Result<Vec<i64>> {
+		let mut conn = self.create_connection().await?;
+		let collection = self
+			.client
+			.database(DB_NAME)
+			.collection::<models::Channel>(CHANNEL_COLL_NAME);
+
+		let (threads, children): (Vec<_>, Vec<_>) = collection
+			.find(doc! {"parent_id": id}, None)
+			.await?
+			.try_collect::<Vec<_>>()
+			.await?
+			.into_iter()
+			.partition(|c| c.kind == models::ChannelType::Thread);
+		let threads = threads.into_iter().map(|c| c.id).collect::<Vec<_>>();
+
+		if !children.is_empty() {
+			collection
+				.update_many(
+					doc! {"parent_id": id, "id": {"$nin": &threads}},
+					doc! {"$set": {"parent_id": null}},
+					None,
+				)
+				.await?;
+		}
+
+		if !threads.is_empty() {
+			collection
+				.delete_many(doc! {"id": {"$in": &threads}}, None)
+				.await?;
+		}
+
+		let mut keys = children
+			.iter()
+			.map(|c| c.id)
+			.chain(threads.iter().copied())
+			.map(|id| format!("channel_{id}"))
+			.collect::<Vec<_>>();
+
+		collection.delete_one(doc! {"id": id}, None).await?;
+		keys.push(format!("channel_{id}"));
+		conn.del::<_, ()>(keys).await?;
+
+		log::debug!("deleted channel {id} from db");
+		Ok(threads)
+	}
+
+	/// Fetches the thread started from a message, if there is one.
+	pub async fn fetch_thread(
+		&self, starter_message_id: i64,
+	) -> Result<Option<models::Channel>> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Channel>(CHANNEL_COLL_NAME)
+			.find_one(doc! {"starter_message_id": starter_message_id}, None)
+			.await
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	/// Adds a user to or removes a user from the members of a thread.
+	pub async fn modify_thread_members(
+		&self, thread: &mut models::Channel, user_id: i64, add: bool,
+	) -> Result<()> {
+		let id = thread.id;
+		let mut conn = self.create_connection().await?;
+		let update = if add {
+			if !thread.member_ids.contains(&user_id) {
+				thread.member_ids.push(user_id);
+			}
+
+			doc! {"$addToSet": {"member_ids": user_id}}
+		} else {
+			thread.member_ids.retain(|id| *id != user_id);
+			doc! {"$pull": {"member_ids": user_id}}
+		};
+
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Channel>(CHANNEL_COLL_NAME)
+			.update_one(doc! {"id": id}, update, None)
+			.await?;
+
+		conn.del::<_, ()>(format!("channel_{id}")).await?;
+
+		log::debug!("modified members of thread {id} in db");
+		Ok(())
+	}
+
+	/// Records a message sent by a user in a thread. The thread is unarchived
+	/// and the user joins it if they have not yet.
+	pub async fn touch_thread(
+		&self, thread: &mut models::Channel, user_id: i64,
+	) -> Result<()> {
+		let id = thread.id;
+		let mut conn = self.create_connection().await?;
+
+		thread.archived = false;
+		thread.last_activity_at = Utc::now().timestamp() as usize;
+
+		if !thread.member_ids.contains(&user_id) {
+			thread.member_ids.push(user_id);
+		}
+
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Channel>(CHANNEL_COLL_NAME)
+			.update_one(
+				doc! {"id": id},
+				doc! {
+					"$set": {
+						"archived": false,
+						"last_activity_at": thread.last_activity_at as i64,
+					},
+					"$addToSet": {"member_ids": user_id},
+				},
+				None,
+			)
+			.await?;
+
+		conn.del::<_, ()>(format!("channel_{id}"))
+			.await
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	/// Archives the given threads.
+	pub async fn archive_threads(&self, ids: &[i64]) -> Result<()> {
+		let mut conn = self.create_connection().await?;
+		let keys =
+			ids.iter().map(|id| format!("channel_{id}")).collect::<Vec<_>>();
+
+		let res = self
+			.client
+			.database(DB_NAME)
+			.collection::<models::Channel>(CHANNEL_COLL_NAME)
+			.update_many(
+				doc! {"id": {"$in": ids}},
+				doc! {"$set": {"archived": true}},
+				None,
+			)
+			.await?;
+
+		conn.del::<_, ()>(keys).await?;
+
+		log::debug!("archived {} threads", res.modified_count);
+		Ok(())
+	}
+
+	/// Deletes every message sent in a channel. This can take a while for
+	/// busy channels, so it should not be awaited in a request handler.
+	pub async fn delete_channel_messages(
+		&self, channel_id: i64,
+	) -> Result<u64> {
+		let mut conn = self.create_connection().await?;
+		let collection = self
+			.client
+			.database(DB_NAME)
+			.collection::<models::Message>(MESSAGE_COLL_NAME);
+		let mut cursor =
+			collection.find(doc! {"channel_id": channel_id}, None).await?;
+		let mut keys = Vec::new();
+
+		while let Some(message) = cursor.try_next().await? {
+			keys.push(format!("message_{}", message.id));
+
+			if keys.len() == 100 {
+				conn.del::<_, ()>(std::mem::take(&mut keys)).await?;
+			}
+		}
+
+		if !keys.is_empty() {
+			conn.del::<_, ()>(keys).await?;
+		}
+
+		let res = collection
+			.delete_many(doc! {"channel_id": channel_id}, None)
+			.await?;
+
+		log::debug!(
+			"deleted {} messages from channel {channel_id}",
+			res.deleted_count
+		);
+		Ok(res.deleted_count)
+	}
+
+	pub async fn insert_message(&self, message: models::Message) -> Result<()> {
+		let mut conn = self.create_connection().await?;
+
+		set_value(&mut conn, &format!("message_{}", message.id), &message)
+			.await?;
+
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Message>(MESSAGE_COLL_NAME)
+			.insert_one(message, None)
+			.await
+			.map(|_| ())
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	/// Pins or unpins a message.
+	pub async fn set_pinned(
+		&self, message_id: i64, pinned: bool,
+	) -> Result<()> {
+		let mut conn = self.create_connection().await?;
+		let key = format!("message_{message_id}");
+
+		if conn.exists::<_, bool>(&key).await? {
+			conn.hset::<_, _, _, ()>(&key, "pinned", pinned).await?;
+		}
+
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Message>(MESSAGE_COLL_NAME)
+			.update_one(
+				doc! {"id": message_id},
+				doc! {"$set": {"pinned": pinned}},
+				None,
+			)
+			.await
+			.map(|_| {
+				log::debug!("set pinned of message {message_id} to {pinned}");
+			})
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	/// Fetches the pinned messages of a channel, newest first.
+	pub async fn fetch_pins(
+		&self, channel_id: i64,
+	) -> Result<Vec<models::Message>> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Message>(MESSAGE_COLL_NAME)
+			.find(
+				doc! {"channel_id": channel_id, "pinned": true},
+				FindOptions::builder().sort(doc! {"id": -1}).build(),
+			)
+			.await?
+			.try_collect()
+			.await
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	pub async fn count_pins(&self, channel_id: i64) -> Result<u64> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Message>(MESSAGE_COLL_NAME)
+			.count_documents(
+				doc! {"channel_id": channel_id, "pinned": true},
+				None,
+			)
+			.await
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	/// Marks every message up to `message_id` as read by a user. The read
+	/// state never moves backward, acknowledging an older message than the
+	/// last acknowledged one, as another device of the user may, leaves it as
+	/// it is. Returns the stored read state.
+	pub async fn ack_message(
+		&self, user_id: i64, channel_id: i64, message_id: i64,
+	) -> Result<models::ReadState> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::ReadState>(READ_STATE_COLL_NAME)
+			.find_one_and_update(
+				doc! {"user_id": user_id, "channel_id": channel_id},
+				doc! {"$max": {"last_message_id": message_id}},
+				FindOneAndUpdateOptions::builder()
+					.upsert(true)
+					.return_document(ReturnDocument::After)
+					.build(),
+			)
+			.await?
+			.ok_or_else(|| anyhow::anyhow!("Read state was not upserted"))
+	}
+
+	/// Fetches the read states of a user in every channel they have read.
+	pub async fn fetch_read_states(
+		&self, user_id: i64,
+	) -> Result<Vec<models::ReadState>> {
+		self.client
+			.database(DB_NAME)
+			.collection::<models::ReadState>(READ_STATE_COLL_NAME)
+			.find(doc! {"user_id": user_id}, None)
+			.await?
+			.try_collect()
+			.await
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	/// Counts the messages in each channel sent after the last message the
+	/// user acknowledged in it, given as `(channel_id, last_message_id)`, and
+	/// how many of those mention the user. Channels without unread messages
+	/// are left out. Snowflakes are ordered by creation time, so newer
+	/// messages always have greater ids.
+	pub async fn count_unread_channels(
+		&self, user: &models::User, read_states: &[(i64, Option<i64>)],
+	) -> Result<HashMap<i64, (u64, u64)>> {
+		#[derive(Deserialize)]
+		struct UnreadCount {
+			#[serde(rename = "_id")]
+			channel_id: i64,
+			unread: u64,
+			mentions: u64,
+		}
+
+		if read_states.is_empty() {
+			return Ok(HashMap::new());
+		}
+
+		let unread = read_states
+			.iter()
+			.map(|(channel_id, last_message_id)| {
+				let mut query = doc! {"channel_id": channel_id};
+
+				if let Some(last_message_id) = last_message_id {
+					query.insert("id", doc! {"$gt": last_message_id});
+				}
+
+				Bson::from(query)
+			})
+			.collect::<Vec<_>>();
+		let pipeline = [
+			doc! {"$match": {"$or": unread}},
+			doc! {
+				"$group": {
+					"_id": "$channel_id",
+					"unread": {"$sum": 1},
+					"mentions": {"$sum": {"$cond": [mentions_expr(user), 1, 0]}},
+				}
+			},
+		];
+
+		let mut cursor = self
+			.client
+			.database(DB_NAME)
+			.collection::<models::Message>(MESSAGE_COLL_NAME)
+			.aggregate(pipeline, None)
+			.await?;
+		let mut counts = HashMap::new();
+
+		while let Some(count) = cursor.try_next().await? {
+			let count = bson::from_document::<UnreadCount>(count)?;
+
+			counts.insert(count.channel_id, (count.unread, count.mentions));
+		}
+
+		Ok(counts)
+	}
+
+	/// Counts the messages in a channel sent after `last_message_id`, and how
+	/// many of those mention the user.
+	pub async fn count_unread(
+		&self, user: &models::User, channel_id: i64,
+		last_message_id: Option<i64>,
+	) -> Result<(u64, u64)> {
+		Ok(self
+			.count_unread_channels(user, &[(channel_id, last_message_id)])
+			.await?
+			.remove(&channel_id)
+			.unwrap_or((0, 0)))
+	}
+
+	/// Fetches the most recent messages which mention a user in the given
+	/// channels, newest first.
+	pub async fn fetch_mentions(
+		&self, user: &models::User, channel_ids: &[i64], before: Option<i64>,
+		limit: i64,
+	) -> Result<Vec<models::Message>> {
+		let mut query = doc! {
+			"$or": mentions_query(user),
+			"author_id": {"$ne": user.id},
+			"channel_id": {"$in": channel_ids},
+		};
+
+		if let Some(before) = before {
+			query.insert("id", doc! {"$lt": before});
+		}
+
+		self.client
+			.database(DB_NAME)
+			.collection::<models::Message>(MESSAGE_COLL_NAME)
+			.find(
+				query,
+				FindOptions::builder()
+					.sort(doc! {"id": -1})
+					.limit(limit)
+					.build(),
+			)
+			.await?
+			.try_collect()
+			.await
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	/// Stores a presence which expires after `ttl` seconds unless refreshed.
+	pub async fn set_presence(
+		&self, presence: &models::Presence, ttl: usize,
+	) -> Result<()> {
+		let mut conn = self.create_connection().await?;
+		let key = format!("presence_{}", presence.user_id);
+
+		// Clear the previous presence so unset fields do not linger.
+		conn.del::<_, ()>(&key).await?;
+		set_value(&mut conn, &key, presence).await?;
+		conn.expire::<_, ()>(&key, ttl).await.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	/// Fetches the presence of a user, who is offline if none is stored.
+	pub async fn fetch_presence(
+		&self, user_id: i64,
+	) -> Result<models::Presence> {
+		let mut conn = self.create_connection().await?;
+
+		match get_value::<models::Presence>(
+			&mut conn,
+			&format!("presence_{user_id}"),
+		)
+		.await
+		{
+			Ok(presence) => Ok(presence),
+			Err(_) => {
+				Ok(models::Presence::new(user_id, models::Status::Offline))
+			}
+		}
+	}
+
+	/// Extends the expiry of the presences of online users.
+	pub async fn refresh_presences(
+		&self, user_ids: &[i64], ttl: usize,
+	) -> Result<()> {
+		let mut conn = self.create_connection().await?;
+
+		for id in user_ids {
+			conn.expire::<_, ()>(format!("presence_{id}"), ttl).await?;
+		}
+
+		Ok(())
+	}
+
+	pub async fn remove_presence(&self, user_id: i64) -> Result<()> {
+		let mut conn = self.create_connection().await?;
+
+		conn.del::<_, ()>(format!("presence_{user_id}"))
+			.await
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+
+	pub async fn modify_user(
+		&self, user: &mut models::User, data: ModifyUser,
+	) -> Result<()> {
+		let id = user.id;
+		let mut conn = self.create_connection().await?;
+
+		if let Some(ref username) = data.username {
+			user.username = username.clone();
+		}
+
+		if let Some(ref avatar) = data.avatar {
+			user.avatar = Some(avatar.clone());
+		}
+
+		if get_value::<models::User>(&mut conn, &format!("user_{}", user.id))
+			.await
+			.is_ok()
+		{
+			let mut fields: Vec<(String, String)> = vec![];
+
+			if let Some(ref username) = data.username {
+				fields.push(("username".to_string(), username.clone()));
+			}
+
+			if let Some(ref avatar) = data.avatar {
+				fields.push(("avatar".to_string(), avatar.clone()));
+			}
+
+			conn.hset_multiple::<_, _, _, ()>(
+				format!("user_{id}"),
+				fields.as_slice(),
+			)
+			.await
+			.map(|_| {
+				log::debug!("modified user {id} in cache");
+			})
+			.map_err(|e| anyhow::anyhow!(e))?;
+		}
+
+		let mut fields = doc! {};
+
+		if let Some(username) = data.username {
+			fields.insert("username", username);
+		}
+
+		if let Some(avatar) = data.avatar {
+			fields.insert("avatar", avatar);
+		}
+
+		self.client
+			.database(DB_NAME)
+			.collection::<models::User>(USER_COLL_NAME)
+			.update_one(doc! {"id": id}, doc! {"$set": fields}, None)
+			.await
+			.map(|_| {
+				log::debug!("modified user {} in db", id);
+			})
+			.map_err(|e| anyhow::anyhow!(e))
+	}
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct ModifyChannel {
+	/// Channel name
+	#[validate(length(min = 1, max = 100), non_control_character)]
+	#[schema(min_length = 1, max_length = 100, pattern = r"^\P{Cc}*$")]
+	pub name: Option<String>,
+	/// Channel topic, `null` clears it
+	#[serde(
+		default,
+		alias = "topic",
+		deserialize_with = "utils::deserialize_some"
+	)]
+	#[validate(length(max = 1024))]
+	#[schema(value_type = Option<String>, max_length = 1024)]
+	pub description: Option<Option<String>>,
+	/// Sorting position of the channel
+	pub position: Option<i64>,
+	/// Id of the parent category, `null` removes the channel from its category
+	#[serde(default, deserialize_with = "utils::deserialize_some")]
+	#[schema(value_type = Option<i64>)]
+	pub parent_id: Option<Option<i64>>,
+	/// Whether a thread is archived
+	pub archived: Option<bool>,
+	/// Minutes of inactivity after which a thread is archived
+	#[validate(custom = "utils::validate_auto_archive_duration")]
+	#[schema(minimum = 60, maximum = 10080)]
+	pub auto_archive_duration: Option<u64>,
+}
+
+#[derive(Deserialize, Validate, IntoParams)]
+pub struct AuditLogFilter {
+	/// Only entries of actions taken by this user
+	pub user_id: Option<i64>,
+	/// Only entries of actions which affected this user, channel or role
+	pub target_id: Option<i64>,
+	/// Only entries of this kind of action
+	pub action: Option<AuditLogAction>,
+	/// Only entries before this entry ID
+	pub before: Option<i64>,
+	/// Max number of entries to return (1-100)
+	#[serde(default = "default_audit_log_limit")]
+	#[validate(range(min = 1, max = 100))]
+	#[param(minimum = 1, maximum = 100)]
+	pub limit: i64,
+}
+
+fn default_audit_log_limit() -> i64 {
+	50
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct ModifyPresence {
+	/// User's status
+	pub status: Status,
+	/// Custom status text
+	#[validate(length(max = 128), non_control_character)]
+	#[schema(max_length = 128, pattern = r"^\P{Cc}*$")]
+	pub custom_status: Option<String>,
 }
 
-#[derive(Deserialize, Validate)]
+#[derive(Deserialize, Validate, ToSchema)]
 pub struct ModifyUser {
 	/// User's username
 	#[validate(length(min = 2, max = 32), non_control_character)]
+	#[schema(min_length = 2, max_length = 32, pattern = r"^\P{Cc}*$")]
 	pub username: Option<String>,
 	/// User's avatar
 	pub avatar: Option<String>,
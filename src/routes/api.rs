@@ -1,24 +1,54 @@
-use super::middleware::Auth;
+use super::middleware::{Auth, RateLimit};
 use crate::{
-	models::{Channel, Message, User},
-	redis::{ModifyUser, RedisFetcher},
-	routes::{DB_NAME, MESSAGE_COLL_NAME},
-	ws::server::{self, CreateMessage, Join, ListChannels, ShikiServer},
+	audit_log::{self, Reason},
+	mentions,
+	models::{
+		self, AuditLogAction, AuditLogChange, AuditLogEntry, Channel,
+		ChannelType, Message, Snowflakes, Status, User,
+	},
+	permissions::{self, Permissions},
+	redis::{
+		AuditLogFilter, FetchUserId, ModifyChannel, ModifyPresence, ModifyUser,
+		RedisFetcher,
+	},
+	routes::{
+		AUDIT_LOG_COLL_NAME, BAN_COLL_NAME, DB_NAME, INVITE_COLL_NAME,
+		MEMBER_COLL_NAME, MESSAGE_COLL_NAME, READ_STATE_COLL_NAME,
+	},
+	utils,
+	version::ApiVersion,
+	ws::server::{
+		self, AckMessage, AddMember, BanMember, CreateMessage, DeleteChannel,
+		Join, ListChannels, ReadState, RemoveMember, ShikiServer, StartTyping,
+		TypingResult, UnbanMember, UpdateChannel, UpdatePins, UpdatePresence,
+	},
 };
 use actix::Addr;
-use actix_web::{get, patch, post, web, HttpResponse, Responder};
+use actix_web::{delete, get, patch, post, put, web, HttpResponse, Responder};
 use futures::TryStreamExt;
 use futures_util::lock::Mutex;
-use mongodb::{bson::doc, options::FindOptions, Client};
+use mongodb::{
+	bson::doc,
+	options::{FindOptions, IndexOptions},
+	Client, IndexModel,
+};
 use serde::{Deserialize, Serialize};
+use shiki_client::model::{GetMessage, InvitePreview};
 use snowflake::SnowflakeIdGenerator;
 use std::{
 	collections::{HashMap, HashSet},
 	sync::atomic::{AtomicUsize, Ordering},
 };
+use utoipa::{IntoParams, OpenApi, ToSchema};
 use validator::Validate;
 
 /// Displays state
+#[utoipa::path(
+	tag = "channels",
+	responses(
+		(status = 200, description = "The number of visitors", body = String, content_type = "text/plain"),
+	)
+)]
 #[get("/count")]
 async fn get_count(count: web::Data<AtomicUsize>) -> impl Responder {
 	let current_count = count.load(Ordering::SeqCst);
@@ -26,6 +56,12 @@ async fn get_count(count: web::Data<AtomicUsize>) -> impl Responder {
 }
 
 /// Shows all the channels available
+#[utoipa::path(
+	tag = "channels",
+	responses(
+		(status = 200, description = "The channels", body = [Channel]),
+	)
+)]
 #[get("/channels")]
 async fn get_channels_list(
 	srv: web::Data<Addr<crate::ws::server::ShikiServer>>,
@@ -36,62 +72,407 @@ async fn get_channels_list(
 	}
 }
 
-#[derive(Deserialize, Validate, Serialize)]
+#[derive(Deserialize, Validate, Serialize, ToSchema)]
 struct CreateChannel {
 	#[validate(length(min = 1), non_control_character)]
+	#[schema(min_length = 1, pattern = r"^\P{Cc}*$")]
 	pub name: String,
+	/// The channel topic
+	#[serde(alias = "topic")]
+	#[validate(length(max = 1024))]
+	#[schema(max_length = 1024)]
+	pub description: Option<String>,
+	/// The type of channel to create
+	#[serde(default, rename = "type")]
+	pub kind: ChannelType,
+	/// Id of the category to create the channel in
+	pub parent_id: Option<i64>,
+	/// Sorting position of the channel
+	#[serde(default)]
+	pub position: i64,
+	/// Id of the guild to create the channel in
+	pub guild_id: Option<i64>,
+}
+
+/// Checks that `parent_id` refers to a category that a channel of type `kind`
+/// can be placed in.
+async fn validate_parent(
+	fetcher: &RedisFetcher, id: Option<i64>, kind: ChannelType,
+	parent_id: Option<i64>,
+) -> Result<(), HttpResponse> {
+	let parent_id = match parent_id {
+		Some(parent_id) => parent_id,
+		None => return Ok(()),
+	};
+
+	match kind {
+		ChannelType::Category => {
+			return Err(HttpResponse::BadRequest()
+				.body("Categories cannot be placed in a category"));
+		}
+		ChannelType::Thread => {
+			return Err(
+				HttpResponse::BadRequest().body("Threads cannot be moved")
+			);
+		}
+		ChannelType::Text => {}
+	}
+
+	if Some(parent_id) == id {
+		return Err(HttpResponse::BadRequest()
+			.body("A channel cannot be its own parent"));
+	}
+
+	match fetcher.fetch_channel(parent_id).await {
+		Ok(Some(parent)) if parent.kind == ChannelType::Category => Ok(()),
+		Ok(Some(_)) => {
+			Err(HttpResponse::BadRequest().body("Parent must be a category"))
+		}
+		Ok(None) => {
+			Err(HttpResponse::BadRequest().body("Parent does not exist"))
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			Err(HttpResponse::InternalServerError()
+				.body("Something went wrong"))
+		}
+	}
 }
 
 /// Creates a new channel.
+#[utoipa::path(
+	tag = "channels",
+	responses(
+		(status = 200, description = "The created channel", body = Channel),
+		(status = 400, description = "Invalid channel"),
+		(status = 403, description = "Not a member of the guild"),
+	)
+)]
 #[post("/channels")]
 async fn create_channel(
 	data: web::Json<CreateChannel>, fetcher: web::Data<RedisFetcher>,
 	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>,
-	srv: web::Data<Addr<ShikiServer>>, user: User,
+	srv: web::Data<Addr<ShikiServer>>, user: User, reason: Reason,
 ) -> HttpResponse {
 	if let Err(err) = data.validate() {
 		return HttpResponse::BadRequest().json(err);
 	}
 
 	let data = data.into_inner();
+
+	if data.kind == ChannelType::Thread {
+		return HttpResponse::BadRequest()
+			.body("Threads must be started from a message");
+	}
+
+	if let Err(res) =
+		validate_parent(&fetcher, None, data.kind, data.parent_id).await
+	{
+		return res;
+	}
+
+	if let Some(guild_id) = data.guild_id {
+		match fetcher.fetch_member(guild_id, user.id).await {
+			Ok(Some(_)) => {}
+			Ok(None) => {
+				return HttpResponse::Forbidden()
+					.body("Not a member of this guild");
+			}
+			Err(err) => {
+				log::error!("{:?}", err);
+				return HttpResponse::InternalServerError()
+					.body("Something went wrong");
+			}
+		}
+	}
+
 	let id = snowflake_gen.lock().await.real_time_generate();
-	let channel = Channel::new(id, &data.name, None, user.id);
-	let res = fetcher.insert_channel(channel).await;
+	let mut channel = Channel::new(id, &data.name, data.description, user.id);
+
+	channel.kind = data.kind;
+	channel.position = data.position;
+	channel.parent_id = data.parent_id;
+	channel.guild_id = data.guild_id;
+
+	let res = fetcher.insert_channel(channel.clone()).await;
 
 	if res.is_err() {
 		return HttpResponse::InternalServerError()
 			.body("Something went wrong");
 	}
 
+	audit_log::record(
+		&fetcher,
+		&snowflake_gen,
+		AuditLogEntry {
+			target_id: Some(channel.id),
+			changes: audit_log::diff(None, Some(&channel)),
+			reason: reason.0,
+			..AuditLogEntry::new(
+				channel.guild_id,
+				user.id,
+				AuditLogAction::ChannelCreate,
+			)
+		},
+	)
+	.await;
+
+	match srv.send(server::Channel::from(channel)).await {
+		Ok(Some(channel)) => HttpResponse::Ok().json(channel),
+		Ok(None) => HttpResponse::BadRequest().body("Channel already exists"),
+		Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+	}
+}
+
+/// Fetches a channel and checks that `user` has `required` in it. Returns the
+/// channel along with all of the user's permissions in it. Users who are not
+/// allowed to do anything in the channel, such as those outside of its guild,
+/// are turned away even when nothing is `required`.
+async fn fetch_permitted_channel(
+	fetcher: &RedisFetcher, channel_id: i64, user: &User, required: Permissions,
+) -> Result<(Channel, Permissions), HttpResponse> {
+	let channel = match fetcher.fetch_channel(channel_id).await {
+		Ok(Some(channel)) => channel,
+		Ok(None) => {
+			return Err(HttpResponse::NotFound().body("Channel does not exist"));
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			return Err(HttpResponse::InternalServerError()
+				.body("Something went wrong"));
+		}
+	};
+
+	match permissions::in_channel(fetcher, user, &channel).await {
+		Ok(permissions)
+			if permissions != Permissions::NONE
+				&& permissions.contains(required) =>
+		{
+			Ok((channel, permissions))
+		}
+		Ok(_) => Err(HttpResponse::Forbidden().body("Missing permissions")),
+		Err(err) => {
+			log::error!("{:?}", err);
+			Err(HttpResponse::InternalServerError()
+				.body("Something went wrong"))
+		}
+	}
+}
+
+/// Updates a channel's name, topic, position or category. Returns the updated
+/// channel on success.
+#[utoipa::path(
+	tag = "channels",
+	responses(
+		(status = 200, description = "The updated channel", body = Channel),
+		(status = 400, description = "Invalid changes"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Channel does not exist"),
+	)
+)]
+#[patch("/channels/{channel_id}")]
+async fn modify_channel(
+	channel_id: web::Path<i64>, data: web::Json<ModifyChannel>,
+	fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>,
+	srv: web::Data<Addr<ShikiServer>>, user: User, reason: Reason,
+) -> HttpResponse {
+	if let Err(err) = data.validate() {
+		return HttpResponse::BadRequest().json(err);
+	}
+
+	let mut channel = match fetch_permitted_channel(
+		&fetcher,
+		*channel_id,
+		&user,
+		Permissions::MANAGE_CHANNELS,
+	)
+	.await
+	{
+		Ok((channel, _)) => channel,
+		Err(res) => return res,
+	};
+
+	if let Some(parent_id) = data.parent_id {
+		if let Err(res) =
+			validate_parent(&fetcher, Some(channel.id), channel.kind, parent_id)
+				.await
+		{
+			return res;
+		}
+	}
+
+	if channel.kind != ChannelType::Thread
+		&& (data.archived.is_some() || data.auto_archive_duration.is_some())
+	{
+		return HttpResponse::BadRequest().body("Only threads can be archived");
+	}
+
+	let before = channel.clone();
+
+	if let Err(err) =
+		fetcher.modify_channel(&mut channel, data.into_inner()).await
+	{
+		log::error!("{:?}", err);
+		return HttpResponse::InternalServerError()
+			.body("Something went wrong");
+	}
+
+	audit_log::record(
+		&fetcher,
+		&snowflake_gen,
+		AuditLogEntry {
+			target_id: Some(channel.id),
+			changes: audit_log::diff(Some(&before), Some(&channel)),
+			reason: reason.0,
+			..AuditLogEntry::new(
+				channel.guild_id,
+				user.id,
+				AuditLogAction::ChannelUpdate,
+			)
+		},
+	)
+	.await;
+
 	match srv
-		.send(server::Channel {
-			id,
-			guild_id: None,
-			name: data.name,
-			sessions: HashSet::new(),
-		})
+		.send(UpdateChannel { channel: server::Channel::from(channel) })
 		.await
 	{
 		Ok(Some(channel)) => HttpResponse::Ok().json(channel),
-		Ok(None) => HttpResponse::BadRequest().body("Channel already exists"),
+		Ok(None) => HttpResponse::NotFound().body("Channel does not exist"),
+		Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+	}
+}
+
+/// Deletes a channel along with its threads. Channels in a deleted category are
+/// moved out of it, and the messages of the channel are removed in the
+/// background.
+#[utoipa::path(
+	tag = "channels",
+	responses(
+		(status = 200, description = "The deleted channel", body = Channel),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Channel does not exist"),
+	)
+)]
+#[delete("/channels/{channel_id}")]
+async fn delete_channel(
+	channel_id: web::Path<i64>, fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>,
+	srv: web::Data<Addr<ShikiServer>>, user: User, reason: Reason,
+) -> HttpResponse {
+	let channel = match fetch_permitted_channel(
+		&fetcher,
+		*channel_id,
+		&user,
+		Permissions::MANAGE_CHANNELS,
+	)
+	.await
+	{
+		Ok((channel, _)) => channel,
+		Err(res) => return res,
+	};
+
+	let threads = match fetcher.delete_channel(channel.id).await {
+		Ok(threads) => threads,
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	};
+
+	audit_log::record(
+		&fetcher,
+		&snowflake_gen,
+		AuditLogEntry {
+			target_id: Some(channel.id),
+			changes: audit_log::diff(Some(&channel), None),
+			reason: reason.0,
+			..AuditLogEntry::new(
+				channel.guild_id,
+				user.id,
+				AuditLogAction::ChannelDelete,
+			)
+		},
+	)
+	.await;
+
+	let fetcher = fetcher.into_inner();
+	let id = channel.id;
+
+	actix_web::rt::spawn(async move {
+		for id in threads.into_iter().chain(std::iter::once(id)) {
+			if let Err(err) = fetcher.delete_channel_messages(id).await {
+				log::error!(
+					"Failed to delete messages of channel {id}: {:?}",
+					err
+				);
+			}
+		}
+	});
+
+	match srv.send(DeleteChannel { id }).await {
+		Ok(_) => HttpResponse::Ok().json(server::Channel::from(channel)),
 		Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
 	}
 }
 
 /// Joins a channel
 // NOTE: This is should be an internal feature, caused by the future addition of channel viewing permissions. Editing said permissions should allow a user to effectively "join" a channel.
+#[utoipa::path(
+	tag = "channels",
+	responses(
+		(status = 200, description = "The joined channel", body = Channel),
+		(status = 400, description = "Channel does not exist"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Channel does not exist"),
+	)
+)]
 #[post("/channels/{channel_id}/join")]
 async fn join_channel(
-	channel_id: web::Path<i64>, srv: web::Data<Addr<ShikiServer>>,
+	channel_id: web::Path<i64>, fetcher: web::Data<RedisFetcher>,
+	srv: web::Data<Addr<ShikiServer>>, user: User,
 ) -> HttpResponse {
-	match srv.send(Join { client_id: 0, channel_id: *channel_id }).await {
+	// Only users allowed in the channel, such as the members of its guild,
+	// may join it.
+	let channel = match fetch_permitted_channel(
+		&fetcher,
+		*channel_id,
+		&user,
+		Permissions::NONE,
+	)
+	.await
+	{
+		Ok((channel, _)) => channel,
+		Err(res) => return res,
+	};
+
+	// Banned users are no longer members, but a ban is checked on its own so
+	// it holds even if they are.
+	if let Some(guild_id) = channel.guild_id {
+		match fetcher.fetch_ban(guild_id, user.id).await {
+			Ok(None) => {}
+			Ok(Some(_)) => {
+				return HttpResponse::Forbidden()
+					.body("You are banned from this guild");
+			}
+			Err(err) => {
+				log::error!("{:?}", err);
+				return HttpResponse::InternalServerError()
+					.body("Something went wrong");
+			}
+		}
+	}
+
+	match srv.send(Join { user_id: user.id, channel_id: *channel_id }).await {
 		Ok(Some(channel)) => HttpResponse::Ok().json(channel),
 		Ok(None) => HttpResponse::BadRequest().body("Channel does not exist"),
 		Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
 	}
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
 struct GetMessages {
 	/// Get messages before this message ID
 	#[serde(default = "default_before")]
@@ -101,6 +482,7 @@ struct GetMessages {
 	after: Option<i64>,
 	/// Max number of messages to return (1-100)
 	#[serde(default = "default_limit")]
+	#[param(minimum = 1, maximum = 100)]
 	limit: i64,
 }
 
@@ -116,31 +498,35 @@ fn default_limit() -> i64 {
 	50
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
-pub struct GetMessage {
-	/// The id of the message
-	pub id: i64,
-	/// The id of the channel the message was sent in
-	pub channel_id: i64,
-	/// The content of the message
-	pub content: String,
-	/// Unix timestamp for when the message was created
-	pub created_at: usize,
-	/// User who sent the message
-	pub author: server::User,
-}
-
 /// Fetches the messages in a channel
+#[utoipa::path(
+	tag = "messages",
+	params(GetMessages),
+	responses(
+		(status = 200, description = "The messages, oldest first", body = [GetMessage]),
+		(status = 400, description = "Invalid limit"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Channel does not exist"),
+	)
+)]
 #[get("/channels/{channel_id}/messages")]
 async fn get_messages(
 	channel_id: web::Path<i64>, client: web::Data<Client>,
 	data: web::Query<GetMessages>, fetcher: web::Data<RedisFetcher>,
+	user: User,
 ) -> HttpResponse {
 	if data.limit < 1 || data.limit > 100 {
 		return HttpResponse::BadRequest()
 			.body("Limit must be between 1 and 100");
 	}
 
+	if let Err(res) =
+		fetch_permitted_channel(&fetcher, *channel_id, &user, Permissions::NONE)
+			.await
+	{
+		return res;
+	}
+
 	let mut query = doc! {
 		"channel_id": *channel_id
 	};
@@ -177,7 +563,7 @@ async fn get_messages(
 		)
 		.await;
 
-	let mut messages = match cursor {
+	let messages = match cursor {
 		Ok(cursor) => match cursor.try_collect::<Vec<Message>>().await {
 			Ok(res) => res,
 			Err(_) => {
@@ -192,6 +578,20 @@ async fn get_messages(
 		}
 	};
 
+	match resolve_authors(&fetcher, messages).await {
+		Ok(messages) => HttpResponse::Ok().json(messages),
+		Err(err) => {
+			log::error!("{:?}", err);
+			HttpResponse::InternalServerError().body("Something went wrong")
+		}
+	}
+}
+
+/// Attaches the author to each message. Authors which no longer exist are
+/// shown as a deleted user.
+async fn resolve_authors(
+	fetcher: &RedisFetcher, mut messages: Vec<Message>,
+) -> anyhow::Result<Vec<GetMessage>> {
 	// Make a set of all of the user IDs mentioned in the messages.
 	let user_ids = messages
 		.iter()
@@ -204,15 +604,8 @@ async fn get_messages(
 
 	// Fetch the users
 	let users = fetcher.fetch_users(Some(&user_ids)).await;
-	let users: HashMap<i64, server::User> = match users {
-		Ok(users) => {
-			users.into_iter().map(|user| (user.id, user.into())).collect()
-		}
-		Err(_) => {
-			return HttpResponse::InternalServerError()
-				.body("Something went wrong");
-		}
-	};
+	let users: HashMap<i64, server::User> =
+		users?.into_iter().map(|user| (user.id, user.into())).collect();
 
 	if users.len() < user_ids.len() {
 		log::warn!(
@@ -235,7 +628,7 @@ async fn get_messages(
 		}
 	}
 
-	let messages: Vec<GetMessage> = messages
+	Ok(messages
 		.into_iter()
 		.map(|msg| {
 			let author = if msg.author_id == 0 {
@@ -253,14 +646,25 @@ async fn get_messages(
 				content: msg.content,
 				created_at: msg.created_at,
 				author,
+				mentions: msg.mentions,
+				mention_roles: msg.mention_roles,
+				mention_everyone: msg.mention_everyone,
+				pinned: msg.pinned,
 			}
 		})
-		.collect();
-
-	HttpResponse::Ok().json(messages)
+		.collect())
 }
 
 /// Creates a new message
+#[utoipa::path(
+	tag = "messages",
+	responses(
+		(status = 200, description = "The created message", body = GetMessage),
+		(status = 400, description = "Channel does not exist"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Channel does not exist"),
+	)
+)]
 #[post("/channels/{channel_id}/messages")]
 async fn create_message(
 	channel_id: web::Path<i64>, data: web::Json<CreateMessage>,
@@ -269,6 +673,41 @@ async fn create_message(
 	srv: web::Data<Addr<ShikiServer>>, user: User,
 ) -> HttpResponse {
 	let mut data = data.into_inner();
+	let (mut channel, permissions) = match fetch_permitted_channel(
+		&fetcher,
+		*channel_id,
+		&user,
+		Permissions::SEND_MESSAGES,
+	)
+	.await
+	{
+		Ok(res) => res,
+		Err(res) => return res,
+	};
+
+	if let Err(err) = mentions::resolve(&fetcher, &mut data, permissions).await
+	{
+		log::error!("{:?}", err);
+		return HttpResponse::InternalServerError()
+			.body("Something went wrong");
+	}
+
+	// Sending a message in a thread unarchives it and makes the author one of
+	// its members.
+	if channel.kind == ChannelType::Thread {
+		let changed =
+			channel.archived || !channel.member_ids.contains(&user.id);
+
+		if let Err(err) = fetcher.touch_thread(&mut channel, user.id).await {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+
+		if changed {
+			srv.do_send(UpdateChannel { channel: channel.into() });
+		}
+	}
 
 	data.id = snowflake_gen.lock().await.real_time_generate();
 	data.channel_id = channel_id.into_inner();
@@ -297,24 +736,163 @@ async fn create_message(
 	}
 }
 
-/// Modify the requester's user account settings. Returns a user object on success.
-// TODO: Fire a User Update Gateway event.
-#[patch("/users/@me")]
-async fn modify_user(
-	data: web::Json<ModifyUser>, fetcher: web::Data<RedisFetcher>,
-	mut user: User,
+#[derive(Deserialize, Validate, ToSchema)]
+struct CreateThread {
+	#[validate(length(min = 1, max = 100), non_control_character)]
+	#[schema(min_length = 1, max_length = 100, pattern = r"^\P{Cc}*$")]
+	pub name: String,
+	/// Minutes of inactivity after which the thread is archived
+	#[serde(default = "default_auto_archive_duration")]
+	#[validate(custom = "utils::validate_auto_archive_duration")]
+	#[schema(minimum = 60, maximum = 10080)]
+	pub auto_archive_duration: u64,
+}
+
+fn default_auto_archive_duration() -> u64 {
+	1440
+}
+
+/// Starts a thread from a message. The user who starts the thread is its first
+/// member.
+#[utoipa::path(
+	tag = "threads",
+	responses(
+		(status = 200, description = "The created thread", body = Channel),
+		(status = 400, description = "Invalid thread"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Channel or message does not exist"),
+	)
+)]
+#[post("/channels/{channel_id}/messages/{message_id}/threads")]
+async fn create_thread(
+	path: web::Path<(i64, i64)>, data: web::Json<CreateThread>,
+	fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>,
+	srv: web::Data<Addr<ShikiServer>>, user: User,
 ) -> HttpResponse {
 	if let Err(err) = data.validate() {
 		return HttpResponse::BadRequest().json(err);
 	}
 
-	match fetcher.modify_user(&mut user, data.into_inner()).await {
-		Ok(_) => HttpResponse::Ok().json(server::User {
-			id: user.id,
-			username: user.username,
-			joined: user.created_at,
-			avatar: user.avatar,
-		}),
+	let (channel_id, message_id) = path.into_inner();
+	let parent = match fetch_permitted_channel(
+		&fetcher,
+		channel_id,
+		&user,
+		Permissions::SEND_MESSAGES,
+	)
+	.await
+	{
+		Ok((channel, _)) => channel,
+		Err(res) => return res,
+	};
+
+	if parent.kind != ChannelType::Text {
+		return HttpResponse::BadRequest()
+			.body("Threads can only be started in text channels");
+	}
+
+	match fetcher.fetch_message(message_id).await {
+		Ok(Some(message)) if message.channel_id == channel_id => {}
+		Ok(_) => {
+			return HttpResponse::NotFound().body("Message does not exist");
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	}
+
+	match fetcher.fetch_thread(message_id).await {
+		Ok(None) => {}
+		Ok(Some(_)) => {
+			return HttpResponse::BadRequest()
+				.body("A thread has already been started from this message");
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	}
+
+	let data = data.into_inner();
+	let id = snowflake_gen.lock().await.real_time_generate();
+	let mut thread = Channel::new(id, &data.name, None, user.id);
+
+	thread.kind = ChannelType::Thread;
+	thread.guild_id = parent.guild_id;
+	thread.parent_id = Some(channel_id);
+	thread.starter_message_id = Some(message_id);
+	thread.member_ids = vec![user.id].into();
+	thread.auto_archive_duration = data.auto_archive_duration;
+	thread.last_activity_at = thread.created_at;
+
+	if let Err(err) = fetcher.insert_channel(thread.clone()).await {
+		log::error!("{:?}", err);
+		return HttpResponse::InternalServerError()
+			.body("Something went wrong");
+	}
+
+	match srv.send(server::Channel::from(thread)).await {
+		Ok(Some(thread)) => HttpResponse::Ok().json(thread),
+		Ok(None) => HttpResponse::BadRequest().body("Channel already exists"),
+		Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+	}
+}
+
+/// Fetches a thread, responding with an error if the channel is not one or
+/// if the user is not allowed in its parent channel.
+async fn fetch_thread(
+	fetcher: &RedisFetcher, id: i64, user: &User,
+) -> Result<Channel, HttpResponse> {
+	let thread = match fetcher.fetch_channel(id).await {
+		Ok(Some(channel)) if channel.kind == ChannelType::Thread => channel,
+		Ok(_) => {
+			return Err(HttpResponse::NotFound().body("Thread does not exist"))
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			return Err(HttpResponse::InternalServerError()
+				.body("Something went wrong"));
+		}
+	};
+
+	if let Some(parent_id) = thread.parent_id {
+		fetch_permitted_channel(fetcher, parent_id, user, Permissions::NONE)
+			.await?;
+	}
+
+	Ok(thread)
+}
+
+/// Fetches the members of a thread
+#[utoipa::path(
+	tag = "threads",
+	responses(
+		(status = 200, description = "The members of the thread", body = [User]),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Thread does not exist"),
+	)
+)]
+#[get("/channels/{channel_id}/thread-members")]
+async fn get_thread_members(
+	channel_id: web::Path<i64>, fetcher: web::Data<RedisFetcher>, user: User,
+) -> HttpResponse {
+	let thread = match fetch_thread(&fetcher, *channel_id, &user).await {
+		Ok(thread) => thread,
+		Err(res) => return res,
+	};
+
+	if thread.member_ids.is_empty() {
+		return HttpResponse::Ok().json(Vec::<server::User>::new());
+	}
+
+	match fetcher.fetch_users(Some(&thread.member_ids)).await {
+		Ok(users) => HttpResponse::Ok().json(
+			users.into_iter().map(server::User::from).collect::<Vec<_>>(),
+		),
 		Err(err) => {
 			log::error!("{:?}", err);
 			HttpResponse::InternalServerError().body("Something went wrong")
@@ -322,16 +900,1784 @@ async fn modify_user(
 	}
 }
 
-pub fn routes(client: &RedisFetcher, cfg: &mut web::ServiceConfig) {
+/// Joins a thread
+#[utoipa::path(
+	tag = "threads",
+	responses(
+		(status = 204, description = "Joined the thread"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Thread does not exist"),
+	)
+)]
+#[put("/channels/{channel_id}/thread-members/@me")]
+async fn join_thread(
+	channel_id: web::Path<i64>, fetcher: web::Data<RedisFetcher>,
+	srv: web::Data<Addr<ShikiServer>>, user: User,
+) -> HttpResponse {
+	modify_thread_member(&fetcher, &srv, *channel_id, &user, true).await
+}
+
+/// Leaves a thread
+#[utoipa::path(
+	tag = "threads",
+	responses(
+		(status = 204, description = "Left the thread"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Thread does not exist"),
+	)
+)]
+#[delete("/channels/{channel_id}/thread-members/@me")]
+async fn leave_thread(
+	channel_id: web::Path<i64>, fetcher: web::Data<RedisFetcher>,
+	srv: web::Data<Addr<ShikiServer>>, user: User,
+) -> HttpResponse {
+	modify_thread_member(&fetcher, &srv, *channel_id, &user, false).await
+}
+
+async fn modify_thread_member(
+	fetcher: &RedisFetcher, srv: &Addr<ShikiServer>, thread_id: i64,
+	user: &User, add: bool,
+) -> HttpResponse {
+	let mut thread = match fetch_thread(fetcher, thread_id, user).await {
+		Ok(thread) => thread,
+		Err(res) => return res,
+	};
+
+	if thread.member_ids.contains(&user.id) == add {
+		return HttpResponse::NoContent().finish();
+	}
+
+	if let Err(err) =
+		fetcher.modify_thread_members(&mut thread, user.id, add).await
+	{
+		log::error!("{:?}", err);
+		return HttpResponse::InternalServerError()
+			.body("Something went wrong");
+	}
+
+	srv.do_send(UpdateChannel { channel: thread.into() });
+
+	HttpResponse::NoContent().finish()
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+struct CreateGuild {
+	#[validate(length(min = 1, max = 100), non_control_character)]
+	#[schema(min_length = 1, max_length = 100, pattern = r"^\P{Cc}*$")]
+	pub name: String,
+}
+
+/// Creates a new guild owned by the user.
+#[utoipa::path(
+	tag = "guilds",
+	responses(
+		(status = 200, description = "The created guild", body = Guild),
+		(status = 400, description = "Invalid guild"),
+	)
+)]
+#[post("/guilds")]
+async fn create_guild(
+	data: web::Json<CreateGuild>, fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>, user: User,
+) -> HttpResponse {
+	if let Err(err) = data.validate() {
+		return HttpResponse::BadRequest().json(err);
+	}
+
+	let id = snowflake_gen.lock().await.real_time_generate();
+	let guild = models::Guild::new(id, &data.name, user.id);
+
+	match fetcher.insert_guild(guild.clone()).await {
+		Ok(_) => HttpResponse::Ok().json(server::Guild::from(guild)),
+		Err(err) => {
+			log::error!("{:?}", err);
+			HttpResponse::InternalServerError().body("Something went wrong")
+		}
+	}
+}
+
+/// Fetches a guild
+#[utoipa::path(
+	tag = "guilds",
+	responses(
+		(status = 200, description = "The guild", body = Guild),
+		(status = 404, description = "Guild does not exist"),
+	)
+)]
+#[get("/guilds/{guild_id}")]
+async fn get_guild(
+	guild_id: web::Path<i64>, fetcher: web::Data<RedisFetcher>,
+) -> HttpResponse {
+	match fetcher.fetch_guild(*guild_id).await {
+		Ok(Some(guild)) => HttpResponse::Ok().json(server::Guild::from(guild)),
+		Ok(None) => HttpResponse::NotFound().body("Guild does not exist"),
+		Err(err) => {
+			log::error!("{:?}", err);
+			HttpResponse::InternalServerError().body("Something went wrong")
+		}
+	}
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+struct CreateInvite {
+	/// Seconds until the invite expires, 0 for never
+	#[serde(default = "default_max_age")]
+	#[validate(range(max = 604800))]
+	#[schema(maximum = 604800)]
+	pub max_age: u64,
+	/// How many times the invite can be used, 0 for unlimited
+	#[serde(default)]
+	#[validate(range(max = 100))]
+	#[schema(maximum = 100)]
+	pub max_uses: u64,
+}
+
+fn default_max_age() -> u64 {
+	86400
+}
+
+/// Creates an invite to a channel and its guild.
+#[utoipa::path(
+	tag = "invites",
+	responses(
+		(status = 200, description = "The created invite", body = Invite),
+		(status = 400, description = "Invalid invite"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Channel does not exist"),
+	)
+)]
+#[post("/channels/{channel_id}/invites")]
+async fn create_invite(
+	channel_id: web::Path<i64>, data: web::Json<CreateInvite>,
+	fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>, user: User,
+	reason: Reason,
+) -> HttpResponse {
+	if let Err(err) = data.validate() {
+		return HttpResponse::BadRequest().json(err);
+	}
+
+	let channel = match fetch_permitted_channel(
+		&fetcher,
+		*channel_id,
+		&user,
+		Permissions::CREATE_INVITE,
+	)
+	.await
+	{
+		Ok((channel, _)) => channel,
+		Err(res) => return res,
+	};
+
+	let invite =
+		models::Invite::new(&channel, user.id, data.max_age, data.max_uses);
+
+	if let Err(err) = fetcher.insert_invite(invite.clone()).await {
+		log::error!("{:?}", err);
+		return HttpResponse::InternalServerError()
+			.body("Something went wrong");
+	}
+
+	audit_log::record(
+		&fetcher,
+		&snowflake_gen,
+		AuditLogEntry {
+			target_id: Some(invite.channel_id),
+			changes: audit_log::diff(None, Some(&invite)),
+			reason: reason.0,
+			..AuditLogEntry::new(
+				invite.guild_id,
+				user.id,
+				AuditLogAction::InviteCreate,
+			)
+		},
+	)
+	.await;
+
+	HttpResponse::Ok().json(invite)
+}
+
+/// What a user sees of an invite before accepting it.
+/// Fetches an invite which can still be used.
+async fn fetch_usable_invite(
+	fetcher: &RedisFetcher, code: &str,
+) -> Result<models::Invite, HttpResponse> {
+	let now = chrono::Utc::now().timestamp() as usize;
+
+	match fetcher.fetch_invite(code).await {
+		Ok(Some(invite))
+			if !invite.is_expired(now)
+				&& (invite.max_uses == 0 || invite.uses < invite.max_uses) =>
+		{
+			Ok(invite)
+		}
+		Ok(_) => {
+			Err(HttpResponse::NotFound().body("Invalid or expired invite"))
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			Err(HttpResponse::InternalServerError()
+				.body("Something went wrong"))
+		}
+	}
+}
+
+/// Shows which guild and channel an invite leads to.
+#[utoipa::path(
+	tag = "invites",
+	responses(
+		(status = 200, description = "Where the invite leads to", body = InvitePreview),
+		(status = 404, description = "Invalid or expired invite"),
+	)
+)]
+#[get("/invites/{code}")]
+async fn get_invite(
+	code: web::Path<String>, fetcher: web::Data<RedisFetcher>,
+) -> HttpResponse {
+	let invite = match fetch_usable_invite(&fetcher, &code).await {
+		Ok(invite) => invite,
+		Err(res) => return res,
+	};
+
+	let preview = async {
+		let channel = match fetcher.fetch_channel(invite.channel_id).await? {
+			Some(channel) => channel,
+			None => return Ok(None),
+		};
+		let inviter = fetcher
+			.fetch_user(FetchUserId::Id(invite.inviter_id))
+			.await?
+			.map(server::User::from)
+			.unwrap_or_else(|| server::User {
+				username: "Deleted User".to_string(),
+				..Default::default()
+			});
+		let (guild, member_count) = match invite.guild_id {
+			Some(guild_id) => (
+				fetcher.fetch_guild(guild_id).await?.map(server::Guild::from),
+				Some(fetcher.count_members(guild_id).await?),
+			),
+			None => (None, None),
+		};
+
+		anyhow::Ok(Some(InvitePreview {
+			code: invite.code,
+			guild,
+			channel: (&server::Channel::from(channel)).into(),
+			inviter,
+			member_count,
+			expires_at: invite.expires_at,
+			max_uses: invite.max_uses,
+			uses: invite.uses,
+		}))
+	};
+
+	match preview.await {
+		Ok(Some(preview)) => HttpResponse::Ok().json(preview),
+		Ok(None) => HttpResponse::NotFound().body("Invalid or expired invite"),
+		Err(err) => {
+			log::error!("{:?}", err);
+			HttpResponse::InternalServerError().body("Something went wrong")
+		}
+	}
+}
+
+/// Joins the guild and channel of an invite. Members of the guild can accept
+/// an invite again without using it up.
+#[utoipa::path(
+	tag = "invites",
+	responses(
+		(status = 200, description = "The joined channel", body = Channel),
+		(status = 403, description = "Banned from the guild"),
+		(status = 404, description = "Invalid or expired invite"),
+	)
+)]
+#[post("/invites/{code}")]
+async fn accept_invite(
+	code: web::Path<String>, fetcher: web::Data<RedisFetcher>,
+	srv: web::Data<Addr<ShikiServer>>, user: User,
+) -> HttpResponse {
+	let invite = match fetch_usable_invite(&fetcher, &code).await {
+		Ok(invite) => invite,
+		Err(res) => return res,
+	};
+
+	if let Some(guild_id) = invite.guild_id {
+		match fetcher.fetch_ban(guild_id, user.id).await {
+			Ok(None) => {}
+			Ok(Some(_)) => {
+				return HttpResponse::Forbidden()
+					.body("You are banned from this guild");
+			}
+			Err(err) => {
+				log::error!("{:?}", err);
+				return HttpResponse::InternalServerError()
+					.body("Something went wrong");
+			}
+		}
+	}
+
+	let member = match invite.guild_id {
+		Some(guild_id) => fetcher.fetch_member(guild_id, user.id).await,
+		None => Ok(None),
+	};
+
+	let is_member = match member {
+		Ok(member) => member.is_some(),
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	};
+
+	if !is_member {
+		match fetcher.use_invite(&invite).await {
+			Ok(true) => {}
+			Ok(false) => {
+				return HttpResponse::NotFound()
+					.body("Invalid or expired invite");
+			}
+			Err(err) => {
+				log::error!("{:?}", err);
+				return HttpResponse::InternalServerError()
+					.body("Something went wrong");
+			}
+		}
+
+		if let Some(guild_id) = invite.guild_id {
+			let member = models::Member::new(guild_id, user.id);
+
+			if let Err(err) = fetcher.insert_member(member.clone()).await {
+				log::error!("{:?}", err);
+				return HttpResponse::InternalServerError()
+					.body("Something went wrong");
+			}
+
+			srv.do_send(AddMember {
+				guild_id,
+				user: user.clone().into(),
+				joined_at: member.joined_at,
+			});
+		}
+	}
+
+	// Users without a gateway session are not known to the server, so the
+	// channel is sent back as stored rather than as joined.
+	srv.do_send(Join { user_id: user.id, channel_id: invite.channel_id });
+
+	match fetcher.fetch_channel(invite.channel_id).await {
+		Ok(Some(channel)) => {
+			HttpResponse::Ok().json(server::Channel::from(channel))
+		}
+		Ok(None) => HttpResponse::NotFound().body("Channel does not exist"),
+		Err(err) => {
+			log::error!("{:?}", err);
+			HttpResponse::InternalServerError().body("Something went wrong")
+		}
+	}
+}
+
+/// Revokes an invite. Only its creator or users who can manage its channel may
+/// revoke it.
+#[utoipa::path(
+	tag = "invites",
+	responses(
+		(status = 204, description = "Revoked the invite"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Invite does not exist"),
+	)
+)]
+#[delete("/invites/{code}")]
+async fn delete_invite(
+	code: web::Path<String>, fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>, user: User,
+	reason: Reason,
+) -> HttpResponse {
+	let invite = match fetcher.fetch_invite(&code).await {
+		Ok(Some(invite)) => invite,
+		Ok(None) => {
+			return HttpResponse::NotFound().body("Invite does not exist");
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	};
+
+	if invite.inviter_id != user.id {
+		if let Err(res) = fetch_permitted_channel(
+			&fetcher,
+			invite.channel_id,
+			&user,
+			Permissions::MANAGE_CHANNELS,
+		)
+		.await
+		{
+			return res;
+		}
+	}
+
+	if let Err(err) = fetcher.delete_invite(&invite.code).await {
+		log::error!("{:?}", err);
+		return HttpResponse::InternalServerError()
+			.body("Something went wrong");
+	}
+
+	audit_log::record(
+		&fetcher,
+		&snowflake_gen,
+		AuditLogEntry {
+			target_id: Some(invite.channel_id),
+			changes: audit_log::diff(Some(&invite), None),
+			reason: reason.0,
+			..AuditLogEntry::new(
+				invite.guild_id,
+				user.id,
+				AuditLogAction::InviteDelete,
+			)
+		},
+	)
+	.await;
+
+	HttpResponse::NoContent().finish()
+}
+
+/// Fetches a guild and checks that `user` has `required` in it.
+async fn fetch_permitted_guild(
+	fetcher: &RedisFetcher, guild_id: i64, user: &User, required: Permissions,
+) -> Result<models::Guild, HttpResponse> {
+	let guild = match fetcher.fetch_guild(guild_id).await {
+		Ok(Some(guild)) => guild,
+		Ok(None) => {
+			return Err(HttpResponse::NotFound().body("Guild does not exist"));
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			return Err(HttpResponse::InternalServerError()
+				.body("Something went wrong"));
+		}
+	};
+
+	match permissions::in_guild(fetcher, user, &guild).await {
+		Ok(permissions) if permissions.contains(required) => Ok(guild),
+		Ok(_) => Err(HttpResponse::Forbidden().body("Missing permissions")),
+		Err(err) => {
+			log::error!("{:?}", err);
+			Err(HttpResponse::InternalServerError()
+				.body("Something went wrong"))
+		}
+	}
+}
+
+/// Whether a moderation action may target a user. Nobody can act on the owner
+/// of a guild or on themselves.
+fn can_moderate(guild: &models::Guild, user: &User, target_id: i64) -> bool {
+	target_id != guild.owner_id && target_id != user.id
+}
+
+/// Removes a member from a guild.
+#[utoipa::path(
+	tag = "moderation",
+	responses(
+		(status = 204, description = "Removed the member"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Guild or member does not exist"),
+	)
+)]
+#[delete("/guilds/{guild_id}/members/{user_id}")]
+async fn kick_member(
+	path: web::Path<(i64, i64)>, fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>,
+	srv: web::Data<Addr<ShikiServer>>, user: User, reason: Reason,
+) -> HttpResponse {
+	let (guild_id, user_id) = path.into_inner();
+	let guild = match fetch_permitted_guild(
+		&fetcher,
+		guild_id,
+		&user,
+		Permissions::KICK_MEMBERS,
+	)
+	.await
+	{
+		Ok(guild) => guild,
+		Err(res) => return res,
+	};
+
+	if !can_moderate(&guild, &user, user_id) {
+		return HttpResponse::Forbidden().body("Cannot moderate this user");
+	}
+
+	match fetcher.remove_member(guild_id, user_id).await {
+		Ok(true) => {}
+		Ok(false) => {
+			return HttpResponse::NotFound().body("Member does not exist");
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	}
+
+	srv.do_send(RemoveMember { guild_id, user_id });
+	audit_log::record(
+		&fetcher,
+		&snowflake_gen,
+		AuditLogEntry {
+			target_id: Some(user_id),
+			reason: reason.0,
+			..AuditLogEntry::new(Some(guild_id), user.id, AuditLogAction::Kick)
+		},
+	)
+	.await;
+
+	HttpResponse::NoContent().finish()
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+struct TimeoutMember {
+	/// Unix timestamp until which the member may not send messages, `null`
+	/// lifts the timeout
+	pub communication_disabled_until: Option<usize>,
+}
+
+/// The longest a member can be timed out for, in seconds.
+const MAX_TIMEOUT: usize = 28 * 24 * 60 * 60;
+
+/// Times out a member, or lifts their timeout.
+#[utoipa::path(
+	tag = "moderation",
+	responses(
+		(status = 204, description = "Updated the timeout"),
+		(status = 400, description = "Invalid timeout"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Guild or member does not exist"),
+	)
+)]
+#[patch("/guilds/{guild_id}/members/{user_id}")]
+async fn timeout_member(
+	path: web::Path<(i64, i64)>, data: web::Json<TimeoutMember>,
+	fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>,
+	srv: web::Data<Addr<ShikiServer>>, user: User, reason: Reason,
+) -> HttpResponse {
+	let (guild_id, user_id) = path.into_inner();
+	let guild = match fetch_permitted_guild(
+		&fetcher,
+		guild_id,
+		&user,
+		Permissions::MODERATE_MEMBERS,
+	)
+	.await
+	{
+		Ok(guild) => guild,
+		Err(res) => return res,
+	};
+
+	if !can_moderate(&guild, &user, user_id) {
+		return HttpResponse::Forbidden().body("Cannot moderate this user");
+	}
+
+	let until = data.communication_disabled_until;
+	let now = chrono::Utc::now().timestamp() as usize;
+
+	if until.is_some_and(|until| until <= now || until - now > MAX_TIMEOUT) {
+		return HttpResponse::BadRequest()
+			.body("Timeouts must end in the future and last at most 28 days");
+	}
+
+	let member = match fetcher.fetch_member(guild_id, user_id).await {
+		Ok(Some(member)) => member,
+		Ok(None) => {
+			return HttpResponse::NotFound().body("Member does not exist");
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	};
+
+	if let Err(err) = fetcher.set_member_timeout(guild_id, user_id, until).await
+	{
+		log::error!("{:?}", err);
+		return HttpResponse::InternalServerError()
+			.body("Something went wrong");
+	}
+
+	srv.do_send(server::TimeoutMember {
+		guild_id,
+		user_id,
+		communication_disabled_until: until,
+	});
+	audit_log::record(
+		&fetcher,
+		&snowflake_gen,
+		AuditLogEntry {
+			target_id: Some(user_id),
+			changes: audit_log::diff(
+				Some(&member),
+				Some(&models::Member {
+					communication_disabled_until: until,
+					..member.clone()
+				}),
+			),
+			reason: reason.0,
+			..AuditLogEntry::new(
+				Some(guild_id),
+				user.id,
+				AuditLogAction::Timeout,
+			)
+		},
+	)
+	.await;
+
+	HttpResponse::NoContent().finish()
+}
+
+/// Fetches the audit log of a guild, newest first. Entries can be filtered by
+/// who took the action, what it affected and what kind of action it was.
+#[utoipa::path(
+	tag = "moderation",
+	params(AuditLogFilter),
+	responses(
+		(status = 200, description = "The audit log entries, newest first", body = [AuditLogEntry]),
+		(status = 400, description = "Invalid filter"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Guild does not exist"),
+	)
+)]
+#[get("/guilds/{guild_id}/audit-logs")]
+async fn get_audit_logs(
+	guild_id: web::Path<i64>, filter: web::Query<AuditLogFilter>,
+	fetcher: web::Data<RedisFetcher>, user: User,
+) -> HttpResponse {
+	if let Err(err) = filter.validate() {
+		return HttpResponse::BadRequest().json(err);
+	}
+
+	if let Err(res) = fetch_permitted_guild(
+		&fetcher,
+		*guild_id,
+		&user,
+		Permissions::VIEW_AUDIT_LOG,
+	)
+	.await
+	{
+		return res;
+	}
+
+	match fetcher.fetch_audit_logs(Some(*guild_id), &filter).await {
+		Ok(entries) => HttpResponse::Ok().json(entries),
+		Err(err) => {
+			log::error!("{:?}", err);
+			HttpResponse::InternalServerError().body("Something went wrong")
+		}
+	}
+}
+
+/// Fetches the audit log of the instance, newest first: the changes to roles
+/// and to channels outside of any guild. Only administrators can read it.
+#[utoipa::path(
+	tag = "moderation",
+	params(AuditLogFilter),
+	responses(
+		(status = 200, description = "The audit log entries, newest first", body = [AuditLogEntry]),
+		(status = 400, description = "Invalid filter"),
+		(status = 403, description = "Missing permissions"),
+	)
+)]
+#[get("/audit-logs")]
+async fn get_instance_audit_logs(
+	filter: web::Query<AuditLogFilter>, fetcher: web::Data<RedisFetcher>,
+	user: User,
+) -> HttpResponse {
+	if let Err(err) = filter.validate() {
+		return HttpResponse::BadRequest().json(err);
+	}
+
+	if let Err(res) =
+		check_permissions(&fetcher, &user, Permissions::ADMINISTRATOR).await
+	{
+		return res;
+	}
+
+	match fetcher.fetch_audit_logs(None, &filter).await {
+		Ok(entries) => HttpResponse::Ok().json(entries),
+		Err(err) => {
+			log::error!("{:?}", err);
+			HttpResponse::InternalServerError().body("Something went wrong")
+		}
+	}
+}
+
+/// Fetches the users banned from a guild.
+#[utoipa::path(
+	tag = "moderation",
+	responses(
+		(status = 200, description = "The bans", body = [Ban]),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Guild does not exist"),
+	)
+)]
+#[get("/guilds/{guild_id}/bans")]
+async fn get_bans(
+	guild_id: web::Path<i64>, fetcher: web::Data<RedisFetcher>, user: User,
+) -> HttpResponse {
+	if let Err(res) = fetch_permitted_guild(
+		&fetcher,
+		*guild_id,
+		&user,
+		Permissions::BAN_MEMBERS,
+	)
+	.await
+	{
+		return res;
+	}
+
+	match fetcher.fetch_bans(*guild_id).await {
+		Ok(bans) => HttpResponse::Ok().json(bans),
+		Err(err) => {
+			log::error!("{:?}", err);
+			HttpResponse::InternalServerError().body("Something went wrong")
+		}
+	}
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+struct CreateBan {
+	/// Why the user is banned
+	#[validate(length(max = 512))]
+	#[schema(max_length = 512)]
+	pub reason: Option<String>,
+	/// Delete the messages the user sent in the guild in this many past days
+	#[serde(default)]
+	#[validate(range(max = 7))]
+	#[schema(maximum = 7)]
+	pub delete_message_days: u64,
+}
+
+/// Bans a user from a guild, removing them from it if they are a member. The
+/// user can no longer join the guild through invites or see its channels.
+/// Banning a user who is already banned changes nothing.
+#[utoipa::path(
+	tag = "moderation",
+	responses(
+		(status = 204, description = "Banned the user, or they already were"),
+		(status = 400, description = "Invalid ban"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Guild or user does not exist"),
+	)
+)]
+#[put("/guilds/{guild_id}/bans/{user_id}")]
+async fn ban_member(
+	path: web::Path<(i64, i64)>, data: web::Json<CreateBan>,
+	fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>,
+	srv: web::Data<Addr<ShikiServer>>, user: User, reason: Reason,
+) -> HttpResponse {
+	if let Err(err) = data.validate() {
+		return HttpResponse::BadRequest().json(err);
+	}
+
+	let (guild_id, user_id) = path.into_inner();
+	let guild = match fetch_permitted_guild(
+		&fetcher,
+		guild_id,
+		&user,
+		Permissions::BAN_MEMBERS,
+	)
+	.await
+	{
+		Ok(guild) => guild,
+		Err(res) => return res,
+	};
+
+	if !can_moderate(&guild, &user, user_id) {
+		return HttpResponse::Forbidden().body("Cannot moderate this user");
+	}
+
+	match fetcher.fetch_user(FetchUserId::Id(user_id)).await {
+		Ok(Some(_)) => {}
+		Ok(None) => {
+			return HttpResponse::NotFound().body("User does not exist");
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	}
+
+	match fetcher.fetch_ban(guild_id, user_id).await {
+		Ok(None) => {}
+		Ok(Some(_)) => return HttpResponse::NoContent().finish(),
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	}
+
+	// A reason in the body takes precedence over the audit log header.
+	let mut data = data.into_inner();
+	let now = chrono::Utc::now().timestamp() as usize;
+
+	data.reason = data.reason.or(reason.0);
+
+	let ban = models::Ban {
+		guild_id,
+		user_id,
+		reason: data.reason.clone(),
+		moderator_id: user.id,
+		created_at: now,
+	};
+
+	if let Err(err) = fetcher.insert_ban(ban).await {
+		log::error!("{:?}", err);
+		return HttpResponse::InternalServerError()
+			.body("Something went wrong");
+	}
+
+	match fetcher.remove_member(guild_id, user_id).await {
+		Ok(true) => srv.do_send(RemoveMember { guild_id, user_id }),
+		Ok(false) => {}
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	}
+
+	if data.delete_message_days > 0 {
+		let fetcher = fetcher.clone().into_inner();
+		let since = now - data.delete_message_days as usize * 24 * 60 * 60;
+
+		actix_web::rt::spawn(async move {
+			let res = match fetcher.fetch_guild_channels(guild_id).await {
+				Ok(channels) => {
+					fetcher
+						.delete_user_messages(user_id, &channels, since)
+						.await
+				}
+				Err(err) => Err(err),
+			};
+
+			if let Err(err) = res {
+				log::error!(
+					"Failed to delete messages of banned user {user_id}: {:?}",
+					err
+				);
+			}
+		});
+	}
+
+	srv.do_send(BanMember { guild_id, user_id });
+	audit_log::record(
+		&fetcher,
+		&snowflake_gen,
+		AuditLogEntry {
+			target_id: Some(user_id),
+			reason: data.reason,
+			..AuditLogEntry::new(Some(guild_id), user.id, AuditLogAction::Ban)
+		},
+	)
+	.await;
+
+	HttpResponse::NoContent().finish()
+}
+
+/// Lifts a user's ban from a guild.
+#[utoipa::path(
+	tag = "moderation",
+	responses(
+		(status = 204, description = "Lifted the ban"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Guild or ban does not exist"),
+	)
+)]
+#[delete("/guilds/{guild_id}/bans/{user_id}")]
+async fn unban_member(
+	path: web::Path<(i64, i64)>, fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>,
+	srv: web::Data<Addr<ShikiServer>>, user: User, reason: Reason,
+) -> HttpResponse {
+	let (guild_id, user_id) = path.into_inner();
+
+	if let Err(res) = fetch_permitted_guild(
+		&fetcher,
+		guild_id,
+		&user,
+		Permissions::BAN_MEMBERS,
+	)
+	.await
+	{
+		return res;
+	}
+
+	match fetcher.delete_ban(guild_id, user_id).await {
+		Ok(true) => {}
+		Ok(false) => {
+			return HttpResponse::NotFound().body("Ban does not exist");
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	}
+
+	srv.do_send(UnbanMember { guild_id, user_id });
+	audit_log::record(
+		&fetcher,
+		&snowflake_gen,
+		AuditLogEntry {
+			target_id: Some(user_id),
+			reason: reason.0,
+			..AuditLogEntry::new(Some(guild_id), user.id, AuditLogAction::Unban)
+		},
+	)
+	.await;
+
+	HttpResponse::NoContent().finish()
+}
+
+/// The most messages which can be pinned in a single channel.
+const MAX_PINS: u64 = 50;
+
+/// Fetches the pinned messages of a channel, newest first.
+#[utoipa::path(
+	tag = "pins",
+	responses(
+		(status = 200, description = "The pinned messages, newest first", body = [GetMessage]),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Channel does not exist"),
+	)
+)]
+#[get("/channels/{channel_id}/pins")]
+async fn get_pins(
+	channel_id: web::Path<i64>, fetcher: web::Data<RedisFetcher>, user: User,
+) -> HttpResponse {
+	if let Err(res) =
+		fetch_permitted_channel(&fetcher, *channel_id, &user, Permissions::NONE)
+			.await
+	{
+		return res;
+	}
+
+	let messages = match fetcher.fetch_pins(*channel_id).await {
+		Ok(messages) => messages,
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	};
+
+	match resolve_authors(&fetcher, messages).await {
+		Ok(messages) => HttpResponse::Ok().json(messages),
+		Err(err) => {
+			log::error!("{:?}", err);
+			HttpResponse::InternalServerError().body("Something went wrong")
+		}
+	}
+}
+
+/// Pins a message in a channel.
+#[utoipa::path(
+	tag = "pins",
+	responses(
+		(status = 204, description = "Pinned the message"),
+		(status = 400, description = "Too many pinned messages"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Channel or message does not exist"),
+	)
+)]
+#[put("/channels/{channel_id}/pins/{message_id}")]
+async fn pin_message(
+	path: web::Path<(i64, i64)>, fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>,
+	srv: web::Data<Addr<ShikiServer>>, user: User, reason: Reason,
+) -> HttpResponse {
+	let ids = path.into_inner();
+
+	set_pinned(&fetcher, &snowflake_gen, &srv, &user, ids, true, reason).await
+}
+
+/// Unpins a message in a channel.
+#[utoipa::path(
+	tag = "pins",
+	responses(
+		(status = 204, description = "Unpinned the message"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Channel or message does not exist"),
+	)
+)]
+#[delete("/channels/{channel_id}/pins/{message_id}")]
+async fn unpin_message(
+	path: web::Path<(i64, i64)>, fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>,
+	srv: web::Data<Addr<ShikiServer>>, user: User, reason: Reason,
+) -> HttpResponse {
+	let ids = path.into_inner();
+
+	set_pinned(&fetcher, &snowflake_gen, &srv, &user, ids, false, reason).await
+}
+
+async fn set_pinned(
+	fetcher: &RedisFetcher, snowflake_gen: &Mutex<SnowflakeIdGenerator>,
+	srv: &Addr<ShikiServer>, user: &User, (channel_id, message_id): (i64, i64),
+	pinned: bool, reason: Reason,
+) -> HttpResponse {
+	let channel = match fetch_permitted_channel(
+		fetcher,
+		channel_id,
+		user,
+		Permissions::MANAGE_MESSAGES,
+	)
+	.await
+	{
+		Ok((channel, _)) => channel,
+		Err(res) => return res,
+	};
+
+	let message = match fetcher.fetch_message(message_id).await {
+		Ok(Some(message)) if message.channel_id == channel_id => message,
+		Ok(_) => {
+			return HttpResponse::NotFound().body("Message does not exist");
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	};
+
+	if message.pinned == pinned {
+		return HttpResponse::NoContent().finish();
+	}
+
+	if pinned {
+		match fetcher.count_pins(channel_id).await {
+			Ok(count) if count >= MAX_PINS => {
+				return HttpResponse::BadRequest().body(format!(
+					"Maximum number of pins reached ({MAX_PINS})"
+				));
+			}
+			Ok(_) => {}
+			Err(err) => {
+				log::error!("{:?}", err);
+				return HttpResponse::InternalServerError()
+					.body("Something went wrong");
+			}
+		}
+	}
+
+	if let Err(err) = fetcher.set_pinned(message_id, pinned).await {
+		log::error!("{:?}", err);
+		return HttpResponse::InternalServerError()
+			.body("Something went wrong");
+	}
+
+	srv.do_send(UpdatePins { channel_id, message_id, pinned });
+	audit_log::record(
+		fetcher,
+		snowflake_gen,
+		AuditLogEntry {
+			target_id: Some(message_id),
+			reason: reason.0,
+			..AuditLogEntry::new(
+				channel.guild_id,
+				user.id,
+				if pinned {
+					AuditLogAction::MessagePin
+				} else {
+					AuditLogAction::MessageUnpin
+				},
+			)
+		},
+	)
+	.await;
+
+	HttpResponse::NoContent().finish()
+}
+
+/// Marks every message in a channel up to the given one as read. The new read
+/// state is synced to the requester's other sessions.
+#[utoipa::path(
+	tag = "messages",
+	responses(
+		(status = 200, description = "The new read state", body = ReadState),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Channel or message does not exist"),
+	)
+)]
+#[post("/channels/{channel_id}/messages/{message_id}/ack")]
+async fn ack_message(
+	path: web::Path<(i64, i64)>, fetcher: web::Data<RedisFetcher>,
+	srv: web::Data<Addr<ShikiServer>>, user: User,
+) -> HttpResponse {
+	let (channel_id, message_id) = path.into_inner();
+
+	if let Err(res) =
+		fetch_permitted_channel(&fetcher, channel_id, &user, Permissions::NONE)
+			.await
+	{
+		return res;
+	}
+
+	match fetcher.fetch_message(message_id).await {
+		Ok(Some(message)) if message.channel_id == channel_id => {}
+		Ok(_) => {
+			return HttpResponse::NotFound().body("Message does not exist");
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	}
+
+	// Another session may have acknowledged a newer message already, so the
+	// counts are those of the stored read state.
+	let last_message_id =
+		match fetcher.ack_message(user.id, channel_id, message_id).await {
+			Ok(read_state) => read_state.last_message_id,
+			Err(err) => {
+				log::error!("{:?}", err);
+				return HttpResponse::InternalServerError()
+					.body("Something went wrong");
+			}
+		};
+
+	let (unread_count, mention_count) = match fetcher
+		.count_unread(&user, channel_id, Some(last_message_id))
+		.await
+	{
+		Ok(counts) => counts,
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	};
+
+	let read_state = ReadState {
+		channel_id,
+		last_message_id: Some(last_message_id),
+		unread_count,
+		mention_count,
+	};
+
+	srv.do_send(AckMessage {
+		user_id: user.id,
+		read_state: read_state.clone(),
+	});
+
+	HttpResponse::Ok().json(read_state)
+}
+
+/// Shows the requester as typing in a channel. The indicator expires after a
+/// few seconds unless it is triggered again.
+#[utoipa::path(
+	tag = "messages",
+	responses(
+		(status = 204, description = "Started typing"),
+		(status = 400, description = "Channel does not exist"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Channel does not exist"),
+		(status = 429, description = "Typing too often, retry after `retry_after` seconds"),
+	)
+)]
+#[post("/channels/{channel_id}/typing")]
+async fn trigger_typing(
+	channel_id: web::Path<i64>, fetcher: web::Data<RedisFetcher>,
+	srv: web::Data<Addr<ShikiServer>>, user: User,
+) -> HttpResponse {
+	// Typing is only shown where the user could send the message, which
+	// excludes members who are timed out.
+	if let Err(res) = fetch_permitted_channel(
+		&fetcher,
+		*channel_id,
+		&user,
+		Permissions::SEND_MESSAGES,
+	)
+	.await
+	{
+		return res;
+	}
+
+	match srv
+		.send(StartTyping { user_id: user.id, channel_id: *channel_id })
+		.await
+	{
+		Ok(TypingResult::Started) => HttpResponse::NoContent().finish(),
+		Ok(TypingResult::Throttled(retry_after)) => {
+			HttpResponse::TooManyRequests().json(serde_json::json!({
+				"retry_after": retry_after.as_secs_f64(),
+			}))
+		}
+		Ok(TypingResult::UnknownChannel) => {
+			HttpResponse::BadRequest().body("Channel does not exist!")
+		}
+		Ok(TypingResult::Forbidden) => {
+			HttpResponse::Forbidden().body("Missing permissions")
+		}
+		Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+	}
+}
+
+/// Sets the status of the requester, which is shared by all of their gateway
+/// sessions. Returns the new presence on success.
+#[utoipa::path(
+	tag = "users",
+	responses(
+		(status = 200, description = "The new presence", body = Presence),
+		(status = 400, description = "Invalid status or not connected to the gateway"),
+	)
+)]
+#[patch("/users/@me/presence")]
+async fn modify_presence(
+	data: web::Json<ModifyPresence>, srv: web::Data<Addr<ShikiServer>>,
+	user: User,
+) -> HttpResponse {
+	if let Err(err) = data.validate() {
+		return HttpResponse::BadRequest().json(err);
+	}
+
+	let data = data.into_inner();
+
+	if data.status == Status::Offline {
+		return HttpResponse::BadRequest()
+			.body("Use the invisible status to appear offline");
+	}
+
+	match srv
+		.send(UpdatePresence {
+			user_id: user.id,
+			status: data.status,
+			custom_status: data.custom_status,
+		})
+		.await
+	{
+		Ok(Some(presence)) => HttpResponse::Ok().json(presence),
+		Ok(None) => HttpResponse::BadRequest()
+			.body("User is not connected to the gateway"),
+		Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+	}
+}
+
+/// Fetches the presence of a user. Invisible users are shown as offline.
+#[utoipa::path(
+	tag = "users",
+	responses(
+		(status = 200, description = "The presence of the user", body = Presence),
+	)
+)]
+#[get("/users/{user_id}/presence")]
+async fn get_presence(
+	user_id: web::Path<i64>, fetcher: web::Data<RedisFetcher>,
+) -> HttpResponse {
+	match fetcher.fetch_presence(*user_id).await {
+		Ok(presence) => HttpResponse::Ok().json(presence.public()),
+		Err(err) => {
+			log::error!("{:?}", err);
+			HttpResponse::InternalServerError().body("Something went wrong")
+		}
+	}
+}
+
+#[derive(Deserialize, IntoParams)]
+struct GetMentions {
+	/// Get mentions before this message ID
+	before: Option<i64>,
+	/// Max number of messages to return (1-100)
+	#[serde(default = "default_mentions_limit")]
+	#[param(minimum = 1, maximum = 100)]
+	limit: i64,
+}
+
+fn default_mentions_limit() -> i64 {
+	25
+}
+
+/// Fetches the recent messages which mention the requester, directly, through
+/// one of their roles or through `@everyone`, in the channels they can see.
+/// Newest messages come first.
+#[utoipa::path(
+	tag = "users",
+	params(GetMentions),
+	responses(
+		(status = 200, description = "The mentions, newest first", body = [GetMessage]),
+		(status = 400, description = "Invalid limit"),
+	)
+)]
+#[get("/users/@me/mentions")]
+async fn get_mentions(
+	data: web::Query<GetMentions>, fetcher: web::Data<RedisFetcher>, user: User,
+) -> HttpResponse {
+	if data.limit < 1 || data.limit > 100 {
+		return HttpResponse::BadRequest()
+			.body("Limit must be between 1 and 100");
+	}
+
+	let channel_ids = match fetcher.fetch_visible_channels(user.id).await {
+		Ok(channel_ids) => channel_ids,
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	};
+
+	let messages = match fetcher
+		.fetch_mentions(&user, &channel_ids, data.before, data.limit)
+		.await
+	{
+		Ok(messages) => messages,
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	};
+
+	match resolve_authors(&fetcher, messages).await {
+		Ok(messages) => HttpResponse::Ok().json(messages),
+		Err(err) => {
+			log::error!("{:?}", err);
+			HttpResponse::InternalServerError().body("Something went wrong")
+		}
+	}
+}
+
+/// Shows all the roles
+#[utoipa::path(
+	tag = "roles",
+	responses(
+		(status = 200, description = "The roles", body = [Role]),
+	)
+)]
+#[get("/roles")]
+async fn get_roles(fetcher: web::Data<RedisFetcher>) -> HttpResponse {
+	match fetcher.fetch_roles(None).await {
+		Ok(roles) => HttpResponse::Ok().json(roles),
+		Err(err) => {
+			log::error!("{:?}", err);
+			HttpResponse::InternalServerError().body("Something went wrong")
+		}
+	}
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+struct CreateRole {
+	#[validate(length(min = 1, max = 100), non_control_character)]
+	#[schema(min_length = 1, max_length = 100, pattern = r"^\P{Cc}*$")]
+	pub name: String,
+	/// Permissions granted by the role
+	#[serde(default)]
+	pub permissions: Permissions,
+}
+
+/// Checks that `user` has `required` outside of any channel.
+async fn check_permissions(
+	fetcher: &RedisFetcher, user: &User, required: Permissions,
+) -> Result<(), HttpResponse> {
+	match permissions::base(fetcher, user).await {
+		Ok(permissions) if permissions.contains(required) => Ok(()),
+		Ok(_) => Err(HttpResponse::Forbidden().body("Missing permissions")),
+		Err(err) => {
+			log::error!("{:?}", err);
+			Err(HttpResponse::InternalServerError()
+				.body("Something went wrong"))
+		}
+	}
+}
+
+/// Creates a new role. Users can only grant permissions they have themselves.
+#[utoipa::path(
+	tag = "roles",
+	responses(
+		(status = 200, description = "The created role", body = Role),
+		(status = 400, description = "Invalid role"),
+		(status = 403, description = "Missing permissions"),
+	)
+)]
+#[post("/roles")]
+async fn create_role(
+	data: web::Json<CreateRole>, fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>, user: User,
+	reason: Reason,
+) -> HttpResponse {
+	if let Err(err) = data.validate() {
+		return HttpResponse::BadRequest().json(err);
+	}
+
+	let required = Permissions::MANAGE_ROLES | data.permissions;
+
+	if let Err(res) = check_permissions(&fetcher, &user, required).await {
+		return res;
+	}
+
+	let id = snowflake_gen.lock().await.real_time_generate();
+	let role = models::Role::new(id, &data.name, data.permissions);
+
+	if let Err(err) = fetcher.insert_role(role.clone()).await {
+		log::error!("{:?}", err);
+		return HttpResponse::InternalServerError()
+			.body("Something went wrong");
+	}
+
+	audit_log::record(
+		&fetcher,
+		&snowflake_gen,
+		AuditLogEntry {
+			target_id: Some(role.id),
+			changes: audit_log::diff(None, Some(&role)),
+			reason: reason.0,
+			..AuditLogEntry::new(None, user.id, AuditLogAction::RoleCreate)
+		},
+	)
+	.await;
+
+	HttpResponse::Ok().json(role)
+}
+
+/// Gives a role to a user.
+#[utoipa::path(
+	tag = "roles",
+	responses(
+		(status = 204, description = "Gave the role"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Role or user does not exist"),
+	)
+)]
+#[put("/roles/{role_id}/members/{user_id}")]
+async fn add_role_member(
+	path: web::Path<(i64, i64)>, fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>, user: User,
+	reason: Reason,
+) -> HttpResponse {
+	let ids = path.into_inner();
+
+	modify_role_member(&fetcher, &snowflake_gen, &user, ids, true, reason).await
+}
+
+/// Takes a role away from a user.
+#[utoipa::path(
+	tag = "roles",
+	responses(
+		(status = 204, description = "Took the role away"),
+		(status = 403, description = "Missing permissions"),
+		(status = 404, description = "Role or user does not exist"),
+	)
+)]
+#[delete("/roles/{role_id}/members/{user_id}")]
+async fn remove_role_member(
+	path: web::Path<(i64, i64)>, fetcher: web::Data<RedisFetcher>,
+	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>, user: User,
+	reason: Reason,
+) -> HttpResponse {
+	let ids = path.into_inner();
+
+	modify_role_member(&fetcher, &snowflake_gen, &user, ids, false, reason)
+		.await
+}
+
+async fn modify_role_member(
+	fetcher: &RedisFetcher, snowflake_gen: &Mutex<SnowflakeIdGenerator>,
+	user: &User, (role_id, user_id): (i64, i64), add: bool, reason: Reason,
+) -> HttpResponse {
+	let role = match fetcher.fetch_roles(Some(&[role_id])).await {
+		Ok(mut roles) => match roles.pop() {
+			Some(role) => role,
+			None => {
+				return HttpResponse::NotFound().body("Role does not exist");
+			}
+		},
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	};
+
+	let required = Permissions::MANAGE_ROLES | role.permissions;
+
+	if let Err(res) = check_permissions(fetcher, user, required).await {
+		return res;
+	}
+
+	match fetcher.fetch_user(FetchUserId::Id(user_id)).await {
+		Ok(Some(_)) => {}
+		Ok(None) => {
+			return HttpResponse::NotFound().body("User does not exist")
+		}
+		Err(err) => {
+			log::error!("{:?}", err);
+			return HttpResponse::InternalServerError()
+				.body("Something went wrong");
+		}
+	}
+
+	if let Err(err) = fetcher.modify_user_roles(user_id, role_id, add).await {
+		log::error!("{:?}", err);
+		return HttpResponse::InternalServerError()
+			.body("Something went wrong");
+	}
+
+	// Like Discord, the role is recorded under `$add` or `$remove`.
+	let change = AuditLogChange {
+		key: if add { "$add" } else { "$remove" }.to_string(),
+		old_value: None,
+		new_value: Some(
+			serde_json::json!([{"id": role.id, "name": role.name}]),
+		),
+	};
+
+	audit_log::record(
+		fetcher,
+		snowflake_gen,
+		AuditLogEntry {
+			target_id: Some(user_id),
+			changes: vec![change],
+			reason: reason.0,
+			..AuditLogEntry::new(
+				None,
+				user.id,
+				AuditLogAction::MemberRoleUpdate,
+			)
+		},
+	)
+	.await;
+
+	HttpResponse::NoContent().finish()
+}
+
+/// Modify the requester's user account settings. Returns a user object on success.
+// TODO: Fire a User Update Gateway event.
+#[utoipa::path(
+	tag = "users",
+	responses(
+		(status = 200, description = "The updated user", body = User),
+		(status = 400, description = "Invalid changes"),
+	)
+)]
+#[patch("/users/@me")]
+async fn modify_user(
+	data: web::Json<ModifyUser>, fetcher: web::Data<RedisFetcher>,
+	mut user: User,
+) -> HttpResponse {
+	if let Err(err) = data.validate() {
+		return HttpResponse::BadRequest().json(err);
+	}
+
+	match fetcher.modify_user(&mut user, data.into_inner()).await {
+		Ok(_) => HttpResponse::Ok().json(server::User {
+			id: user.id,
+			username: user.username,
+			joined: user.created_at,
+			avatar: user.avatar,
+		}),
+		Err(err) => {
+			log::error!("{:?}", err);
+			HttpResponse::InternalServerError().body("Something went wrong")
+		}
+	}
+}
+
+pub async fn setup_indexes(client: &Client) -> anyhow::Result<()> {
+	let db = client.database(DB_NAME);
+
+	db.collection::<Message>(MESSAGE_COLL_NAME)
+		.create_index(
+			IndexModel::builder().keys(doc! {"channel_id": 1, "id": 1}).build(),
+			None,
+		)
+		.await?;
+
+	db.collection::<Message>(MESSAGE_COLL_NAME)
+		.create_index(
+			IndexModel::builder().keys(doc! {"mentions": 1}).build(),
+			None,
+		)
+		.await?;
+
+	db.collection::<models::Member>(MEMBER_COLL_NAME)
+		.create_index(
+			IndexModel::builder()
+				.keys(doc! {"guild_id": 1, "user_id": 1})
+				.options(IndexOptions::builder().unique(true).build())
+				.build(),
+			None,
+		)
+		.await?;
+
+	db.collection::<AuditLogEntry>(AUDIT_LOG_COLL_NAME)
+		.create_index(
+			IndexModel::builder().keys(doc! {"guild_id": 1, "id": -1}).build(),
+			None,
+		)
+		.await?;
+
+	db.collection::<models::Ban>(BAN_COLL_NAME)
+		.create_index(
+			IndexModel::builder()
+				.keys(doc! {"guild_id": 1, "user_id": 1})
+				.options(IndexOptions::builder().unique(true).build())
+				.build(),
+			None,
+		)
+		.await?;
+
+	db.collection::<models::Invite>(INVITE_COLL_NAME)
+		.create_index(
+			IndexModel::builder()
+				.keys(doc! {"code": 1})
+				.options(IndexOptions::builder().unique(true).build())
+				.build(),
+			None,
+		)
+		.await?;
+
+	db.collection::<models::ReadState>(READ_STATE_COLL_NAME)
+		.create_index(
+			IndexModel::builder()
+				.keys(doc! {"user_id": 1, "channel_id": 1})
+				.options(IndexOptions::builder().unique(true).build())
+				.build(),
+			None,
+		)
+		.await?;
+
+	Ok(())
+}
+
+/// Documents the routes of the API. Response bodies refer to the schema of
+/// what they are serialized as, such as `Channel` for a [`server::Channel`],
+/// which is registered here along with the request bodies.
+#[derive(OpenApi)]
+#[openapi(
+	paths(
+		get_count,
+		get_channels_list,
+		create_channel,
+		modify_channel,
+		delete_channel,
+		join_channel,
+		get_messages,
+		create_message,
+		create_thread,
+		get_thread_members,
+		join_thread,
+		leave_thread,
+		create_guild,
+		get_guild,
+		create_invite,
+		get_invite,
+		accept_invite,
+		delete_invite,
+		kick_member,
+		timeout_member,
+		get_audit_logs,
+		get_instance_audit_logs,
+		get_bans,
+		ban_member,
+		unban_member,
+		get_pins,
+		pin_message,
+		unpin_message,
+		ack_message,
+		trigger_typing,
+		modify_presence,
+		get_presence,
+		get_mentions,
+		get_roles,
+		create_role,
+		add_role_member,
+		remove_role_member,
+		modify_user,
+	),
+	components(schemas(
+		CreateChannel,
+		ModifyChannel,
+		CreateMessage,
+		CreateThread,
+		CreateGuild,
+		CreateInvite,
+		TimeoutMember,
+		CreateBan,
+		ModifyPresence,
+		CreateRole,
+		ModifyUser,
+		shiki_client::model::Channel,
+		ChannelType,
+		Snowflakes,
+		GetMessage,
+		server::User,
+		server::Guild,
+		models::Invite,
+		InvitePreview,
+		AuditLogEntry,
+		AuditLogAction,
+		AuditLogChange,
+		models::Ban,
+		ReadState,
+		models::Presence,
+		Status,
+		models::Role,
+		Permissions,
+	))
+)]
+pub struct ApiDoc;
+
+pub fn routes(
+	client: &RedisFetcher, version: ApiVersion, cfg: &mut web::ServiceConfig,
+) {
 	cfg.service(
-		web::scope("/api")
+		web::scope(&version.scope("/api"))
 			.service(get_count)
 			.service(get_channels_list)
 			.service(create_channel)
+			.service(modify_channel)
+			.service(delete_channel)
 			.service(join_channel)
+			.service(create_invite)
+			.service(get_invite)
+			.service(accept_invite)
+			.service(delete_invite)
+			.service(create_guild)
+			.service(get_guild)
+			.service(kick_member)
+			.service(timeout_member)
+			.service(get_audit_logs)
+			.service(get_instance_audit_logs)
+			.service(get_bans)
+			.service(ban_member)
+			.service(unban_member)
 			.service(create_message)
 			.service(get_messages)
+			.service(ack_message)
+			.service(get_pins)
+			.service(pin_message)
+			.service(unpin_message)
+			.service(create_thread)
+			.service(get_thread_members)
+			.service(join_thread)
+			.service(leave_thread)
+			.service(trigger_typing)
 			.service(modify_user)
-			.wrap(Auth::new(client.clone())),
+			.service(modify_presence)
+			.service(get_presence)
+			.service(get_mentions)
+			.service(get_roles)
+			.service(create_role)
+			.service(add_role_member)
+			.service(remove_role_member)
+			.wrap(RateLimit::new(client.clone()))
+			.wrap(Auth::new(client.clone()))
+			.wrap(version.default_headers()),
 	);
 }
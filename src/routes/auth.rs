@@ -1,7 +1,10 @@
 use crate::{
-	models::User,
-	routes::{DB_NAME, USER_COLL_NAME},
+	models::{Role, User},
+	permissions::Permissions,
+	redis::RedisFetcher,
+	routes::{middleware::RateLimit, DB_NAME, USER_COLL_NAME},
 	utils,
+	version::ApiVersion,
 };
 use actix_session::Session;
 use actix_web::{get, post, web, HttpResponse};
@@ -12,35 +15,75 @@ use mongodb::{
 	options::IndexOptions,
 	Client, IndexModel,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
+use shiki_client::model::UserResponse;
 use snowflake::SnowflakeIdGenerator;
+use utoipa::{OpenApi, ToSchema};
 use validator::Validate;
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct UserInsert {
 	#[validate(email)]
+	#[schema(format = "email")]
 	pub email: String,
 	#[validate(length(min = 2, max = 32), non_control_character)]
+	#[schema(min_length = 2, max_length = 32, pattern = r"^\P{Cc}*$")]
 	pub username: String,
 	#[validate(length(min = 8))]
+	#[schema(min_length = 8, format = Password)]
 	pub password: String,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
-pub struct UserResponse {
-	pub user_id: i64,
-	pub token: String,
-}
-
 impl From<User> for UserResponse {
 	fn from(user: User) -> Self {
 		UserResponse { user_id: user.id, token: user.token }
 	}
 }
 
+/// Gives the first user of an instance a role with every permission. Roles
+/// can only be managed by users who have a role allowing it, so nobody could
+/// grant any permission otherwise. Returns whether the user was given the
+/// role.
+async fn grant_first_admin(
+	client: &Client, fetcher: &RedisFetcher,
+	snowflake_gen: &Mutex<SnowflakeIdGenerator>, user_id: i64,
+) -> anyhow::Result<bool> {
+	// Snowflakes are ordered by creation time, so only the first user has no
+	// user before them. Users registering at the same time may both become
+	// administrators, but never neither of them.
+	let earlier = client
+		.database(DB_NAME)
+		.collection::<User>(USER_COLL_NAME)
+		.count_documents(doc! {"id": {"$lt": user_id}}, None)
+		.await?;
+
+	if earlier > 0 {
+		return Ok(false);
+	}
+
+	let id = snowflake_gen.lock().await.real_time_generate();
+
+	fetcher
+		.insert_role(Role::new(id, "Administrator", Permissions::ADMINISTRATOR))
+		.await?;
+	fetcher.modify_user_roles(user_id, id, true).await?;
+
+	Ok(true)
+}
+
+/// Creates an account. The first account of an instance is given the
+/// Administrator role, which grants every permission.
+#[utoipa::path(
+	tag = "auth",
+	responses(
+		(status = 200, description = "The created user and its token", body = UserResponse),
+		(status = 400, description = "Invalid account or user already exists"),
+	)
+)]
 #[post("/register")]
 async fn register(
 	client: web::Data<Client>, data: web::Json<UserInsert>,
+	fetcher: web::Data<RedisFetcher>,
 	snowflake_gen: web::Data<Mutex<SnowflakeIdGenerator>>,
 ) -> HttpResponse {
 	if let Err(err) = data.validate() {
@@ -63,7 +106,16 @@ async fn register(
 		.await;
 
 	match res {
-		Ok(_) => HttpResponse::Ok().json(UserResponse::from(user)),
+		Ok(_) => {
+			match grant_first_admin(&client, &fetcher, &snowflake_gen, id).await
+			{
+				Ok(true) => log::info!("made user {id} an administrator"),
+				Ok(false) => {}
+				Err(err) => log::error!("grant_first_admin: {:?}", err),
+			}
+
+			HttpResponse::Ok().json(UserResponse::from(user))
+		}
 		Err(err) => {
 			log::error!("add_user: {}", err);
 
@@ -81,14 +133,24 @@ async fn register(
 	}
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 struct UserLogin {
 	#[validate(email)]
+	#[schema(format = "email")]
 	pub email: String,
 	#[validate(length(min = 8))]
+	#[schema(min_length = 8, format = Password)]
 	pub password: String,
 }
 
+/// Logs into an account, returning the token to authenticate with.
+#[utoipa::path(
+	tag = "auth",
+	responses(
+		(status = 200, description = "The user and its token", body = UserResponse),
+		(status = 400, description = "Invalid credentials or user not found"),
+	)
+)]
 #[post("/login")]
 async fn login(
 	client: web::Data<Client>, data: web::Json<UserLogin>, session: Session,
@@ -162,6 +224,22 @@ pub async fn setup_indexes(client: &Client) -> anyhow::Result<()> {
 	Ok(())
 }
 
-pub fn routes(cfg: &mut web::ServiceConfig) {
-	cfg.service(web::scope("/auth").service(register).service(login));
+/// Documents the authentication routes.
+#[derive(OpenApi)]
+#[openapi(
+	paths(register, login),
+	components(schemas(UserInsert, UserLogin, UserResponse))
+)]
+pub struct ApiDoc;
+
+pub fn routes(
+	client: &RedisFetcher, version: ApiVersion, cfg: &mut web::ServiceConfig,
+) {
+	cfg.service(
+		web::scope(&version.scope("/auth"))
+			.service(register)
+			.service(login)
+			.wrap(RateLimit::new(client.clone()))
+			.wrap(version.default_headers()),
+	);
 }
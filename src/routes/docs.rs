@@ -0,0 +1,206 @@
+use super::{api, auth, rtc};
+use crate::version::ApiVersion;
+use actix_web::{get, web, HttpResponse};
+use utoipa::{
+	openapi::{
+		security::{Http, HttpAuthScheme, SecurityRequirement, SecurityScheme},
+		RefOr, Response,
+	},
+	OpenApi,
+};
+
+/// Name of the security scheme of routes which need a token.
+const SECURITY_SCHEME: &str = "token";
+
+#[derive(OpenApi)]
+#[openapi(info(
+	title = "shiki",
+	description = "HTTP API of the shiki chat server"
+))]
+struct ShikiDoc;
+
+/// Prefixes the paths of `doc` with the scope its routes are mounted under.
+fn nest(
+	prefix: &str, mut doc: utoipa::openapi::OpenApi,
+) -> utoipa::openapi::OpenApi {
+	doc.paths.paths = std::mem::take(&mut doc.paths.paths)
+		.into_iter()
+		.map(|(path, item)| (format!("{prefix}{path}"), item))
+		.collect();
+	doc
+}
+
+/// Marks every operation under `prefix` as requiring a token, which the
+/// [`Auth`](super::middleware::Auth) middleware of its scope checks.
+fn require_token(doc: &mut utoipa::openapi::OpenApi, prefix: &str) {
+	doc.components.get_or_insert_with(Default::default).add_security_scheme(
+		SECURITY_SCHEME,
+		SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+	);
+
+	let operations = doc
+		.paths
+		.paths
+		.iter_mut()
+		.filter(|(path, _)| path.starts_with(prefix))
+		.flat_map(|(_, item)| item.operations.values_mut());
+
+	for operation in operations {
+		operation.security = Some(vec![SecurityRequirement::new(
+			SECURITY_SCHEME,
+			Vec::<String>::new(),
+		)]);
+		operation.responses.responses.insert(
+			"401".to_string(),
+			RefOr::T(Response::new("Missing or invalid token")),
+		);
+	}
+}
+
+/// Builds the OpenAPI specification of the API, authentication and WebRTC
+/// routes, in their latest version.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+	let mut doc = ShikiDoc::openapi();
+
+	doc.merge(nest(&ApiVersion::LATEST.scope("/api"), api::ApiDoc::openapi()));
+	doc.merge(nest(
+		&ApiVersion::LATEST.scope("/auth"),
+		auth::ApiDoc::openapi(),
+	));
+	doc.merge(rtc::ApiDoc::openapi());
+	require_token(&mut doc, "/api/");
+
+	doc
+}
+
+/// Serves the OpenAPI specification
+#[get("/openapi.json")]
+async fn get_openapi(doc: web::Data<utoipa::openapi::OpenApi>) -> HttpResponse {
+	HttpResponse::Ok().json(doc.get_ref())
+}
+
+/// Serves a page to browse the OpenAPI specification with
+#[cfg(feature = "docs-ui")]
+#[get("/docs")]
+async fn get_docs() -> HttpResponse {
+	HttpResponse::Ok()
+		.content_type("text/html; charset=utf-8")
+		.body(include_str!("docs.html"))
+}
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+	cfg.app_data(web::Data::new(openapi())).service(get_openapi);
+
+	#[cfg(feature = "docs-ui")]
+	cfg.service(get_docs);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::Value;
+	use std::collections::BTreeSet;
+
+	/// Lists the routes mounted by the `routes` function of a module, as
+	/// `METHOD /path` under `prefix`.
+	fn mounted_routes(prefix: &str, source: &str) -> BTreeSet<String> {
+		let methods = ["get", "post", "put", "patch", "delete"];
+		let mut lines = source.lines().map(str::trim).peekable();
+		let mut handlers = Vec::new();
+
+		while let Some(line) = lines.next() {
+			let route = line
+				.strip_prefix("#[")
+				.and_then(|attr| attr.strip_suffix("\")]"))
+				.and_then(|attr| attr.split_once("(\""))
+				.filter(|(method, _)| methods.contains(method));
+			let name = lines
+				.peek()
+				.and_then(|line| line.strip_prefix("async fn "))
+				.and_then(|line| line.split_once('('));
+
+			if let (Some((method, path)), Some((name, _))) = (route, name) {
+				handlers.push((name, method.to_uppercase(), path));
+			}
+		}
+
+		handlers
+			.into_iter()
+			.filter(|(name, ..)| source.contains(&format!(".service({name})")))
+			.map(|(_, method, path)| format!("{method} {prefix}{path}"))
+			.collect()
+	}
+
+	#[test]
+	fn spec_matches_routes() {
+		let mounted = [
+			mounted_routes(
+				&ApiVersion::LATEST.scope("/api"),
+				include_str!("api.rs"),
+			),
+			mounted_routes(
+				&ApiVersion::LATEST.scope("/auth"),
+				include_str!("auth.rs"),
+			),
+			mounted_routes("", include_str!("rtc.rs")),
+		]
+		.into_iter()
+		.flatten()
+		.collect::<BTreeSet<_>>();
+
+		let spec = serde_json::to_value(openapi()).unwrap();
+		let documented = spec["paths"]
+			.as_object()
+			.unwrap()
+			.iter()
+			.flat_map(|(path, item)| {
+				item.as_object().unwrap().keys().map(move |method| {
+					format!("{} {path}", method.to_uppercase())
+				})
+			})
+			.collect::<BTreeSet<_>>();
+
+		assert_eq!(
+			mounted, documented,
+			"the OpenAPI specification is out of date, add the missing \
+			 routes to the `ApiDoc` of their module"
+		);
+	}
+
+	/// Collects the schemas referenced anywhere in `value`.
+	fn references<'a>(value: &'a Value, refs: &mut BTreeSet<&'a str>) {
+		match value {
+			Value::Object(object) => {
+				for (key, value) in object {
+					match (key.as_str(), value) {
+						("$ref", Value::String(reference)) => {
+							refs.insert(reference);
+						}
+						_ => references(value, refs),
+					}
+				}
+			}
+			Value::Array(array) => {
+				array.iter().for_each(|value| references(value, refs))
+			}
+			_ => {}
+		}
+	}
+
+	#[test]
+	fn spec_references_registered_schemas() {
+		let spec = serde_json::to_value(openapi()).unwrap();
+		let mut refs = BTreeSet::new();
+
+		references(&spec, &mut refs);
+
+		for reference in refs {
+			let name = reference.trim_start_matches("#/components/schemas/");
+
+			assert!(
+				spec["components"]["schemas"].get(name).is_some(),
+				"{name} is referenced but not registered in an `ApiDoc`"
+			);
+		}
+	}
+}
@@ -0,0 +1,261 @@
+use super::middleware::{Auth, RateLimit};
+use crate::{
+	models::{Status, User},
+	redis::RedisFetcher,
+	version::ApiVersion,
+	ws::{
+		commands::GatewayCommand,
+		events::{Event, InvalidSession},
+		intents::Intents,
+		server::ShikiServer,
+		stream::{
+			sse_event, Command, EventStream, Poll, Polled, StreamHandle,
+			Streams, Subscribe,
+		},
+	},
+};
+use actix::{Actor, Addr};
+use actix_web::{
+	error::ErrorInternalServerError, get, http::header, post, web, Error,
+	HttpRequest, HttpResponse,
+};
+use futures::{stream, StreamExt};
+use serde::Deserialize;
+use std::sync::Arc;
+use validator::Validate;
+
+#[derive(Deserialize)]
+struct StreamQuery {
+	/// Events to subscribe to when a new stream is opened, every event unless
+	/// specified
+	intents: Option<Intents>,
+	/// Version of the gateway a new stream is opened with
+	#[serde(default)]
+	v: ApiVersion,
+}
+
+#[derive(Deserialize)]
+struct PollQuery {
+	/// The stream to poll, a new one is opened unless specified
+	session_id: Option<usize>,
+	/// The last sequence the client received
+	#[serde(default)]
+	seq: u64,
+	/// Events to subscribe to when a new stream is opened, every event unless
+	/// specified
+	intents: Option<Intents>,
+	/// Version of the gateway a new stream is opened with
+	#[serde(default)]
+	v: ApiVersion,
+}
+
+/// Finds an open stream of the user.
+fn find_stream(
+	streams: &Streams, session_id: usize, user: &User,
+) -> Option<StreamHandle> {
+	streams
+		.lock()
+		.unwrap()
+		.get(&session_id)
+		.filter(|handle| handle.user_id == user.id)
+		.cloned()
+}
+
+/// Opens a stream which identifies as the user.
+fn open_stream(
+	srv: &web::Data<Addr<ShikiServer>>, streams: &web::Data<Streams>,
+	user: &User, intents: Option<Intents>, version: ApiVersion,
+) -> Addr<EventStream> {
+	EventStream::new(
+		srv.get_ref().clone(),
+		Arc::clone(streams),
+		user.id,
+		user.token.clone(),
+		intents.unwrap_or_default(),
+		version,
+	)
+	.start()
+}
+
+fn invalid_session(version: ApiVersion) -> serde_json::Value {
+	let event = Event::InvalidSession(InvalidSession { resumable: false });
+
+	// Events always serialize to JSON
+	serde_json::to_value(event.payload(None, version)).unwrap()
+}
+
+/// Streams gateway events as Server-Sent Events, for clients that cannot use
+/// WebSockets. The ID of every event can be sent back as `Last-Event-ID` to
+/// resume the stream after a disconnect. If it cannot be resumed, a new
+/// stream is opened and starts with an InvalidSession event.
+#[get("")]
+async fn stream_events(
+	req: HttpRequest, query: web::Query<StreamQuery>,
+	srv: web::Data<Addr<ShikiServer>>, streams: web::Data<Streams>, user: User,
+) -> Result<HttpResponse, Error> {
+	if query.intents.is_some_and(|intents| !intents.is_valid()) {
+		return Ok(HttpResponse::BadRequest().body("Invalid intents"));
+	}
+
+	// Last-Event-ID is `<session_id>:<seq>`
+	let last_event_id = req
+		.headers()
+		.get("Last-Event-ID")
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.split_once(':'))
+		.and_then(|(session_id, seq)| {
+			Some((session_id.parse::<usize>().ok()?, seq.parse::<u64>().ok()?))
+		});
+
+	let mut prefix = Vec::new();
+
+	if let Some((session_id, seq)) = last_event_id {
+		if let Some(handle) = find_stream(&streams, session_id, &user) {
+			if let Some(rx) = handle
+				.addr
+				.send(Subscribe { after: seq })
+				.await
+				.map_err(ErrorInternalServerError)?
+			{
+				return Ok(sse_response(prefix, rx));
+			}
+		}
+
+		prefix.push(sse_event(session_id, None, &invalid_session(query.v)));
+	}
+
+	let addr = open_stream(&srv, &streams, &user, query.intents, query.v);
+
+	match addr
+		.send(Subscribe { after: 0 })
+		.await
+		.map_err(ErrorInternalServerError)?
+	{
+		Some(rx) => Ok(sse_response(prefix, rx)),
+		None => {
+			Ok(HttpResponse::InternalServerError().body("Something went wrong"))
+		}
+	}
+}
+
+fn sse_response(
+	prefix: Vec<web::Bytes>,
+	rx: futures::channel::mpsc::UnboundedReceiver<web::Bytes>,
+) -> HttpResponse {
+	HttpResponse::Ok()
+		.content_type(mime::TEXT_EVENT_STREAM)
+		.insert_header(header::CacheControl(vec![
+			header::CacheDirective::NoCache,
+		]))
+		// Stop proxies such as nginx from buffering the stream
+		.insert_header(("X-Accel-Buffering", "no"))
+		.streaming(stream::iter(prefix).chain(rx).map(Ok::<_, Error>))
+}
+
+/// Long-polls gateway events. Without a session ID a new stream is opened.
+/// Clients poll again with the returned session ID and the sequence of the
+/// last event they received. If the stream cannot be resumed, a new one is
+/// opened and its events start with an InvalidSession event.
+#[get("/poll")]
+async fn poll_events(
+	query: web::Query<PollQuery>, srv: web::Data<Addr<ShikiServer>>,
+	streams: web::Data<Streams>, user: User,
+) -> Result<HttpResponse, Error> {
+	if query.intents.is_some_and(|intents| !intents.is_valid()) {
+		return Ok(HttpResponse::BadRequest().body("Invalid intents"));
+	}
+
+	let mut prefix = Vec::new();
+
+	if let Some(session_id) = query.session_id {
+		if let Some(handle) = find_stream(&streams, session_id, &user) {
+			if let Some(polled) = handle
+				.addr
+				.send(Poll { after: query.seq })
+				.await
+				.map_err(ErrorInternalServerError)?
+			{
+				return Ok(HttpResponse::Ok().json(polled));
+			}
+		}
+
+		prefix.push(invalid_session(query.v));
+	}
+
+	let addr = open_stream(&srv, &streams, &user, query.intents, query.v);
+
+	match addr
+		.send(Poll { after: 0 })
+		.await
+		.map_err(ErrorInternalServerError)?
+	{
+		Some(Polled { session_id, events }) => {
+			prefix.extend(events);
+			Ok(HttpResponse::Ok().json(Polled { session_id, events: prefix }))
+		}
+		None => {
+			Ok(HttpResponse::InternalServerError().body("Something went wrong"))
+		}
+	}
+}
+
+/// Sends a gateway command for a stream. The body is a gateway payload, such
+/// as `{"op": 21, "d": {...}}`. Identify, Heartbeat and Resume are not
+/// needed, streams are authenticated with the request that opened them.
+#[post("/{session_id}/commands")]
+async fn send_command(
+	session_id: web::Path<usize>, data: web::Json<serde_json::Value>,
+	streams: web::Data<Streams>, user: User,
+) -> Result<HttpResponse, Error> {
+	let command =
+		match serde_json::from_value::<GatewayCommand>(data.into_inner()) {
+			Ok(command) => command,
+			Err(err) => {
+				return Ok(HttpResponse::BadRequest().body(err.to_string()))
+			}
+		};
+
+	if let Err(err) = command.validate() {
+		return Ok(HttpResponse::BadRequest().json(err));
+	}
+
+	match &command {
+		GatewayCommand::Identify(_)
+		| GatewayCommand::Heartbeat(_)
+		| GatewayCommand::Resume(_) => {
+			return Ok(HttpResponse::BadRequest()
+				.body("Command is not supported for event streams"));
+		}
+		GatewayCommand::PresenceUpdate(presence)
+			if presence.status == Status::Offline =>
+		{
+			return Ok(HttpResponse::BadRequest()
+				.body("Use the invisible status to appear offline"));
+		}
+		_ => (),
+	}
+
+	let handle = match find_stream(&streams, *session_id, &user) {
+		Some(handle) => handle,
+		None => return Ok(HttpResponse::NotFound().body("Unknown session")),
+	};
+
+	handle
+		.addr
+		.send(Command(command))
+		.await
+		.map_err(ErrorInternalServerError)?;
+
+	Ok(HttpResponse::NoContent().finish())
+}
+
+pub fn routes(client: &RedisFetcher, cfg: &mut web::ServiceConfig) {
+	cfg.service(
+		web::scope("/events")
+			.service(stream_events)
+			.service(poll_events)
+			.service(send_command)
+			.wrap(RateLimit::new(client.clone()))
+			.wrap(Auth::new(client.clone())),
+	);
+}
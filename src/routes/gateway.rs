@@ -1,14 +1,47 @@
-use crate::ws::{server::ShikiServer, session::GatewaySession};
+use crate::{
+	version::ApiVersion,
+	ws::{
+		close_code::{CloseCode, CloseCodeInfo},
+		compression::{Compression, CompressionStats, Compressor},
+		encoding::Encoding,
+		intents::Intents,
+		server::ShikiServer,
+		session::{GatewaySession, MAX_FRAME_SIZE},
+	},
+};
 use actix::Addr;
-use actix_web::{get, web, Error, HttpRequest, HttpResponse};
+use actix_web::{
+	error::ErrorInternalServerError, get, web, Error, HttpRequest, HttpResponse,
+};
 use actix_web_actors::ws;
+use serde::Deserialize;
 use std::time::Instant;
 
+#[derive(Deserialize)]
+struct GatewayQuery {
+	/// Encoding of the payloads, JSON unless specified
+	#[serde(default)]
+	encoding: Encoding,
+	/// Transport compression to use for payloads sent to the client
+	compress: Option<Compression>,
+	/// Version of the gateway, payloads keep their unversioned shape unless
+	/// specified
+	#[serde(default)]
+	v: ApiVersion,
+}
+
 #[get("/gateway")]
 async fn gateway(
 	req: HttpRequest, stream: web::Payload, srv: web::Data<Addr<ShikiServer>>,
+	query: web::Query<GatewayQuery>,
 ) -> Result<HttpResponse, Error> {
-	ws::start(
+	let compressor = query
+		.compress
+		.map(Compressor::new)
+		.transpose()
+		.map_err(ErrorInternalServerError)?;
+
+	ws::WsResponseBuilder::new(
 		GatewaySession {
 			session_id: 0,
 			hb: Instant::now(),
@@ -17,12 +50,32 @@ async fn gateway(
 			name: None,
 			addr: srv.get_ref().clone(),
 			token: None,
+			commands: 0,
+			commands_since: Instant::now(),
+			seq: 0,
+			intents: Intents::default(),
+			encoding: query.encoding,
+			version: query.v,
+			compressor,
+			compression_stats: CompressionStats::default(),
 		},
 		&req,
 		stream,
 	)
+	.frame_size(MAX_FRAME_SIZE)
+	.start()
+	.map(|res| query.v.with_headers(res))
+}
+
+/// Lists the codes the gateway closes connections with, and whether clients
+/// should reconnect after each of them.
+#[get("/gateway/close-codes")]
+async fn close_codes() -> HttpResponse {
+	HttpResponse::Ok().json(
+		CloseCode::ALL.into_iter().map(CloseCodeInfo::from).collect::<Vec<_>>(),
+	)
 }
 
 pub fn routes(cfg: &mut web::ServiceConfig) {
-	cfg.service(gateway);
+	cfg.service(close_codes).service(gateway);
 }
@@ -1,14 +1,26 @@
-use crate::{redis::RedisFetcher, utils::validate_token};
+use crate::{
+	models::User,
+	redis::{BucketState, RedisFetcher},
+	utils::validate_token,
+	version::ApiVersion,
+};
 use actix_web::{
+	body::EitherBody,
 	dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
 	error::ErrorInternalServerError,
-	HttpMessage,
+	http::{
+		header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+		Method,
+	},
+	HttpMessage, HttpResponse,
 };
 use actix_web::{error::ErrorUnauthorized, Error};
+use chrono::Utc;
 use futures_util::future::LocalBoxFuture;
 use std::{
 	future::{ready, Ready},
 	rc::Rc,
+	time::Duration,
 };
 
 pub struct Auth {
@@ -114,3 +126,259 @@ where
 		})
 	}
 }
+
+/// A token bucket which limits how often a route can be called.
+pub struct Bucket {
+	/// Name of the bucket, sent in the `X-RateLimit-Bucket` header
+	pub name: &'static str,
+	/// HTTP method of the route
+	pub method: Method,
+	/// Path pattern of the route, as it is registered without a version
+	pub pattern: &'static str,
+	/// Requests which can be made in a burst
+	pub limit: u64,
+	/// Time it takes for an empty bucket to refill
+	pub period: Duration,
+}
+
+/// Limits every request of a user or IP address, on top of their route bucket.
+pub const GLOBAL_BUCKET: Bucket = Bucket {
+	name: "global",
+	method: Method::GET,
+	pattern: "",
+	limit: 50,
+	period: Duration::from_secs(1),
+};
+
+/// Limits the messages a user sends, through the API or over IRC.
+pub const CREATE_MESSAGE_BUCKET: Bucket = Bucket {
+	name: "create_message",
+	method: Method::POST,
+	pattern: "/api/channels/{channel_id}/messages",
+	limit: 5,
+	period: Duration::from_secs(5),
+};
+
+/// Rate limits of individual routes. Routes which are not listed are only
+/// limited by the global bucket.
+pub const BUCKETS: &[Bucket] = &[
+	CREATE_MESSAGE_BUCKET,
+	Bucket {
+		name: "typing",
+		method: Method::POST,
+		pattern: "/api/channels/{channel_id}/typing",
+		limit: 5,
+		period: Duration::from_secs(5),
+	},
+	Bucket {
+		name: "register",
+		method: Method::POST,
+		pattern: "/auth/register",
+		limit: 3,
+		period: Duration::from_secs(60 * 60),
+	},
+	Bucket {
+		name: "login",
+		method: Method::POST,
+		pattern: "/auth/login",
+		limit: 5,
+		period: Duration::from_secs(60),
+	},
+	Bucket {
+		name: "rtc_connect",
+		method: Method::POST,
+		pattern: "/connect",
+		limit: 5,
+		period: Duration::from_secs(60),
+	},
+];
+
+/// Rate limits requests with Redis backed token buckets, keyed by the user
+/// or, for anonymous requests, their IP address. On authenticated scopes it
+/// is wrapped inside `Auth`, so the user is the one `Auth` already looked up.
+pub struct RateLimit {
+	client: RedisFetcher,
+}
+
+impl RateLimit {
+	pub fn new(client: RedisFetcher) -> Self {
+		RateLimit { client }
+	}
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+	S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>
+		+ 'static,
+	S::Future: 'static,
+	B: 'static,
+{
+	type Response = ServiceResponse<EitherBody<B>>;
+	type Error = Error;
+	type InitError = ();
+	type Transform = RateLimitMiddleware<S>;
+	type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+	fn new_transform(&self, service: S) -> Self::Future {
+		ready(Ok(RateLimitMiddleware {
+			client: self.client.clone(),
+			service: Rc::new(service),
+		}))
+	}
+}
+
+pub struct RateLimitMiddleware<S> {
+	client: RedisFetcher,
+	service: Rc<S>,
+}
+
+/// Who a request is counted against: the user `Auth` authenticated it as or,
+/// for routes without authentication, its IP address. Requests are not counted
+/// against the token itself, or clients could get fresh buckets by sending
+/// made up tokens.
+fn rate_limit_subject(req: &ServiceRequest) -> String {
+	if let Some(user) = req.extensions().get::<User>() {
+		return user_subject(user.id);
+	}
+
+	let info = req.connection_info();
+	format!("ip:{}", info.realip_remote_addr().unwrap_or("unknown"))
+}
+
+fn user_subject(user_id: i64) -> String {
+	format!("user:{user_id}")
+}
+
+/// Takes a token from the bucket of a user for an action taken outside of the
+/// HTTP routes, such as a message sent over IRC, so it shares the limits of
+/// the route. Returns whether the action is allowed.
+pub async fn take_user_token(
+	client: &RedisFetcher, bucket: &Bucket, user_id: i64,
+) -> bool {
+	take_token(client, bucket, &user_subject(user_id))
+		.await
+		.is_none_or(|state| state.allowed)
+}
+
+/// Takes a token from a bucket. Requests are let through if Redis cannot be
+/// reached, an outage should not take the API down with it.
+async fn take_token(
+	client: &RedisFetcher, bucket: &Bucket, subject: &str,
+) -> Option<BucketState> {
+	let key = format!("ratelimit_{}_{subject}", bucket.name);
+
+	match client.take_token(&key, bucket.limit, bucket.period).await {
+		Ok(state) => Some(state),
+		Err(e) => {
+			log::error!("Failed to check rate limit: {}", e);
+			None
+		}
+	}
+}
+
+fn insert_rate_limit_headers(
+	headers: &mut HeaderMap, bucket: &Bucket, state: &BucketState,
+) {
+	let reset = Utc::now().timestamp_millis() as f64 / 1000.0
+		+ state.reset_after.as_secs_f64();
+	let values = [
+		("x-ratelimit-limit", bucket.limit.to_string()),
+		("x-ratelimit-remaining", state.remaining.to_string()),
+		("x-ratelimit-reset", format!("{reset:.3}")),
+		(
+			"x-ratelimit-reset-after",
+			format!("{:.3}", state.reset_after.as_secs_f64()),
+		),
+		("x-ratelimit-bucket", bucket.name.to_string()),
+	];
+
+	for (name, value) in values {
+		if let Ok(value) = HeaderValue::from_str(&value) {
+			headers.insert(HeaderName::from_static(name), value);
+		}
+	}
+}
+
+fn too_many_requests(
+	bucket: &Bucket, state: &BucketState, global: bool,
+) -> HttpResponse {
+	let mut res = HttpResponse::TooManyRequests().json(serde_json::json!({
+		"message": "You are being rate limited.",
+		"retry_after": state.retry_after.as_secs_f64(),
+		"global": global,
+	}));
+	let headers = res.headers_mut();
+
+	insert_rate_limit_headers(headers, bucket, state);
+	headers.insert(
+		RETRY_AFTER,
+		HeaderValue::from(state.retry_after.as_secs_f64().ceil() as u64),
+	);
+
+	if global {
+		headers.insert(
+			HeaderName::from_static("x-ratelimit-global"),
+			HeaderValue::from_static("true"),
+		);
+	}
+
+	res
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+	S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>
+		+ 'static,
+	S::Future: 'static,
+	B: 'static,
+{
+	type Response = ServiceResponse<EitherBody<B>>;
+	type Error = Error;
+	type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+	forward_ready!(service);
+
+	fn call(&self, req: ServiceRequest) -> Self::Future {
+		let client = self.client.clone();
+		let service = self.service.clone();
+		let subject = rate_limit_subject(&req);
+		let pattern = req
+			.match_pattern()
+			.map(|pattern| ApiVersion::unversioned(&pattern));
+		let bucket = BUCKETS.iter().find(|bucket| {
+			bucket.method == req.method()
+				&& pattern.as_deref() == Some(bucket.pattern)
+		});
+
+		Box::pin(async move {
+			let global = take_token(&client, &GLOBAL_BUCKET, &subject).await;
+
+			if let Some(state) = global.filter(|state| !state.allowed) {
+				let res = too_many_requests(&GLOBAL_BUCKET, &state, true);
+				return Ok(req.into_response(res).map_into_right_body());
+			}
+
+			let limited = match bucket {
+				Some(bucket) => take_token(&client, bucket, &subject)
+					.await
+					.map(|state| (bucket, state)),
+				None => global.map(|state| (&GLOBAL_BUCKET, state)),
+			};
+
+			if let Some((bucket, state)) =
+				limited.filter(|(_, state)| !state.allowed)
+			{
+				let res = too_many_requests(bucket, &state, false);
+				return Ok(req.into_response(res).map_into_right_body());
+			}
+
+			let mut res = service.call(req).await?;
+
+			if let Some((bucket, state)) = limited {
+				insert_rate_limit_headers(res.headers_mut(), bucket, &state);
+			}
+
+			Ok(res.map_into_left_body())
+		})
+	}
+}
@@ -1,28 +1,58 @@
 mod api;
 mod auth;
+mod docs;
+mod events;
 mod gateway;
-mod middleware;
+pub mod middleware;
 mod rtc;
 
-use crate::redis::RedisFetcher;
+use crate::{redis::RedisFetcher, version::ApiVersion};
 use actix_web::web;
 use mongodb::Client;
 
 pub const DB_NAME: &str = "shiki";
+pub const AUDIT_LOG_COLL_NAME: &str = "audit_logs";
+pub const BAN_COLL_NAME: &str = "bans";
 pub const CHANNEL_COLL_NAME: &str = "channels";
+pub const GUILD_COLL_NAME: &str = "guilds";
+pub const INVITE_COLL_NAME: &str = "invites";
+pub const MEMBER_COLL_NAME: &str = "members";
 pub const MESSAGE_COLL_NAME: &str = "messages";
+pub const READ_STATE_COLL_NAME: &str = "read_states";
+pub const ROLE_COLL_NAME: &str = "roles";
 pub const USER_COLL_NAME: &str = "users";
 
 pub async fn setup_indexes(client: &Client) -> anyhow::Result<()> {
-	auth::setup_indexes(client).await
+	auth::setup_indexes(client).await?;
+	api::setup_indexes(client).await
 }
 
+/// Mounts the routes of every served version. A handler replaced in a newer
+/// version stays mounted in the scopes of the older ones, which pick their
+/// handlers by `version`.
 pub fn routes(client: &RedisFetcher, cfg: &mut web::ServiceConfig) {
+	// The latest versions come first, as the unversioned scopes would catch
+	// their requests otherwise.
+	for version in ApiVersion::SERVED {
+		cfg.configure(|cfg| {
+			api::routes(client, version, cfg);
+		})
+		.configure(|cfg| {
+			auth::routes(client, version, cfg);
+		});
+	}
+
 	cfg.configure(|cfg| {
-		api::routes(client, cfg);
+		events::routes(client, cfg);
 	});
 
-	cfg.configure(auth::routes)
-		.configure(gateway::routes)
-		.configure(rtc::routes);
+	// The routes without authentication are limited by IP address. This scope
+	// catches every remaining path, so it has to come last.
+	cfg.service(
+		web::scope("")
+			.configure(docs::routes)
+			.configure(gateway::routes)
+			.configure(rtc::routes)
+			.wrap(middleware::RateLimit::new(client.clone())),
+	);
 }
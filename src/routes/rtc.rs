@@ -4,8 +4,19 @@ use actix_web::{
 	HttpResponse,
 };
 use futures_util::lock::Mutex;
+use utoipa::OpenApi;
 use webrtc_unreliable::SessionEndpoint;
 
+/// Opens a WebRTC session. The body is the SDP offer of the client, and the
+/// response is the session description answering it.
+#[utoipa::path(
+	tag = "rtc",
+	request_body(content = String, content_type = "text/plain"),
+	responses(
+		(status = 200, description = "The session description of the server", content_type = "application/json"),
+		(status = 500, description = "The offer could not be answered"),
+	)
+)]
 #[post("/connect")]
 async fn connect(
 	se_mutex: web::Data<Mutex<SessionEndpoint>>, sdp: web::Payload,
@@ -20,6 +31,11 @@ async fn connect(
 	}
 }
 
+/// Documents the WebRTC routes.
+#[derive(OpenApi)]
+#[openapi(paths(connect))]
+pub struct ApiDoc;
+
 pub fn routes(cfg: &mut web::ServiceConfig) {
 	cfg.service(connect);
 }
@@ -9,6 +9,11 @@ use argon2::{
 	},
 	Argon2,
 };
+use serde::{Deserialize, Deserializer};
+use validator::ValidationError;
+
+/// Minutes of inactivity after which a thread may be archived.
+pub const AUTO_ARCHIVE_DURATIONS: [u64; 4] = [60, 1440, 4320, 10080];
 
 pub async fn validate_token(
 	fetcher: RedisFetcher, token: String,
@@ -37,3 +42,25 @@ pub async fn verify_password(
 
 	Argon2::default().verify_password(password, &parsed_hash)
 }
+
+/// Deserializes a present field as `Some`, so that `Option<Option<T>>` can tell
+/// an explicit `null` apart from a missing field.
+pub fn deserialize_some<'de, T, D>(
+	deserializer: D,
+) -> Result<Option<T>, D::Error>
+where
+	T: Deserialize<'de>,
+	D: Deserializer<'de>,
+{
+	T::deserialize(deserializer).map(Some)
+}
+
+pub fn validate_auto_archive_duration(
+	duration: u64,
+) -> Result<(), ValidationError> {
+	if AUTO_ARCHIVE_DURATIONS.contains(&duration) {
+		Ok(())
+	} else {
+		Err(ValidationError::new("auto_archive_duration"))
+	}
+}
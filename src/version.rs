@@ -0,0 +1,124 @@
+use actix_web::{
+	http::header::{HeaderName, HeaderValue, HttpDate},
+	middleware::DefaultHeaders,
+	HttpResponse,
+};
+use serde::Deserialize;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Version of the HTTP API and of the gateway. The routes of every served
+/// version are mounted under their own scope, such as `/api/v1`, and gateway
+/// clients pick theirs with `/gateway?v=1`.
+#[derive(
+	Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Default,
+)]
+#[serde(try_from = "u8")]
+pub enum ApiVersion {
+	/// Routes mounted without a version, and gateway connections without `v`,
+	/// kept for clients from before the API was versioned
+	#[default]
+	Unversioned,
+	V1,
+}
+
+impl ApiVersion {
+	/// The versions which are still served, the latest first.
+	pub const SERVED: [ApiVersion; 2] =
+		[ApiVersion::V1, ApiVersion::Unversioned];
+
+	/// The version clients should use.
+	pub const LATEST: ApiVersion = ApiVersion::V1;
+
+	/// Path segment which follows the scopes of this version, if any.
+	fn segment(self) -> Option<&'static str> {
+		match self {
+			ApiVersion::Unversioned => None,
+			ApiVersion::V1 => Some("v1"),
+		}
+	}
+
+	/// Path of `scope` in this version, such as `/api/v1` for `/api`.
+	pub fn scope(self, scope: &str) -> String {
+		match self.segment() {
+			Some(segment) => format!("{scope}/{segment}"),
+			None => scope.to_string(),
+		}
+	}
+
+	/// Removes the version from the path pattern of a route, such as
+	/// `/api/roles` for `/api/v1/roles`, so every version of a route shares
+	/// its rate limits.
+	pub fn unversioned(pattern: &str) -> String {
+		let mut segments = pattern.split('/').collect::<Vec<_>>();
+		// The version follows the scope, as in ["", "api", "v1", ...]
+		let versioned = segments.get(2).is_some_and(|segment| {
+			ApiVersion::SERVED.iter().any(|v| v.segment() == Some(segment))
+		});
+
+		if versioned {
+			segments.remove(2);
+		}
+
+		segments.join("/")
+	}
+
+	/// Unix timestamps for when the version was deprecated and for when it
+	/// stops being served, if it is deprecated.
+	fn deprecation(self) -> Option<(u64, u64)> {
+		match self {
+			// 2026-10-18 and 2027-04-18
+			ApiVersion::Unversioned => Some((1792281600, 1808006400)),
+			ApiVersion::V1 => None,
+		}
+	}
+
+	/// The `Deprecation` and `Sunset` headers of responses in this version,
+	/// none unless it is deprecated.
+	pub fn headers(self) -> Vec<(HeaderName, HeaderValue)> {
+		let (deprecated_at, sunset_at) = match self.deprecation() {
+			Some(deprecation) => deprecation,
+			None => return Vec::new(),
+		};
+		let sunset =
+			HttpDate::from(UNIX_EPOCH + Duration::from_secs(sunset_at));
+
+		vec![
+			(
+				HeaderName::from_static("deprecation"),
+				HeaderValue::from_str(&format!("@{deprecated_at}")).unwrap(),
+			),
+			(
+				HeaderName::from_static("sunset"),
+				HeaderValue::from_str(&sunset.to_string()).unwrap(),
+			),
+		]
+	}
+
+	/// Middleware adding the headers of this version to every response of a
+	/// scope.
+	pub fn default_headers(self) -> DefaultHeaders {
+		self.headers()
+			.into_iter()
+			.fold(DefaultHeaders::new(), |headers, header| headers.add(header))
+	}
+
+	/// Adds the headers of this version to `res`.
+	pub fn with_headers(self, mut res: HttpResponse) -> HttpResponse {
+		for (name, value) in self.headers() {
+			res.headers_mut().insert(name, value);
+		}
+
+		res
+	}
+}
+
+impl TryFrom<u8> for ApiVersion {
+	type Error = String;
+
+	fn try_from(version: u8) -> Result<Self, Self::Error> {
+		match version {
+			1 => Ok(ApiVersion::V1),
+			_ => Err(format!("Unknown API version {version}")),
+		}
+	}
+}
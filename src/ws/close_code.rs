@@ -0,0 +1,32 @@
+use actix_web_actors::ws;
+use serde::Serialize;
+
+// Close codes are shared with clients, this file contains how the server
+// closes connections with them.
+pub use shiki_client::close_code::*;
+
+/// Reason to close a connection with, the description of the code.
+pub fn close_reason(code: CloseCode) -> ws::CloseReason {
+	ws::CloseReason {
+		code: ws::CloseCode::Other(code as u16),
+		description: Some(code.description().to_string()),
+	}
+}
+
+/// Documentation of a close code, as returned by the gateway.
+#[derive(Serialize, Debug)]
+pub struct CloseCodeInfo {
+	pub code: CloseCode,
+	pub description: &'static str,
+	pub reconnect: bool,
+}
+
+impl From<CloseCode> for CloseCodeInfo {
+	fn from(code: CloseCode) -> Self {
+		CloseCodeInfo {
+			code,
+			description: code.description(),
+			reconnect: code.reconnect(),
+		}
+	}
+}
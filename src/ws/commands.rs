@@ -0,0 +1,3 @@
+// The commands clients send are shared with them, and validated by the
+// server with the "validate" feature of shiki_client.
+pub use shiki_client::commands::*;
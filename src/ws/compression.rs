@@ -0,0 +1,122 @@
+use flate2::{Compress, FlushCompress};
+use serde::Deserialize;
+use std::io;
+use zstd::stream::raw::{Encoder, InBuffer, Operation, OutBuffer};
+
+/// Transport compression a client can ask for when connecting to the gateway.
+#[derive(Deserialize, Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Compression {
+	#[serde(rename = "zlib-stream")]
+	ZlibStream,
+	#[serde(rename = "zstd-stream")]
+	ZstdStream,
+}
+
+/// Compression context shared by every payload sent over a connection, so
+/// later payloads can refer back to earlier ones. Each payload is flushed, so
+/// it can be decompressed as soon as it is received. zlib payloads end with
+/// the `00 00 ff ff` suffix.
+pub enum Compressor {
+	Zlib(Box<Compress>),
+	Zstd(Box<Encoder<'static>>),
+}
+
+impl std::fmt::Debug for Compressor {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Compressor::Zlib(_) => f.write_str("Zlib"),
+			Compressor::Zstd(_) => f.write_str("Zstd"),
+		}
+	}
+}
+
+impl Compressor {
+	pub fn new(compression: Compression) -> io::Result<Self> {
+		Ok(match compression {
+			Compression::ZlibStream => Compressor::Zlib(Box::new(
+				Compress::new(flate2::Compression::default(), true),
+			)),
+			Compression::ZstdStream => {
+				Compressor::Zstd(Box::new(Encoder::new(0)?))
+			}
+		})
+	}
+
+	pub fn compress(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+		let mut output = Vec::with_capacity(data.len() / 2 + 64);
+
+		match self {
+			Compressor::Zlib(compress) => {
+				let start = compress.total_in();
+
+				loop {
+					let consumed = (compress.total_in() - start) as usize;
+
+					compress.compress_vec(
+						&data[consumed..],
+						&mut output,
+						FlushCompress::Sync,
+					)?;
+
+					// The payload is flushed once all of it was consumed and
+					// there was room left in the output.
+					if compress.total_in() - start == data.len() as u64
+						&& output.len() < output.capacity()
+					{
+						break;
+					}
+
+					output.reserve(output.capacity());
+				}
+			}
+			Compressor::Zstd(encoder) => {
+				let mut input = InBuffer::around(data);
+
+				loop {
+					let pos = output.len();
+					let mut buffer = OutBuffer::around_pos(&mut output, pos);
+
+					encoder.run(&mut input, &mut buffer)?;
+
+					if input.pos() == data.len()
+						&& encoder.flush(&mut buffer)? == 0
+					{
+						break;
+					}
+
+					output.reserve(output.capacity());
+				}
+			}
+		}
+
+		Ok(output)
+	}
+}
+
+/// How well compression worked for a session.
+#[derive(Debug, Default)]
+pub struct CompressionStats {
+	/// Payloads which were compressed
+	pub payloads: u64,
+	/// Bytes before compression
+	pub bytes_in: u64,
+	/// Bytes sent to the client
+	pub bytes_out: u64,
+}
+
+impl CompressionStats {
+	pub fn record(&mut self, bytes_in: usize, bytes_out: usize) {
+		self.payloads += 1;
+		self.bytes_in += bytes_in as u64;
+		self.bytes_out += bytes_out as u64;
+	}
+
+	/// Compressed size as a fraction of the uncompressed size.
+	pub fn ratio(&self) -> f64 {
+		if self.bytes_in == 0 {
+			return 1.0;
+		}
+
+		self.bytes_out as f64 / self.bytes_in as f64
+	}
+}
@@ -0,0 +1,42 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Encoding of the payloads sent over a gateway connection, negotiated when
+/// the client connects.
+#[derive(Deserialize, Debug, PartialEq, Eq, Copy, Clone, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+	#[default]
+	Json,
+	Msgpack,
+	Cbor,
+}
+
+impl Encoding {
+	/// Whether payloads are sent in binary frames rather than text frames.
+	pub fn is_binary(&self) -> bool {
+		*self != Encoding::Json
+	}
+
+	pub fn encode<T: Serialize>(&self, payload: &T) -> Result<Vec<u8>> {
+		Ok(match self {
+			Encoding::Json => serde_json::to_vec(payload)?,
+			// Structs are encoded as maps, so payloads have the same shape
+			// in every encoding.
+			Encoding::Msgpack => rmp_serde::to_vec_named(payload)?,
+			Encoding::Cbor => {
+				let mut data = Vec::new();
+				ciborium::into_writer(payload, &mut data)?;
+				data
+			}
+		})
+	}
+
+	pub fn decode(&self, data: &[u8]) -> Result<serde_json::Value> {
+		Ok(match self {
+			Encoding::Json => serde_json::from_slice(data)?,
+			Encoding::Msgpack => rmp_serde::from_slice(data)?,
+			Encoding::Cbor => ciborium::from_reader(data)?,
+		})
+	}
+}
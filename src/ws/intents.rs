@@ -0,0 +1,36 @@
+use crate::ws::events::Event;
+
+// Intents are shared with clients, this file contains how the server filters
+// events with them.
+pub use shiki_client::intents::*;
+
+pub trait AllowsEvent {
+	/// Whether a session with these intents receives an event. Events which
+	/// are not part of an intent, like Ready, are always sent.
+	fn allows(self, event: &Event) -> bool;
+}
+
+impl AllowsEvent for Intents {
+	fn allows(self, event: &Event) -> bool {
+		let intent = match event {
+			Event::ChannelCreate(_)
+			| Event::ChannelUpdate(_)
+			| Event::ChannelDelete(_)
+			| Event::ChannelPinsUpdate(_)
+			| Event::ThreadCreate(_)
+			| Event::ThreadUpdate(_) => Self::GUILDS,
+			Event::GuildMemberAdd(_)
+			| Event::GuildMemberRemove(_)
+			| Event::GuildMemberUpdate(_)
+			| Event::GuildBanAdd(_)
+			| Event::GuildBanRemove(_) => Self::GUILD_MEMBERS,
+			Event::MessageCreate(_) => Self::GUILD_MESSAGES,
+			Event::TypingStart(_) | Event::TypingStop(_) => Self::TYPING,
+			Event::PresenceUpdate(_) => Self::PRESENCES,
+			Event::VoiceStateUpdate(_) => Self::GUILD_VOICE_STATES,
+			_ => return true,
+		};
+
+		self.contains(intent)
+	}
+}
@@ -1,3 +1,9 @@
+pub mod close_code;
+pub mod commands;
+pub mod compression;
+pub mod encoding;
 pub mod events;
+pub mod intents;
 pub mod server;
 pub mod session;
+pub mod stream;
@@ -1,6 +1,12 @@
-use super::events::{self, Event};
+use super::{
+	close_code::CloseCode,
+	commands,
+	events::{self, Event},
+	intents::Intents,
+	session::HEARTBEAT_INTERVAL,
+};
 use crate::{
-	models,
+	models::{self, ChannelType, Presence, Snowflakes, Status},
 	redis::RedisFetcher,
 	utils::{self},
 	ws::events::Ready,
@@ -10,13 +16,43 @@ use chrono::Utc;
 use mongodb::bson::doc;
 use rand::{self, rngs::ThreadRng, Rng};
 use serde::{Deserialize, Serialize};
+pub use shiki_client::model::{Guild, ReadState, User};
 use std::{
-	collections::{HashMap, HashSet},
+	collections::{hash_map::Entry, HashMap, HashSet},
 	sync::{
 		atomic::{AtomicUsize, Ordering},
 		Arc,
 	},
+	time::{Duration, Instant},
 };
+use utoipa::ToSchema;
+
+/// How long a typing indicator lasts unless it is refreshed
+const TYPING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a user may refresh their typing indicator in a channel
+const TYPING_THROTTLE: Duration = Duration::from_secs(5);
+
+/// How often expired typing indicators are cleared
+const TYPING_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often the stored presences of online users are kept alive
+const PRESENCE_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Seconds until a stored presence expires if it is not kept alive
+const PRESENCE_TTL: usize = 90;
+
+/// How often inactive threads are archived
+const THREAD_ARCHIVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How many online users are sent with Ready
+const READY_MEMBER_LIMIT: usize = 100;
+
+/// How many members are sent in each GuildMembersChunk
+const GUILD_MEMBERS_CHUNK_SIZE: usize = 100;
+
+/// How many members are sent when subscribing to a member list
+const MEMBER_LIST_LIMIT: usize = 100;
 
 /// New chat session is created
 #[derive(Message)]
@@ -38,6 +74,8 @@ pub struct Disconnect {
 pub struct Identify {
 	pub id: usize,
 	pub token: String,
+	/// The intents the session identifies with
+	pub intents: Intents,
 }
 
 /// Create new channel
@@ -50,6 +88,26 @@ pub struct Channel {
 	pub guild_id: Option<i64>,
 	/// Channel name
 	pub name: String,
+	/// Channel topic
+	pub description: Option<String>,
+	/// Channel type
+	#[serde(rename = "type")]
+	pub kind: ChannelType,
+	/// Sorting position
+	pub position: i64,
+	/// ID of the parent category, or of the channel a thread was started in
+	pub parent_id: Option<i64>,
+	/// ID of the message a thread was started from
+	pub starter_message_id: Option<i64>,
+	/// IDs of the users who joined a thread
+	pub member_ids: Snowflakes,
+	/// Whether a thread is archived
+	pub archived: bool,
+	/// Minutes of inactivity after which a thread is archived
+	pub auto_archive_duration: u64,
+	/// When the last message was sent in a thread
+	#[serde(skip_serializing, skip_deserializing)]
+	pub last_activity_at: usize,
 	/// IDs of sessions in the channel
 	#[serde(skip_serializing, skip_deserializing)]
 	pub sessions: HashSet<usize>,
@@ -59,30 +117,173 @@ impl From<models::Channel> for Channel {
 	fn from(channel: models::Channel) -> Self {
 		Self {
 			id: channel.id,
-			guild_id: None,
+			guild_id: channel.guild_id,
 			name: channel.name,
+			description: channel.description,
+			kind: channel.kind,
+			position: channel.position,
+			parent_id: channel.parent_id,
+			starter_message_id: channel.starter_message_id,
+			member_ids: channel.member_ids,
+			archived: channel.archived,
+			auto_archive_duration: channel.auto_archive_duration,
+			last_activity_at: channel.last_activity_at,
 			sessions: HashSet::new(),
 		}
 	}
 }
 
-/// Create new Guild
-#[derive(Message, Serialize, Debug, Clone, Deserialize)]
-#[rtype(result = "Option<Guild>")]
-pub struct Guild {
-	/// ID of the guild
-	pub id: i64,
-	/// Guild name
-	pub name: String,
+impl From<&Channel> for shiki_client::model::Channel {
+	fn from(channel: &Channel) -> Self {
+		Self {
+			id: channel.id,
+			guild_id: channel.guild_id,
+			name: channel.name.clone(),
+			description: channel.description.clone(),
+			kind: channel.kind,
+			position: channel.position,
+			parent_id: channel.parent_id,
+			starter_message_id: channel.starter_message_id,
+			member_ids: channel.member_ids.clone(),
+			archived: channel.archived,
+			auto_archive_duration: channel.auto_archive_duration,
+		}
+	}
+}
+
+impl Channel {
+	/// Whether a thread has gone without messages for longer than its auto
+	/// archive duration at `now`.
+	fn is_inactive(&self, now: usize) -> bool {
+		self.kind == ChannelType::Thread
+			&& !self.archived
+			&& self.last_activity_at + self.auto_archive_duration as usize * 60
+				<= now
+	}
+}
+
+/// Replace an existing channel with its updated version
+#[derive(Message)]
+#[rtype(result = "Option<Channel>")]
+pub struct UpdateChannel {
+	pub channel: Channel,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
-pub struct User {
+/// Remove a channel
+#[derive(Message)]
+#[rtype(result = "Option<Channel>")]
+pub struct DeleteChannel {
+	/// Channel ID
 	pub id: i64,
-	pub username: String,
-	pub joined: usize,
-	/// Avatar URL
-	pub avatar: Option<String>,
+}
+
+impl From<models::Guild> for Guild {
+	fn from(guild: models::Guild) -> Self {
+		Self { id: guild.id, name: guild.name, owner_id: guild.owner_id }
+	}
+}
+
+/// Members of a guild requested by a session, sent back as GuildMembersChunk
+/// events.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RequestGuildMembers {
+	/// Session which requested the members
+	pub id: usize,
+	pub request: commands::RequestGuildMembers,
+}
+
+/// Subscribes a session to the member list of a guild, replacing any previous
+/// subscription.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SubscribeMemberList {
+	/// Session which subscribed
+	pub id: usize,
+	/// Guild ID, or None for every user
+	pub guild_id: Option<i64>,
+}
+
+/// Change the voice state of a user.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UpdateVoiceState {
+	/// User ID
+	pub user_id: i64,
+	/// Voice channel to join, or None to leave
+	pub channel_id: Option<i64>,
+	/// Whether the user muted themselves
+	pub self_mute: bool,
+	/// Whether the user deafened themselves
+	pub self_deaf: bool,
+}
+
+/// Member list a session is subscribed to.
+#[derive(Debug)]
+struct MemberList {
+	guild_id: Option<i64>,
+	/// IDs of the members, or None if the list contains every user
+	user_ids: Option<HashSet<i64>>,
+}
+
+impl MemberList {
+	fn contains(&self, user_id: i64) -> bool {
+		self.user_ids.as_ref().is_none_or(|ids| ids.contains(&user_id))
+	}
+}
+
+/// A user was kicked from a guild
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RemoveMember {
+	/// Guild ID
+	pub guild_id: i64,
+	/// User ID
+	pub user_id: i64,
+}
+
+/// A user was banned from a guild
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct BanMember {
+	/// Guild ID
+	pub guild_id: i64,
+	/// User ID
+	pub user_id: i64,
+}
+
+/// A user's ban was lifted
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UnbanMember {
+	/// Guild ID
+	pub guild_id: i64,
+	/// User ID
+	pub user_id: i64,
+}
+
+/// A member was timed out or had their timeout lifted
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct TimeoutMember {
+	/// Guild ID
+	pub guild_id: i64,
+	/// User ID
+	pub user_id: i64,
+	/// When the timeout ends
+	pub communication_disabled_until: Option<usize>,
+}
+
+/// A user joined a guild
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct AddMember {
+	/// Guild ID
+	pub guild_id: i64,
+	/// The user who joined
+	pub user: User,
+	/// When the user joined
+	pub joined_at: usize,
 }
 
 impl From<models::User> for User {
@@ -97,7 +298,7 @@ impl From<models::User> for User {
 }
 
 /// Create new message
-#[derive(Message, Serialize, Deserialize, Debug, Clone)]
+#[derive(Message, Serialize, Deserialize, Debug, Clone, ToSchema)]
 #[rtype(result = "Option<CreateMessage>")]
 pub struct CreateMessage {
 	/// Message ID
@@ -114,6 +315,65 @@ pub struct CreateMessage {
 	/// Message creation time
 	#[serde(default = "current_utc_timestamp", skip_deserializing)]
 	pub created_at: usize,
+	/// IDs of the users mentioned in the message
+	#[serde(skip_deserializing)]
+	pub mentions: Snowflakes,
+	/// IDs of the roles mentioned in the message
+	#[serde(skip_deserializing)]
+	pub mention_roles: Snowflakes,
+	/// Whether the message mentions everyone
+	#[serde(skip_deserializing)]
+	pub mention_everyone: bool,
+	/// IDs of every user notified by the message, including those mentioned
+	/// through a role.
+	#[serde(skip)]
+	pub mentioned_users: HashSet<i64>,
+}
+
+/// Builds the read state of a user in every text channel. Channels the user has
+/// never acknowledged are entirely unread.
+async fn fetch_read_states(
+	client: &RedisFetcher, user: &models::User,
+	channels: &HashMap<i64, Channel>,
+) -> Vec<ReadState> {
+	let acked = match client.fetch_read_states(user.id).await {
+		Ok(states) => states
+			.into_iter()
+			.map(|state| (state.channel_id, state.last_message_id))
+			.collect::<HashMap<_, _>>(),
+		Err(e) => {
+			log::error!("Failed to fetch read states: {}", e);
+			return Vec::new();
+		}
+	};
+	let last_message_ids = channels
+		.values()
+		.filter(|channel| channel.kind != ChannelType::Category)
+		.map(|channel| (channel.id, acked.get(&channel.id).copied()))
+		.collect::<Vec<_>>();
+	let counts =
+		match client.count_unread_channels(user, &last_message_ids).await {
+			Ok(counts) => counts,
+			Err(e) => {
+				log::error!("Failed to count unread messages: {}", e);
+				return Vec::new();
+			}
+		};
+
+	last_message_ids
+		.into_iter()
+		.map(|(channel_id, last_message_id)| {
+			let (unread_count, mention_count) =
+				counts.get(&channel_id).copied().unwrap_or((0, 0));
+
+			ReadState {
+				channel_id,
+				last_message_id,
+				unread_count,
+				mention_count,
+			}
+		})
+		.collect()
 }
 
 fn current_utc_timestamp() -> usize {
@@ -121,17 +381,76 @@ fn current_utc_timestamp() -> usize {
 	utc_now.timestamp() as usize
 }
 
+/// Start a typing indicator for a user in a channel.
+#[derive(Message)]
+#[rtype(result = "TypingResult")]
+pub struct StartTyping {
+	/// User ID
+	pub user_id: i64,
+	/// Channel ID
+	pub channel_id: i64,
+}
+
+/// Outcome of a [`StartTyping`] request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypingResult {
+	/// The indicator was sent to the channel.
+	Started,
+	/// The user started typing too recently, they may try again after the
+	/// given duration.
+	Throttled(Duration),
+	/// The channel does not exist.
+	UnknownChannel,
+	/// The user cannot see the channel, or is not a member of the thread.
+	Forbidden,
+}
+
+/// Change the presence of a user across all of their sessions.
+#[derive(Message)]
+#[rtype(result = "Option<models::Presence>")]
+pub struct UpdatePresence {
+	/// User ID
+	pub user_id: i64,
+	/// New status
+	pub status: Status,
+	/// New custom status
+	pub custom_status: Option<String>,
+}
+
+/// A user has read a channel up to a message, sync it to their sessions.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct AckMessage {
+	/// User ID
+	pub user_id: i64,
+	/// The user's updated read state
+	pub read_state: ReadState,
+}
+
+/// A message was pinned or unpinned in a channel.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UpdatePins {
+	/// Channel ID
+	pub channel_id: i64,
+	/// Message ID
+	pub message_id: i64,
+	/// Whether the message is now pinned
+	pub pinned: bool,
+}
+
 /// List of available channels
 #[derive(Message)]
 #[rtype(result = "Vec<Channel>")]
 pub struct ListChannels;
 
-/// Join channel, if channel does not exists create new one.
+/// Join every session of a user to a channel. Returns None if the channel does
+/// not exist or the user cannot see it.
 #[derive(Message)]
 #[rtype(result = "Option<Channel>")]
 pub struct Join {
-	/// Client ID
-	pub client_id: usize,
+	/// User ID
+	pub user_id: i64,
 	/// Channel ID
 	pub channel_id: i64,
 }
@@ -147,6 +466,19 @@ pub struct ShikiServer {
 	sessions: HashMap<usize, Recipient<Event>>,
 	/// Chat channels. In this case they're individual channels where messages are propagated to users in the same channel. This could be a channel, guild, etc.
 	channels: HashMap<i64, Channel>,
+	/// IDs of the users which authenticated sessions belong to.
+	session_users: HashMap<usize, i64>,
+	/// IDs of the guilds each user with an authenticated session is a member
+	/// of, leaving out those they are banned from.
+	user_guilds: HashMap<i64, HashSet<i64>>,
+	/// Presences of the users with at least one authenticated session.
+	presences: HashMap<i64, Presence>,
+	/// When each user started typing in a channel, keyed by channel and user.
+	typing: HashMap<(i64, i64), Instant>,
+	/// Member lists sessions are subscribed to.
+	member_lists: HashMap<usize, MemberList>,
+	/// Voice states of the users in a voice channel.
+	voice_states: HashMap<i64, events::VoiceStateUpdate>,
 	/// Random generator for making unique IDs.
 	rng: ThreadRng,
 	/// Number of connected clients
@@ -159,6 +491,12 @@ impl ShikiServer {
 			client,
 			sessions: HashMap::new(),
 			channels: HashMap::new(),
+			session_users: HashMap::new(),
+			user_guilds: HashMap::new(),
+			presences: HashMap::new(),
+			typing: HashMap::new(),
+			member_lists: HashMap::new(),
+			voice_states: HashMap::new(),
 			rng: rand::thread_rng(),
 			visitor_count,
 		}
@@ -170,16 +508,44 @@ impl ShikiServer {
 	fn send_channel_message(
 		&self, channel: i64, message: Event, skip_id: usize,
 	) {
-		if let Some(sessions) =
-			self.channels.get(&channel).map(|channel| &channel.sessions)
-		{
+		self.send_channel_message_filtered(channel, message, |id| id != skip_id)
+	}
+
+	/// Send message to all users in the channel except for the sessions of
+	/// `user_id`.
+	fn send_channel_message_from(
+		&self, channel: i64, message: Event, user_id: i64,
+	) {
+		self.send_channel_message_filtered(channel, message, |id| {
+			self.session_users.get(&id) != Some(&user_id)
+		})
+	}
+
+	/// Send message to the sessions in the channel accepted by `filter`. Only
+	/// the members of a thread receive the messages sent to it.
+	fn send_channel_message_filtered(
+		&self, channel: i64, message: Event, filter: impl Fn(usize) -> bool,
+	) {
+		if let Some(channel) = self.channels.get(&channel) {
+			let sessions = channel
+				.sessions
+				.iter()
+				.filter(|id| {
+					channel.kind != ChannelType::Thread
+						|| self.session_users.get(id).is_some_and(|user_id| {
+							channel.member_ids.contains(user_id)
+						})
+				})
+				.collect::<Vec<_>>();
+
 			log::debug!(
-				"Sending message to {} sessions in channel {channel}",
-				sessions.len()
+				"Sending message to {} sessions in channel {}",
+				sessions.len(),
+				channel.id
 			);
 
 			for id in sessions {
-				if *id != skip_id {
+				if filter(*id) {
 					if let Some(addr) = self.sessions.get(id) {
 						addr.do_send(message.clone());
 					}
@@ -188,11 +554,265 @@ impl ShikiServer {
 		}
 	}
 
-	/// Send message to literally everyone.
-	fn send_to_everyone(&self, message: Event, skip_id: usize) {
-		// message every session.
-		for (id, addr) in &self.sessions {
-			if *id != skip_id {
+	/// Send message to the sessions of the members of a guild, and to those of
+	/// `user_id`, the user the event is about, who may just have left it.
+	fn send_guild_message(&self, guild_id: i64, message: Event, user_id: i64) {
+		let sessions = self
+			.session_users
+			.iter()
+			.filter(|(_, id)| {
+				**id == user_id
+					|| self
+						.user_guilds
+						.get(id)
+						.is_some_and(|guilds| guilds.contains(&guild_id))
+			})
+			.map(|(session, _)| *session)
+			.collect::<HashSet<_>>();
+
+		self.send_to_sessions(&sessions, message);
+	}
+
+	/// Whether the user of a session is notified by a message.
+	fn is_mentioned(&self, session_id: usize, msg: &CreateMessage) -> bool {
+		self.session_users.get(&session_id).is_some_and(|id| {
+			msg.mention_everyone || msg.mentioned_users.contains(id)
+		})
+	}
+
+	/// Whether a user receives the events of a channel, which are those outside
+	/// of any guild and those of the guilds they are a member of.
+	fn can_view(&self, user_id: i64, channel: &Channel) -> bool {
+		channel.guild_id.is_none_or(|guild_id| {
+			self.user_guilds
+				.get(&user_id)
+				.is_some_and(|guilds| guilds.contains(&guild_id))
+		})
+	}
+
+	/// IDs of the sessions which belong to a user.
+	fn user_sessions(&self, user_id: i64) -> impl Iterator<Item = usize> + '_ {
+		self.session_users
+			.iter()
+			.filter(move |(_, id)| **id == user_id)
+			.map(|(session, _)| *session)
+	}
+
+	/// Send a presence to every session which shares a channel with the user.
+	/// The user's own sessions see their real status, everyone else sees the
+	/// public one.
+	fn send_presence_update(&self, presence: &Presence) {
+		let own = self.user_sessions(presence.user_id).collect::<HashSet<_>>();
+		let public = Event::PresenceUpdate(presence.public().into());
+		let mut peers = HashSet::new();
+
+		for channel in self.channels.values() {
+			if !channel.sessions.is_disjoint(&own) {
+				peers.extend(channel.sessions.iter().copied());
+			}
+		}
+
+		for (id, list) in &self.member_lists {
+			if list.contains(presence.user_id) {
+				peers.insert(*id);
+			}
+		}
+
+		for id in peers.difference(&own) {
+			if let Some(addr) = self.sessions.get(id) {
+				addr.do_send(public.clone());
+			}
+		}
+
+		for id in &own {
+			if let Some(addr) = self.sessions.get(id) {
+				addr.do_send(Event::PresenceUpdate(presence.clone().into()));
+			}
+		}
+	}
+
+	/// Persists a presence in the background.
+	fn store_presence(&self, presence: Presence, ctx: &mut Context<Self>) {
+		let client = self.client.clone();
+
+		async move {
+			if let Err(e) = client.set_presence(&presence, PRESENCE_TTL).await {
+				log::error!("Failed to store presence: {}", e);
+			}
+		}
+		.into_actor(self)
+		.spawn(ctx);
+	}
+
+	/// Keeps the stored presences of online users from expiring.
+	fn refresh_presences(&self, ctx: &mut Context<Self>) {
+		let client = self.client.clone();
+		let user_ids = self.presences.keys().copied().collect::<Vec<_>>();
+
+		async move {
+			if let Err(e) =
+				client.refresh_presences(&user_ids, PRESENCE_TTL).await
+			{
+				log::error!("Failed to refresh presences: {}", e);
+			}
+		}
+		.into_actor(self)
+		.spawn(ctx);
+	}
+
+	/// Clears typing indicators which have not been refreshed in time.
+	fn expire_typing(&mut self) {
+		let expired = self
+			.typing
+			.iter()
+			.filter(|(_, started)| started.elapsed() >= TYPING_TIMEOUT)
+			.map(|(key, _)| *key)
+			.collect::<Vec<_>>();
+
+		for (channel_id, user_id) in expired {
+			self.typing.remove(&(channel_id, user_id));
+			self.send_channel_message_from(
+				channel_id,
+				Event::TypingStop(events::TypingStop { channel_id, user_id }),
+				user_id,
+			);
+		}
+	}
+
+	/// Archives the threads which have been inactive for too long.
+	fn archive_threads(&mut self, ctx: &mut Context<Self>) {
+		let now = current_utc_timestamp();
+		let mut archived = Vec::new();
+
+		for channel in self.channels.values_mut() {
+			if channel.is_inactive(now) {
+				channel.archived = true;
+				archived.push(channel.id);
+			}
+		}
+
+		if archived.is_empty() {
+			return;
+		}
+
+		log::info!("Archiving {} inactive threads", archived.len());
+
+		for id in &archived {
+			let thread = &self.channels[id];
+
+			self.send_to_sessions(
+				&thread.sessions,
+				Event::ThreadUpdate(events::ThreadUpdate::from(thread)),
+			);
+		}
+
+		let client = self.client.clone();
+
+		async move {
+			if let Err(e) = client.archive_threads(&archived).await {
+				log::error!("Failed to archive threads: {}", e);
+			}
+		}
+		.into_actor(self)
+		.spawn(ctx);
+	}
+
+	/// Adds the sessions of a user to the channels of a guild they joined, so
+	/// they receive its messages.
+	fn join_guild(&mut self, guild_id: i64, user_id: i64) {
+		let sessions = self.user_sessions(user_id).collect::<Vec<_>>();
+
+		if sessions.is_empty() {
+			return;
+		}
+
+		self.user_guilds.entry(user_id).or_default().insert(guild_id);
+
+		for channel in self.channels.values_mut() {
+			if channel.guild_id == Some(guild_id) {
+				channel.sessions.extend(&sessions);
+			}
+		}
+	}
+
+	/// Removes the sessions of a user from the channels of a guild, so they no
+	/// longer receive its messages.
+	fn leave_guild(&mut self, guild_id: i64, user_id: i64) {
+		let sessions = self.user_sessions(user_id).collect::<Vec<_>>();
+
+		if let Some(guilds) = self.user_guilds.get_mut(&user_id) {
+			guilds.remove(&guild_id);
+		}
+
+		for channel in self.channels.values_mut() {
+			if channel.guild_id == Some(guild_id) {
+				for id in &sessions {
+					channel.sessions.remove(id);
+				}
+			}
+		}
+	}
+
+	/// Adds a user to, or removes them from, the member lists of a guild.
+	fn update_member_lists(&mut self, guild_id: i64, user_id: i64, add: bool) {
+		for list in self.member_lists.values_mut() {
+			if list.guild_id != Some(guild_id) {
+				continue;
+			}
+
+			if let Some(ids) = list.user_ids.as_mut() {
+				if add {
+					ids.insert(user_id);
+				} else {
+					ids.remove(&user_id);
+				}
+			}
+		}
+	}
+
+	/// Sends a voice state to the sessions in its channel and in the channel
+	/// the user was in before.
+	fn send_voice_state(
+		&self, state: &events::VoiceStateUpdate, previous: Option<i64>,
+	) {
+		let mut sessions = HashSet::new();
+
+		for channel_id in state.channel_id.iter().chain(previous.iter()) {
+			if let Some(channel) = self.channels.get(channel_id) {
+				sessions.extend(channel.sessions.iter().copied());
+			}
+		}
+
+		for id in sessions {
+			if let Some(addr) = self.sessions.get(&id) {
+				addr.do_send(Event::VoiceStateUpdate(state.clone()));
+			}
+		}
+	}
+
+	fn leave_voice(&mut self, user_id: i64) {
+		if let Some(state) = self.voice_states.remove(&user_id) {
+			self.send_voice_state(
+				&events::VoiceStateUpdate { channel_id: None, ..state },
+				state.channel_id,
+			);
+		}
+	}
+
+	/// Public presences of the users who are online, by user ID.
+	fn online_statuses(&self) -> HashMap<i64, Status> {
+		self.presences
+			.values()
+			.map(Presence::public)
+			.filter(|p| p.status != Status::Offline)
+			.map(|p| (p.user_id, p.status))
+			.collect()
+	}
+
+	/// Send message to the given sessions.
+	fn send_to_sessions(&self, sessions: &HashSet<usize>, message: Event) {
+		for id in sessions {
+			if let Some(addr) = self.sessions.get(id) {
 				addr.do_send(message.clone());
 			}
 		}
@@ -206,6 +826,14 @@ impl Actor for ShikiServer {
 	type Context = Context<Self>;
 
 	fn started(&mut self, ctx: &mut Self::Context) {
+		ctx.run_interval(TYPING_SWEEP_INTERVAL, |act, _| act.expire_typing());
+		ctx.run_interval(PRESENCE_HEARTBEAT_INTERVAL, |act, ctx| {
+			act.refresh_presences(ctx)
+		});
+		ctx.run_interval(THREAD_ARCHIVE_INTERVAL, |act, ctx| {
+			act.archive_threads(ctx)
+		});
+
 		let client_clone = self.client.clone();
 
 		async move {
@@ -252,13 +880,13 @@ impl Handler<Connect> for ShikiServer {
 		let id = self.rng.gen::<usize>();
 		self.sessions.insert(id, msg.addr.clone());
 
-		// Insert the user into every single channel's sessions.
-		for channel in self.channels.values_mut() {
-			channel.sessions.insert(id);
-		}
+		// The session only joins channels once it identifies, as which
+		// channels it may see depends on the guilds of its user.
 
-		// Send an Identify event to the client so they may authenticate themselves.
-		msg.addr.do_send(Event::Hello);
+		// Send a Hello event to the client so they may authenticate themselves.
+		msg.addr.do_send(Event::Hello(events::Hello {
+			heartbeat_interval: HEARTBEAT_INTERVAL.as_millis() as u64,
+		}));
 
 		let count = self.visitor_count.fetch_add(1, Ordering::SeqCst);
 
@@ -271,7 +899,7 @@ impl Handler<Connect> for ShikiServer {
 impl Handler<Disconnect> for ShikiServer {
 	type Result = ();
 
-	fn handle(&mut self, msg: Disconnect, _: &mut Context<Self>) {
+	fn handle(&mut self, msg: Disconnect, ctx: &mut Context<Self>) {
 		log::info!("{} disconnected", msg.id);
 
 		let count = self.visitor_count.fetch_update(
@@ -290,22 +918,43 @@ impl Handler<Disconnect> for ShikiServer {
 			log::info!("{} visitors online", updated_count);
 		}
 
-		let mut channels: Vec<i64> = Vec::new();
+		// The user goes offline once their last session is gone. Peers are
+		// notified before the session leaves its channels.
+		if let Some(&user_id) = self.session_users.get(&msg.id) {
+			if self.user_sessions(user_id).count() == 1 {
+				if let Some(presence) = self.presences.remove(&user_id) {
+					self.send_presence_update(&Presence {
+						status: Status::Offline,
+						..presence
+					});
+				}
 
-		if self.sessions.remove(&msg.id).is_some() {
-			for channel in self.channels.values_mut() {
-				if channel.sessions.remove(&msg.id) {
-					channels.push(channel.id);
+				// Users leave their voice channel with their last session.
+				if self.voice_states.contains_key(&user_id) {
+					self.leave_voice(user_id);
 				}
+
+				self.user_guilds.remove(&user_id);
+
+				let client = self.client.clone();
+
+				async move {
+					if let Err(e) = client.remove_presence(user_id).await {
+						log::error!("Failed to remove presence: {}", e);
+					}
+				}
+				.into_actor(self)
+				.spawn(ctx);
 			}
 		}
 
-		for channel in channels {
-			self.send_channel_message(
-				channel,
-				Event::Custom(format!("{} left", msg.id)),
-				0,
-			);
+		self.session_users.remove(&msg.id);
+		self.member_lists.remove(&msg.id);
+
+		if self.sessions.remove(&msg.id).is_some() {
+			for channel in self.channels.values_mut() {
+				channel.sessions.remove(&msg.id);
+			}
 		}
 	}
 }
@@ -322,6 +971,7 @@ impl Handler<Identify> for ShikiServer {
 
 		let channels = self.channels.clone();
 		let client_clone = self.client.clone();
+		let presences = self.presences.clone();
 
 		async move {
 			let res =
@@ -332,39 +982,105 @@ impl Handler<Identify> for ShikiServer {
 				Ok(Some(user)) => user,
 				Ok(None) => {
 					log::warn!("Invalid token");
-					return session.do_send(Event::BadToken);
+					session
+						.do_send(Event::Close(CloseCode::AuthenticationFailed));
+					return None;
 				}
 				Err(e) => {
 					log::error!("Failed to validate token: {}", e);
 					log::debug!(
 						"Disconnecting session for failed token validation"
 					);
-					return session.do_send(Event::BadToken);
+					session.do_send(Event::Close(CloseCode::UnknownError));
+					return None;
 				}
 			};
 
-			session.do_send(Event::SetToken(msg.token));
+			// Bots need to be allowed to receive every member of a guild.
+			if user.bot
+				&& !user.privileged_intents
+				&& msg.intents.intersects(Intents::PRIVILEGED)
+			{
+				log::warn!("Bot {} sent disallowed intents", user.id);
+				session.do_send(Event::Close(CloseCode::DisallowedIntents));
+				return None;
+			}
+
+			session.do_send(Event::Authenticated(events::Authenticated {
+				token: msg.token,
+				user_id: user.id,
+			}));
 
 			log::info!(
 				"User {} authenticated, sending Ready payload...",
 				user.username
 			);
 
+			// Only a few online users are sent up front, clients request the
+			// rest of the member list when they need it.
+			let online = presences
+				.values()
+				.filter(|p| p.user_id != user.id)
+				.map(Presence::public)
+				.filter(|p| p.status != Status::Offline)
+				.take(READY_MEMBER_LIMIT)
+				.collect::<Vec<_>>();
+			let user_ids = online
+				.iter()
+				.map(|p| p.user_id)
+				.chain(std::iter::once(user.id))
+				.collect::<Vec<_>>();
 			let users = client_clone
-				.fetch_users(None)
+				.fetch_users(Some(&user_ids))
 				.await
 				.unwrap_or(Vec::new())
 				.into_iter()
-				.map(|u| User {
-					username: u.username,
-					id: u.id,
-					avatar: u.avatar,
-					joined: u.created_at,
-				})
+				.map(User::from)
 				.collect();
 
+			let guilds = match client_clone.fetch_user_guilds(user.id).await {
+				Ok(guilds) => guilds,
+				Err(e) => {
+					log::error!("Failed to fetch guilds: {}", e);
+					session.do_send(Event::Close(CloseCode::UnknownError));
+					return None;
+				}
+			};
+			// Users never see the channels of guilds they are banned from.
+			let banned = match client_clone.fetch_user_bans(user.id).await {
+				Ok(banned) => banned.into_iter().collect::<HashSet<_>>(),
+				Err(e) => {
+					log::error!("Failed to fetch bans: {}", e);
+					session.do_send(Event::Close(CloseCode::UnknownError));
+					return None;
+				}
+			};
+			let guild_ids = guilds
+				.iter()
+				.map(|g| g.id)
+				.filter(|id| !banned.contains(id))
+				.collect::<HashSet<_>>();
+			let channels = channels
+				.into_iter()
+				.filter(|(_, c)| {
+					c.guild_id.is_none_or(|id| guild_ids.contains(&id))
+				})
+				.collect::<HashMap<_, _>>();
+
+			let user_id = user.id;
+			let read_states =
+				fetch_read_states(&client_clone, &user, &channels).await;
+			let presence = presences
+				.get(&user_id)
+				.cloned()
+				.unwrap_or_else(|| Presence::new(user_id, Status::Online));
+			let presences =
+				online.into_iter().chain(std::iter::once(presence)).collect();
+
 			session.do_send(Event::Ready(Ready {
-				channels: channels.values().cloned().collect(),
+				session_id: msg.id,
+				channels: channels.values().map(Into::into).collect(),
+				guilds: guilds.into_iter().map(Guild::from).collect(),
 				user: User {
 					username: user.username,
 					id: user.id,
@@ -372,10 +1088,40 @@ impl Handler<Identify> for ShikiServer {
 					joined: user.created_at,
 				},
 				users,
+				presences,
+				read_states,
 			}));
+
+			Some((user_id, guild_ids))
 		}
 		.into_actor(self)
-		.then(|_, _, _| fut::ready(()))
+		.then(move |res, act, ctx| {
+			if let Some((user_id, guild_ids)) = res {
+				act.session_users.insert(msg.id, user_id);
+
+				// The session receives the messages of every channel it may see.
+				for channel in act.channels.values_mut() {
+					if channel.guild_id.is_none_or(|id| guild_ids.contains(&id))
+					{
+						channel.sessions.insert(msg.id);
+					}
+				}
+
+				act.user_guilds.insert(user_id, guild_ids);
+
+				// The first session of a user brings them online.
+				if let Entry::Vacant(entry) = act.presences.entry(user_id) {
+					let presence = entry
+						.insert(Presence::new(user_id, Status::Online))
+						.clone();
+
+					act.send_presence_update(&presence);
+					act.store_presence(presence, ctx);
+				}
+			}
+
+			fut::ready(())
+		})
 		.wait(ctx);
 	}
 }
@@ -392,18 +1138,119 @@ impl Handler<Channel> for ShikiServer {
 			return MessageResult(None);
 		}
 
-		msg.sessions = self.sessions.keys().cloned().collect();
+		msg.sessions = self
+			.session_users
+			.iter()
+			.filter(|(_, user_id)| self.can_view(**user_id, &msg))
+			.map(|(id, _)| *id)
+			.collect();
 		self.channels.insert(msg.id, msg.clone());
 
-		self.send_to_everyone(
-			Event::ChannelCreate(events::ChannelCreate::new(
-				msg.id,
-				msg.name.clone(),
+		let event = if msg.kind == ChannelType::Thread {
+			Event::ThreadCreate(events::ThreadCreate::from(&msg))
+		} else {
+			Event::ChannelCreate(events::ChannelCreate::from(&msg))
+		};
+
+		self.send_to_sessions(&msg.sessions, event);
+
+		MessageResult(Some(msg))
+	}
+}
+
+impl Handler<UpdateChannel> for ShikiServer {
+	type Result = MessageResult<UpdateChannel>;
+
+	fn handle(
+		&mut self, msg: UpdateChannel, _: &mut Context<Self>,
+	) -> Self::Result {
+		let UpdateChannel { mut channel } = msg;
+
+		match self.channels.get_mut(&channel.id) {
+			Some(existing) => {
+				channel.sessions = std::mem::take(&mut existing.sessions);
+				*existing = channel.clone();
+			}
+			None => return MessageResult(None),
+		}
+
+		log::info!("Channel {} updated", channel.id);
+
+		let event = if channel.kind == ChannelType::Thread {
+			Event::ThreadUpdate(events::ThreadUpdate::from(&channel))
+		} else {
+			Event::ChannelUpdate(events::ChannelUpdate::from(&channel))
+		};
+
+		self.send_to_sessions(&channel.sessions, event);
+
+		MessageResult(Some(channel))
+	}
+}
+
+impl Handler<DeleteChannel> for ShikiServer {
+	type Result = MessageResult<DeleteChannel>;
+
+	fn handle(
+		&mut self, msg: DeleteChannel, _: &mut Context<Self>,
+	) -> Self::Result {
+		let channel = match self.channels.remove(&msg.id) {
+			Some(channel) => channel,
+			None => return MessageResult(None),
+		};
+
+		log::info!("Channel {} deleted", channel.id);
+
+		// Threads are deleted along with their channel.
+		let threads = self
+			.channels
+			.values()
+			.filter(|c| {
+				c.kind == ChannelType::Thread && c.parent_id == Some(channel.id)
+			})
+			.map(|c| c.id)
+			.collect::<Vec<_>>();
+
+		for id in threads {
+			if let Some(thread) = self.channels.remove(&id) {
+				self.send_to_sessions(
+					&thread.sessions,
+					Event::ChannelDelete(events::ChannelDelete::new(
+						id,
+						Some(channel.id),
+					)),
+				);
+			}
+		}
+
+		// Channels inside of a deleted category are moved out of it.
+		let mut orphans = Vec::new();
+
+		for child in self.channels.values_mut() {
+			if child.parent_id == Some(channel.id) {
+				child.parent_id = None;
+				orphans.push(child.id);
+			}
+		}
+
+		for id in orphans {
+			let orphan = &self.channels[&id];
+
+			self.send_to_sessions(
+				&orphan.sessions,
+				Event::ChannelUpdate(events::ChannelUpdate::from(orphan)),
+			);
+		}
+
+		self.send_to_sessions(
+			&channel.sessions,
+			Event::ChannelDelete(events::ChannelDelete::new(
+				channel.id,
+				channel.parent_id,
 			)),
-			0,
 		);
 
-		MessageResult(Some(msg))
+		MessageResult(Some(channel))
 	}
 }
 
@@ -415,19 +1262,218 @@ impl Handler<CreateMessage> for ShikiServer {
 	) -> Self::Result {
 		log::info!("Create message request: {:?}", msg);
 
-		if !self.channels.contains_key(&msg.channel_id) {
-			return MessageResult(None);
+		match self.channels.get_mut(&msg.channel_id) {
+			Some(channel) => {
+				if channel.kind == ChannelType::Thread {
+					channel.last_activity_at = msg.created_at;
+				}
+			}
+			None => return MessageResult(None),
 		}
 
+		// Sending a message ends the author's typing indicator.
+		self.typing.remove(&(msg.channel_id, msg.author.id));
+
 		let event = events::MessageCreate::from(msg.clone());
 
+		// Mentioned users receive their own copy of the event, flagged so
+		// clients can notify them.
+		self.send_channel_message_filtered(
+			msg.channel_id,
+			Event::MessageCreate(event.clone()),
+			|id| !self.is_mentioned(id, &msg),
+		);
+		self.send_channel_message_filtered(
+			msg.channel_id,
+			Event::MessageCreate(events::MessageCreate {
+				mentioned: true,
+				..event
+			}),
+			|id| self.is_mentioned(id, &msg),
+		);
+
+		MessageResult(Some(msg))
+	}
+}
+
+impl Handler<StartTyping> for ShikiServer {
+	type Result = MessageResult<StartTyping>;
+
+	fn handle(
+		&mut self, msg: StartTyping, _: &mut Context<Self>,
+	) -> Self::Result {
+		let StartTyping { user_id, channel_id } = msg;
+
+		let channel = match self.channels.get(&channel_id) {
+			Some(channel) => channel,
+			None => return MessageResult(TypingResult::UnknownChannel),
+		};
+
+		// Only the members of a thread see who is typing in it.
+		if !self.can_view(user_id, channel)
+			|| (channel.kind == ChannelType::Thread
+				&& !channel.member_ids.contains(&user_id))
+		{
+			return MessageResult(TypingResult::Forbidden);
+		}
+
+		if let Some(started) = self.typing.get(&(channel_id, user_id)) {
+			let elapsed = started.elapsed();
+
+			if elapsed < TYPING_THROTTLE {
+				return MessageResult(TypingResult::Throttled(
+					TYPING_THROTTLE - elapsed,
+				));
+			}
+		}
+
+		self.typing.insert((channel_id, user_id), Instant::now());
+		self.send_channel_message_from(
+			channel_id,
+			Event::TypingStart(events::TypingStart {
+				channel_id,
+				user_id,
+				timestamp: current_utc_timestamp(),
+			}),
+			user_id,
+		);
+
+		MessageResult(TypingResult::Started)
+	}
+}
+
+impl Handler<UpdatePresence> for ShikiServer {
+	type Result = Option<Presence>;
+
+	fn handle(
+		&mut self, msg: UpdatePresence, ctx: &mut Context<Self>,
+	) -> Self::Result {
+		let presence = self.presences.get_mut(&msg.user_id)?;
+
+		presence.status = msg.status;
+		presence.custom_status = msg.custom_status;
+
+		let presence = presence.clone();
+
+		self.send_presence_update(&presence);
+		self.store_presence(presence.clone(), ctx);
+
+		Some(presence)
+	}
+}
+
+impl Handler<AckMessage> for ShikiServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: AckMessage, _: &mut Context<Self>) {
+		let event = Event::MessageAck(msg.read_state.into());
+
+		for id in self.user_sessions(msg.user_id) {
+			if let Some(addr) = self.sessions.get(&id) {
+				addr.do_send(event.clone());
+			}
+		}
+	}
+}
+
+impl Handler<UpdatePins> for ShikiServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: UpdatePins, _: &mut Context<Self>) {
 		self.send_channel_message(
 			msg.channel_id,
-			Event::MessageCreate(event),
+			Event::ChannelPinsUpdate(events::ChannelPinsUpdate {
+				channel_id: msg.channel_id,
+				message_id: msg.message_id,
+				pinned: msg.pinned,
+			}),
 			0,
 		);
+	}
+}
 
-		MessageResult(Some(msg))
+impl Handler<AddMember> for ShikiServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: AddMember, _: &mut Context<Self>) {
+		let user_id = msg.user.id;
+
+		self.join_guild(msg.guild_id, user_id);
+		self.update_member_lists(msg.guild_id, user_id, true);
+		self.send_guild_message(
+			msg.guild_id,
+			Event::GuildMemberAdd(events::GuildMemberAdd {
+				guild_id: msg.guild_id,
+				user: msg.user,
+				joined_at: msg.joined_at,
+			}),
+			user_id,
+		);
+	}
+}
+
+impl Handler<RemoveMember> for ShikiServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: RemoveMember, _: &mut Context<Self>) {
+		self.leave_guild(msg.guild_id, msg.user_id);
+		self.update_member_lists(msg.guild_id, msg.user_id, false);
+		self.send_guild_message(
+			msg.guild_id,
+			Event::GuildMemberRemove(events::GuildMemberRemove {
+				guild_id: msg.guild_id,
+				user_id: msg.user_id,
+			}),
+			msg.user_id,
+		);
+	}
+}
+
+impl Handler<BanMember> for ShikiServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: BanMember, _: &mut Context<Self>) {
+		self.leave_guild(msg.guild_id, msg.user_id);
+		self.update_member_lists(msg.guild_id, msg.user_id, false);
+		self.send_guild_message(
+			msg.guild_id,
+			Event::GuildBanAdd(events::GuildBanAdd {
+				guild_id: msg.guild_id,
+				user_id: msg.user_id,
+			}),
+			msg.user_id,
+		);
+	}
+}
+
+impl Handler<UnbanMember> for ShikiServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: UnbanMember, _: &mut Context<Self>) {
+		self.send_guild_message(
+			msg.guild_id,
+			Event::GuildBanRemove(events::GuildBanRemove {
+				guild_id: msg.guild_id,
+				user_id: msg.user_id,
+			}),
+			msg.user_id,
+		);
+	}
+}
+
+impl Handler<TimeoutMember> for ShikiServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: TimeoutMember, _: &mut Context<Self>) {
+		self.send_guild_message(
+			msg.guild_id,
+			Event::GuildMemberUpdate(events::GuildMemberUpdate {
+				guild_id: msg.guild_id,
+				user_id: msg.user_id,
+				communication_disabled_until: msg.communication_disabled_until,
+			}),
+			msg.user_id,
+		);
 	}
 }
 
@@ -445,26 +1491,268 @@ impl Handler<Join> for ShikiServer {
 	type Result = MessageResult<Join>;
 
 	fn handle(&mut self, msg: Join, _: &mut Context<Self>) -> Self::Result {
-		let Join { client_id, channel_id } = msg;
+		let Join { user_id, channel_id } = msg;
+		let sessions = self.user_sessions(user_id).collect::<Vec<_>>();
+
+		// Sessions only join the channels of the guilds their user is a member
+		// of.
+		match self.channels.get(&channel_id) {
+			Some(channel) if self.can_view(user_id, channel) => {}
+			_ => return MessageResult(None),
+		}
 
-		match self.channels.get_mut(&channel_id) {
-			Some(channel) => {
-				channel.sessions.insert(client_id);
-			}
+		let channel = self.channels.get_mut(&channel_id).unwrap();
+		channel.sessions.extend(sessions);
+
+		MessageResult(Some(channel.clone()))
+	}
+}
+
+/// IDs of the members of a guild if the user is one of them, or None for
+/// every user when there is no guild.
+async fn fetch_permitted_members(
+	client: &RedisFetcher, guild_id: Option<i64>, user_id: i64,
+) -> Result<Option<Vec<i64>>, ()> {
+	let guild_id = match guild_id {
+		Some(guild_id) => guild_id,
+		None => return Ok(None),
+	};
+
+	match client.fetch_member_ids(guild_id).await {
+		Ok(ids) if ids.contains(&user_id) => Ok(Some(ids)),
+		Ok(_) => Err(()),
+		Err(e) => {
+			log::error!("Failed to fetch members: {}", e);
+			Err(())
+		}
+	}
+}
+
+impl Handler<RequestGuildMembers> for ShikiServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: RequestGuildMembers, ctx: &mut Context<Self>) {
+		let RequestGuildMembers { id, request: msg } = msg;
+		let user_id = match self.session_users.get(&id) {
+			Some(&user_id) => user_id,
+			None => return,
+		};
+		let client = self.client.clone();
+
+		async move {
+			let members =
+				fetch_permitted_members(&client, msg.guild_id, user_id)
+					.await
+					.ok()?;
+			let ids = match (members, &msg.user_ids) {
+				(Some(members), Some(ids)) => Some(
+					ids.iter()
+						.filter(|id| members.contains(id))
+						.copied()
+						.collect::<Vec<_>>(),
+				),
+				(members, ids) => members.or_else(|| ids.clone()),
+			};
 
-			None => {
-				return MessageResult(None);
+			match client
+				.search_users(
+					ids.as_deref(),
+					msg.query.as_deref(),
+					msg.after,
+					msg.limit,
+				)
+				.await
+			{
+				Ok(users) => Some((msg, users)),
+				Err(e) => {
+					log::error!("Failed to fetch members: {}", e);
+					None
+				}
 			}
 		}
+		.into_actor(self)
+		.then(move |res, act, _| {
+			let (msg, users) = match res {
+				Some(res) => res,
+				None => return fut::ready(()),
+			};
+			let session = match act.sessions.get(&id) {
+				Some(session) => session,
+				None => return fut::ready(()),
+			};
+			let statuses = act.online_statuses();
+			let not_found = msg
+				.user_ids
+				.iter()
+				.flatten()
+				.filter(|id| !users.iter().any(|user| user.id == **id))
+				.copied()
+				.collect::<Vec<_>>();
+			let chunk_count =
+				users.len().div_ceil(GUILD_MEMBERS_CHUNK_SIZE).max(1);
+
+			for chunk_index in 0..chunk_count {
+				let members = users
+					.iter()
+					.skip(chunk_index * GUILD_MEMBERS_CHUNK_SIZE)
+					.take(GUILD_MEMBERS_CHUNK_SIZE)
+					.cloned()
+					.map(User::from)
+					.collect::<Vec<_>>();
+				let presences = if msg.presences {
+					members
+						.iter()
+						.filter_map(|user| {
+							statuses
+								.get(&user.id)
+								.map(|&status| Presence::new(user.id, status))
+						})
+						.collect()
+				} else {
+					Vec::new()
+				};
+
+				session.do_send(Event::GuildMembersChunk(
+					events::GuildMembersChunk {
+						guild_id: msg.guild_id,
+						members,
+						presences,
+						chunk_index,
+						chunk_count,
+						not_found: if chunk_index == 0 {
+							not_found.clone()
+						} else {
+							Vec::new()
+						},
+						nonce: msg.nonce.clone(),
+					},
+				));
+			}
 
-		self.send_channel_message(
-			channel_id,
-			Event::Custom("Someone connected".to_owned()),
-			client_id,
-		);
+			fut::ready(())
+		})
+		.spawn(ctx);
+	}
+}
 
-		let channel = self.channels.get(&channel_id).unwrap().clone();
+impl Handler<SubscribeMemberList> for ShikiServer {
+	type Result = ();
 
-		MessageResult(Some(channel))
+	fn handle(&mut self, msg: SubscribeMemberList, ctx: &mut Context<Self>) {
+		let user_id = match self.session_users.get(&msg.id) {
+			Some(&user_id) => user_id,
+			None => return,
+		};
+		let client = self.client.clone();
+		let statuses = self.online_statuses();
+
+		async move {
+			let members =
+				fetch_permitted_members(&client, msg.guild_id, user_id)
+					.await
+					.ok()?;
+			let member_count = match &members {
+				Some(members) => members.len(),
+				None => client.count_users().await.ok()? as usize,
+			};
+
+			// Online members come first, ordered by username, followed by
+			// as many offline members as fit in the list.
+			let online_ids = statuses
+				.keys()
+				.filter(|id| members.as_ref().is_none_or(|m| m.contains(id)))
+				.copied()
+				.collect::<Vec<_>>();
+			let mut online =
+				client.fetch_users(Some(&online_ids)).await.ok()?;
+			online.sort_by(|a, b| a.username.cmp(&b.username));
+			online.truncate(MEMBER_LIST_LIMIT);
+
+			let mut offline = client
+				.search_users(
+					members.as_deref(),
+					None,
+					0,
+					(MEMBER_LIST_LIMIT + online_ids.len()) as i64,
+				)
+				.await
+				.ok()?;
+			offline.retain(|user| !statuses.contains_key(&user.id));
+			offline.sort_by(|a, b| a.username.cmp(&b.username));
+			offline.truncate(MEMBER_LIST_LIMIT - online.len());
+
+			let items = online
+				.into_iter()
+				.chain(offline)
+				.map(|user| events::MemberListItem {
+					status: statuses
+						.get(&user.id)
+						.copied()
+						.unwrap_or(Status::Offline),
+					user: user.into(),
+				})
+				.collect();
+
+			let guild_id = msg.guild_id;
+
+			Some((
+				msg,
+				members,
+				events::MemberListUpdate {
+					guild_id,
+					member_count,
+					online_count: online_ids.len(),
+					members: items,
+				},
+			))
+		}
+		.into_actor(self)
+		.then(|res, act, _| {
+			if let Some((msg, members, update)) = res {
+				if let Some(session) = act.sessions.get(&msg.id) {
+					session.do_send(Event::MemberListUpdate(update));
+					act.member_lists.insert(
+						msg.id,
+						MemberList {
+							guild_id: msg.guild_id,
+							user_ids: members
+								.map(|ids| ids.into_iter().collect()),
+						},
+					);
+				}
+			}
+
+			fut::ready(())
+		})
+		.spawn(ctx);
+	}
+}
+
+impl Handler<UpdateVoiceState> for ShikiServer {
+	type Result = ();
+
+	fn handle(&mut self, msg: UpdateVoiceState, _: &mut Context<Self>) {
+		let channel_id = match msg.channel_id {
+			Some(channel_id) => channel_id,
+			None => return self.leave_voice(msg.user_id),
+		};
+
+		if !self.channels.contains_key(&channel_id) {
+			return;
+		}
+
+		let state = events::VoiceStateUpdate {
+			user_id: msg.user_id,
+			channel_id: Some(channel_id),
+			self_mute: msg.self_mute,
+			self_deaf: msg.self_deaf,
+		};
+		let previous = self
+			.voice_states
+			.insert(msg.user_id, state.clone())
+			.and_then(|previous| previous.channel_id)
+			.filter(|&previous| previous != channel_id);
+
+		self.send_voice_state(&state, previous);
 	}
 }
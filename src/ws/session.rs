@@ -1,14 +1,25 @@
-use crate::ws::{
-	events::{Event, Opcode},
-	server,
+use crate::{
+	models::Status,
+	version::ApiVersion,
+	ws::{
+		close_code::{self, CloseCode},
+		commands::GatewayCommand,
+		compression::{Compression, CompressionStats, Compressor},
+		encoding::Encoding,
+		events::{Event, HeartbeatAck, InvalidSession},
+		intents::{AllowsEvent, Intents},
+		server,
+	},
 };
 use actix::prelude::*;
 use actix_web_actors::ws::{self, Message};
 use anyhow::Result;
 use std::time::{Duration, Instant};
+use validator::Validate;
 
-/// How often heartbeat pings are sent
-const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// How often heartbeat pings are sent, and how often clients are asked to send
+/// a Heartbeat
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 
 /// How long to wait before lack of client response causes a timeout
 const AUTH_TIMEOUT: Duration = Duration::from_secs(30);
@@ -16,6 +27,18 @@ const AUTH_TIMEOUT: Duration = Duration::from_secs(30);
 /// How long before lack of client response causes a timeout
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// Largest WebSocket frame accepted from a client, in bytes
+pub const MAX_FRAME_SIZE: usize = 16 * 1024;
+
+/// Largest JSON payload accepted from a client, in bytes
+const MAX_PAYLOAD_SIZE: usize = 4096;
+
+/// How many commands a client can send per COMMAND_PERIOD
+const COMMAND_LIMIT: u32 = 120;
+
+/// Window in which COMMAND_LIMIT commands can be sent
+const COMMAND_PERIOD: Duration = Duration::from_secs(60);
+
 #[derive(Debug)]
 pub struct GatewaySession {
 	/// unique session id
@@ -33,6 +56,22 @@ pub struct GatewaySession {
 	pub addr: Addr<server::ShikiServer>,
 	/// Auth token
 	pub token: Option<String>,
+	/// Commands received in the current COMMAND_PERIOD
+	pub commands: u32,
+	/// When the current COMMAND_PERIOD started
+	pub commands_since: Instant,
+	/// Sequence number of the last event dispatched to the client
+	pub seq: u64,
+	/// Events the client subscribed to
+	pub intents: Intents,
+	/// Encoding of the payloads sent and received
+	pub encoding: Encoding,
+	/// Version of the gateway, which selects the shape of the payloads
+	pub version: ApiVersion,
+	/// Compresses payloads if the client asked for transport compression
+	pub compressor: Option<Compressor>,
+	/// How well compression worked so far
+	pub compression_stats: CompressionStats,
 }
 
 impl GatewaySession {
@@ -50,7 +89,7 @@ impl GatewaySession {
 				// notify chat server
 				act.addr.do_send(server::Disconnect { id: act.session_id });
 				// stop actor
-				ctx.stop();
+				act.close(ctx, CloseCode::SessionTimedOut);
 
 				// don't try to send a ping
 				return;
@@ -67,37 +106,216 @@ impl GatewaySession {
 					"Websocket Client authentication failed, disconnecting!"
 				);
 				act.addr.do_send(server::Disconnect { id: act.session_id });
-				ctx.close(Some(ws::CloseReason {
-					code: ws::CloseCode::Other(4000),
-					description: None,
-				}));
+				act.close(ctx, CloseCode::SessionTimedOut);
 			}
 		});
 	}
 
-	fn handle_json(
-		&self, text: &str, _ctx: &mut ws::WebsocketContext<Self>,
+	fn close(&self, ctx: &mut ws::WebsocketContext<Self>, code: CloseCode) {
+		log::debug!("Closing session {} with {:?}", self.session_id, code);
+
+		ctx.close(Some(close_code::close_reason(code)));
+		ctx.stop();
+	}
+
+	/// Counts a command against the session's budget, returning whether the
+	/// client is still within COMMAND_LIMIT.
+	fn take_command(&mut self) -> bool {
+		let now = Instant::now();
+
+		if now.duration_since(self.commands_since) > COMMAND_PERIOD {
+			self.commands = 0;
+			self.commands_since = now;
+		}
+
+		self.commands += 1;
+		self.commands <= COMMAND_LIMIT
+	}
+
+	fn handle_payload(
+		&mut self, data: &[u8], binary: bool,
+		ctx: &mut ws::WebsocketContext<Self>,
+	) {
+		if !self.take_command() {
+			return self.close(ctx, CloseCode::RateLimited);
+		}
+
+		if data.len() > MAX_PAYLOAD_SIZE {
+			return self.close(ctx, CloseCode::PayloadTooLarge);
+		}
+
+		// Payloads have to arrive in the frame type of the session's encoding.
+		if binary != self.encoding.is_binary() {
+			return self.close(ctx, CloseCode::DecodeError);
+		}
+
+		let json = match self.encoding.decode(data) {
+			Ok(json) => json,
+			Err(_) => return self.close(ctx, CloseCode::DecodeError),
+		};
+
+		// Only commands can be sent by clients, everything else is
+		// dispatched by the server.
+		let code = json.get("op").and_then(|op| op.as_u64());
+
+		if !code.is_some_and(GatewayCommand::accepts) {
+			return self.close(ctx, CloseCode::UnknownOpcode);
+		}
+
+		let command = match serde_json::from_value::<GatewayCommand>(json) {
+			Ok(command) => command,
+			Err(e) => {
+				log::debug!("Failed to decode payload: {}", e);
+				return self.close(ctx, CloseCode::DecodeError);
+			}
+		};
+
+		if let Err(e) = command.validate() {
+			log::debug!("Invalid {:?} payload: {}", command.opcode(), e);
+			return self.close(ctx, CloseCode::DecodeError);
+		}
+
+		if self.token.is_none() && !command.allowed_before_identify() {
+			return self.close(ctx, CloseCode::NotAuthenticated);
+		}
+
+		if let Err(e) = self.handle_command(command, ctx) {
+			log::debug!("Failed to handle payload: {}", e);
+			self.close(ctx, CloseCode::DecodeError);
+		}
+	}
+
+	fn handle_command(
+		&mut self, command: GatewayCommand,
+		ctx: &mut ws::WebsocketContext<Self>,
 	) -> Result<()> {
-		let json = serde_json::from_str::<serde_json::Value>(text)?;
-		let opcode = Opcode::from_json(json.get("op"))
-			.ok_or(anyhow::anyhow!("no opcode"))?;
-		let data = json.get("d").ok_or(anyhow::anyhow!("no data"))?;
-
-		log::debug!("Received opcode: {:?}", opcode);
-		log::debug!("Received data: {:?}", data);
-
-		if opcode == Opcode::Identify {
-			let token = data
-				.get("token")
-				.and_then(|t| t.as_str())
-				.ok_or(anyhow::anyhow!("no token"))?
-				.to_string();
-
-			self.addr.do_send(server::Identify { id: self.session_id, token });
+		log::debug!("Received command: {:?}", command);
+
+		match command {
+			GatewayCommand::Identify(_) if self.token.is_some() => {
+				self.close(ctx, CloseCode::AlreadyAuthenticated);
+			}
+			GatewayCommand::Identify(identify) => {
+				if !identify.intents.is_valid() {
+					self.close(ctx, CloseCode::InvalidIntents);
+					return Ok(());
+				}
+
+				// Transport compression asked for when connecting wins.
+				if identify.compress && self.compressor.is_none() {
+					self.compressor =
+						Some(Compressor::new(Compression::ZlibStream)?);
+				}
+
+				log::debug!(
+					"Session {} identifies with {:?}",
+					self.session_id,
+					identify.properties
+				);
+
+				self.intents = identify.intents;
+				self.addr.do_send(server::Identify {
+					id: self.session_id,
+					token: identify.token,
+					intents: identify.intents,
+				});
+			}
+			GatewayCommand::Heartbeat(seq) => {
+				if seq.is_some_and(|seq| seq > self.seq) {
+					self.close(ctx, CloseCode::InvalidSeq);
+					return Ok(());
+				}
+
+				self.hb = Instant::now();
+				self.send(ctx, &Event::HeartbeatAck(HeartbeatAck), None);
+			}
+			GatewayCommand::Resume(resume) => {
+				// Sessions do not outlive their connection yet, so there is
+				// nothing to resume.
+				log::debug!(
+					"Session {} cannot resume session {} at {}",
+					self.session_id,
+					resume.session_id,
+					resume.seq
+				);
+
+				let event =
+					Event::InvalidSession(InvalidSession { resumable: false });
+				self.send(ctx, &event, None);
+			}
+			GatewayCommand::PresenceUpdate(presence) => {
+				if presence.status == Status::Offline {
+					anyhow::bail!("Cannot set the status to offline");
+				}
+
+				self.addr.do_send(server::UpdatePresence {
+					user_id: self.id,
+					status: presence.status,
+					custom_status: presence.custom_status,
+				});
+			}
+			GatewayCommand::RequestGuildMembers(request) => {
+				self.addr.do_send(server::RequestGuildMembers {
+					id: self.session_id,
+					request,
+				});
+			}
+			GatewayCommand::MemberListSubscribe(subscribe) => {
+				self.addr.do_send(server::SubscribeMemberList {
+					id: self.session_id,
+					guild_id: subscribe.guild_id,
+				});
+			}
+			GatewayCommand::VoiceStateUpdate(voice) => {
+				self.addr.do_send(server::UpdateVoiceState {
+					user_id: self.id,
+					channel_id: voice.channel_id,
+					self_mute: voice.self_mute,
+					self_deaf: voice.self_deaf,
+				});
+			}
+			GatewayCommand::TypingStart(typing) => {
+				self.addr.do_send(server::StartTyping {
+					user_id: self.id,
+					channel_id: typing.channel_id,
+				});
+			}
 		}
 
 		Ok(())
 	}
+
+	fn send(
+		&mut self, ctx: &mut ws::WebsocketContext<Self>, event: &Event,
+		seq: Option<u64>,
+	) {
+		let data = match self.encoding.encode(&event.payload(seq, self.version))
+		{
+			Ok(data) => data,
+			Err(e) => {
+				log::error!("Failed to encode payload: {}", e);
+				return self.close(ctx, CloseCode::UnknownError);
+			}
+		};
+
+		let compressor = match self.compressor.as_mut() {
+			Some(compressor) => compressor,
+			None if self.encoding.is_binary() => return ctx.binary(data),
+			// JSON is always valid UTF-8
+			None => return ctx.text(String::from_utf8(data).unwrap()),
+		};
+
+		match compressor.compress(&data) {
+			Ok(compressed) => {
+				self.compression_stats.record(data.len(), compressed.len());
+				ctx.binary(compressed);
+			}
+			Err(e) => {
+				log::error!("Failed to compress payload: {}", e);
+				self.close(ctx, CloseCode::UnknownError);
+			}
+		}
+	}
 }
 
 impl Actor for GatewaySession {
@@ -133,6 +351,17 @@ impl Actor for GatewaySession {
 	}
 
 	fn stopping(&mut self, _: &mut Self::Context) -> Running {
+		if self.compressor.is_some() {
+			log::debug!(
+				"Session {} compressed {} payloads from {} to {} bytes ({:.1}%)",
+				self.session_id,
+				self.compression_stats.payloads,
+				self.compression_stats.bytes_in,
+				self.compression_stats.bytes_out,
+				self.compression_stats.ratio() * 100.0
+			);
+		}
+
 		// notify chat server
 		self.addr.do_send(server::Disconnect { id: self.session_id });
 		Running::Stop
@@ -146,36 +375,41 @@ impl Handler<Event> for GatewaySession {
 	fn handle(&mut self, msg: Event, ctx: &mut Self::Context) {
 		log::debug!("session {} received event {:?}", self.session_id, msg);
 
-		match msg {
-			Event::Custom(msg) => ctx.text(msg),
+		if !self.intents.allows(&msg) {
+			return;
+		}
 
-			Event::BadToken => {
-				ctx.close(Some(ws::CloseReason {
-					code: ws::CloseCode::Other(4000),
-					description: None,
-				}));
-			}
+		match msg {
+			Event::Close(code) => self.close(ctx, code),
 
-			Event::Hello => {
-				log::debug!("{} connected", self.session_id);
+			Event::Hello(_)
+			| Event::HeartbeatAck(_)
+			| Event::InvalidSession(_) => {
+				self.send(ctx, &msg, None);
 			}
 
-			Event::SetToken(token) => {
+			Event::Authenticated(authenticated) => {
 				log::debug!(
-					"{} has authenticated, setting token",
-					self.session_id
+					"{} has authenticated as {}",
+					self.session_id,
+					authenticated.user_id
 				);
-				self.token = Some(token);
+				self.id = authenticated.user_id;
+				self.token = Some(authenticated.token);
 			}
 
-			ref other => {
-				let opcode = other.opcode();
-				let json = serde_json::json!({
-					"op": opcode,
-					"d": serde_json::to_value(other).unwrap(),
-				});
+			Event::Ready(mut ready) => {
+				if !self.intents.contains(Intents::PRESENCES) {
+					ready.presences.clear();
+				}
 
-				ctx.text(serde_json::to_string(&json).unwrap());
+				self.seq += 1;
+				self.send(ctx, &Event::Ready(ready), Some(self.seq));
+			}
+
+			ref other => {
+				self.seq += 1;
+				self.send(ctx, other, Some(self.seq));
 			}
 		}
 	}
@@ -188,6 +422,9 @@ impl StreamHandler<Result<Message, ws::ProtocolError>> for GatewaySession {
 		ctx: &mut Self::Context,
 	) {
 		let msg = match msg {
+			Err(ws::ProtocolError::Overflow) => {
+				return self.close(ctx, CloseCode::PayloadTooLarge);
+			}
 			Err(_) => {
 				ctx.stop();
 				return;
@@ -203,16 +440,12 @@ impl StreamHandler<Result<Message, ws::ProtocolError>> for GatewaySession {
 			Message::Pong(_) => {
 				self.hb = Instant::now();
 			}
-			Message::Text(text) => match self.handle_json(&text, ctx) {
-				Ok(_) => (),
-				Err(e) => {
-					ctx.text(e.to_string());
-					ctx.stop();
-				}
-			},
+			Message::Text(text) => {
+				self.handle_payload(text.as_bytes(), false, ctx)
+			}
 			Message::Binary(b) => {
 				log::debug!("Received len {} bytes", b.len());
-				ctx.binary(b);
+				self.handle_payload(&b, true, ctx);
 			}
 			Message::Close(reason) => {
 				ctx.close(reason);
@@ -0,0 +1,430 @@
+use crate::{
+	version::ApiVersion,
+	ws::{
+		close_code::{CloseCode, CloseCodeInfo},
+		commands::GatewayCommand,
+		events::Event,
+		intents::{AllowsEvent, Intents},
+		server,
+		session::HEARTBEAT_INTERVAL,
+	},
+};
+use actix::prelude::*;
+use actix_web::web::Bytes;
+use futures::channel::{mpsc, oneshot};
+use serde::Serialize;
+use std::{
+	collections::{HashMap, VecDeque},
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+/// How many dispatched payloads are kept for clients to resume from
+const BUFFER_SIZE: usize = 256;
+
+/// How long a stream nobody listens to is kept for the client to resume it
+const STREAM_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long a long-poll request waits for events before returning none
+const POLL_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// Event streams that are open, by session ID.
+pub type Streams = Mutex<HashMap<usize, StreamHandle>>;
+
+/// An open event stream, and the user it belongs to.
+#[derive(Clone)]
+pub struct StreamHandle {
+	pub user_id: i64,
+	pub addr: Addr<EventStream>,
+}
+
+/// Formats a payload as a Server-Sent Event. The ID of dispatches is
+/// `<session_id>:<seq>`, so clients can resume with `Last-Event-ID`.
+pub fn sse_event(
+	session_id: usize, seq: Option<u64>, payload: &serde_json::Value,
+) -> Bytes {
+	match seq {
+		Some(seq) => {
+			format!("id: {}:{}\ndata: {}\n\n", session_id, seq, payload)
+		}
+		None => format!("data: {}\n\n", payload),
+	}
+	.into()
+}
+
+/// Whoever receives the events of a stream at the moment.
+enum Consumer {
+	/// An open Server-Sent Events response
+	Sse(mpsc::UnboundedSender<Bytes>),
+	/// A long-poll request waiting for events
+	Poll(oneshot::Sender<Vec<serde_json::Value>>),
+}
+
+/// Session for clients that cannot use WebSockets. Events are dispatched the
+/// same way as to a `GatewaySession`, and are sent over Server-Sent Events or
+/// long-polling. The session outlives the requests reading from it for
+/// STREAM_TIMEOUT, so clients can resume it.
+pub struct EventStream {
+	/// unique session id
+	pub session_id: usize,
+	/// Chat server
+	pub addr: Addr<server::ShikiServer>,
+	/// Streams that are open, the session registers itself in it
+	pub streams: Arc<Streams>,
+	/// ID of the user the stream belongs to
+	pub user_id: i64,
+	/// Auth token
+	pub token: String,
+	/// Events the client subscribed to
+	pub intents: Intents,
+	/// Version of the gateway, which selects the shape of the payloads
+	pub version: ApiVersion,
+	/// Sequence number of the last event dispatched to the client
+	pub seq: u64,
+	/// The last BUFFER_SIZE payloads dispatched, with their sequence number
+	buffer: VecDeque<(u64, serde_json::Value)>,
+	consumer: Option<Consumer>,
+	/// When the last consumer went away
+	detached_at: Instant,
+}
+
+impl EventStream {
+	pub fn new(
+		addr: Addr<server::ShikiServer>, streams: Arc<Streams>, user_id: i64,
+		token: String, intents: Intents, version: ApiVersion,
+	) -> Self {
+		EventStream {
+			session_id: 0,
+			addr,
+			streams,
+			user_id,
+			token,
+			intents,
+			version,
+			seq: 0,
+			buffer: VecDeque::with_capacity(BUFFER_SIZE),
+			consumer: None,
+			detached_at: Instant::now(),
+		}
+	}
+
+	/// Sends keep-alives to Server-Sent Events consumers, notices consumers
+	/// that went away, and stops the stream once nobody resumed it for
+	/// STREAM_TIMEOUT.
+	fn check_consumer(&self, ctx: &mut Context<Self>) {
+		ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+			let alive = match &act.consumer {
+				Some(Consumer::Sse(tx)) => {
+					tx.unbounded_send(Bytes::from_static(b":\n\n")).is_ok()
+				}
+				Some(Consumer::Poll(tx)) => !tx.is_canceled(),
+				None => false,
+			};
+
+			if alive {
+				return;
+			}
+
+			if act.consumer.take().is_some() {
+				act.detached_at = Instant::now();
+			}
+
+			if act.detached_at.elapsed() > STREAM_TIMEOUT {
+				log::debug!("Event stream {} timed out", act.session_id);
+				ctx.stop();
+			}
+		});
+	}
+
+	/// Payloads dispatched after `after`, or None if some of them are not
+	/// buffered anymore and the stream cannot be resumed from there.
+	fn replay(&self, after: u64) -> Option<Vec<(u64, serde_json::Value)>> {
+		let oldest = self.buffer.front().map_or(self.seq + 1, |(seq, _)| *seq);
+
+		if after > self.seq || after + 1 < oldest {
+			return None;
+		}
+
+		Some(
+			self.buffer
+				.iter()
+				.filter(|(seq, _)| *seq > after)
+				.cloned()
+				.collect(),
+		)
+	}
+
+	fn dispatch(&mut self, event: &Event) {
+		self.seq += 1;
+
+		let payload = match serde_json::to_value(
+			event.payload(Some(self.seq), self.version),
+		) {
+			Ok(payload) => payload,
+			Err(e) => {
+				log::error!("Failed to encode payload: {}", e);
+				return;
+			}
+		};
+
+		if self.buffer.len() == BUFFER_SIZE {
+			self.buffer.pop_front();
+		}
+
+		self.buffer.push_back((self.seq, payload.clone()));
+
+		match self.consumer.take() {
+			Some(Consumer::Sse(tx)) => {
+				let event =
+					sse_event(self.session_id, Some(self.seq), &payload);
+
+				if tx.unbounded_send(event).is_ok() {
+					self.consumer = Some(Consumer::Sse(tx));
+				} else {
+					self.detached_at = Instant::now();
+				}
+			}
+			Some(Consumer::Poll(tx)) => {
+				// The payload stays buffered if the request went away.
+				let _ = tx.send(vec![payload]);
+				self.detached_at = Instant::now();
+			}
+			None => (),
+		}
+	}
+
+	fn close(&mut self, ctx: &mut Context<Self>, code: CloseCode) {
+		log::debug!("Closing event stream {} with {:?}", self.session_id, code);
+
+		if let Some(Consumer::Sse(tx)) = self.consumer.take() {
+			let info = serde_json::to_string(&CloseCodeInfo::from(code))
+				.unwrap_or_default();
+			let _ = tx.unbounded_send(
+				format!("event: close\ndata: {}\n\n", info).into(),
+			);
+		}
+
+		ctx.stop();
+	}
+}
+
+impl Actor for EventStream {
+	type Context = Context<Self>;
+
+	fn started(&mut self, ctx: &mut Self::Context) {
+		self.check_consumer(ctx);
+
+		// Register with the chat server before handling any other message, so
+		// the session ID is known to whoever subscribes first.
+		self.addr
+			.send(server::Connect { addr: ctx.address().recipient() })
+			.into_actor(self)
+			.then(|res, act, ctx| {
+				match res {
+					Ok(id) => {
+						act.session_id = id;
+						act.streams.lock().unwrap().insert(
+							id,
+							StreamHandle {
+								user_id: act.user_id,
+								addr: ctx.address(),
+							},
+						);
+						act.addr.do_send(server::Identify {
+							id,
+							token: act.token.clone(),
+							intents: act.intents,
+						});
+					}
+					// something is wrong with chat server
+					_ => ctx.stop(),
+				}
+				fut::ready(())
+			})
+			.wait(ctx);
+	}
+
+	fn stopping(&mut self, _: &mut Self::Context) -> Running {
+		self.streams.lock().unwrap().remove(&self.session_id);
+
+		// notify chat server
+		self.addr.do_send(server::Disconnect { id: self.session_id });
+		Running::Stop
+	}
+}
+
+impl Handler<Event> for EventStream {
+	type Result = ();
+
+	fn handle(&mut self, msg: Event, ctx: &mut Self::Context) {
+		log::debug!("stream {} received event {:?}", self.session_id, msg);
+
+		if !self.intents.allows(&msg) {
+			return;
+		}
+
+		match msg {
+			// Keep-alives replace heartbeats, and the stream was
+			// authenticated when it was opened.
+			Event::Hello(_)
+			| Event::HeartbeatAck(_)
+			| Event::Authenticated(_) => (),
+
+			Event::Close(code) => self.close(ctx, code),
+
+			Event::Ready(mut ready) => {
+				if !self.intents.contains(Intents::PRESENCES) {
+					ready.presences.clear();
+				}
+
+				self.dispatch(&Event::Ready(ready));
+			}
+
+			ref other => self.dispatch(other),
+		}
+	}
+}
+
+/// Attaches a Server-Sent Events response to the stream, replacing the one
+/// attached before. Payloads dispatched after `after` are sent first. Returns
+/// None if the stream cannot be resumed from `after`.
+#[derive(Message)]
+#[rtype(result = "Option<mpsc::UnboundedReceiver<Bytes>>")]
+pub struct Subscribe {
+	pub after: u64,
+}
+
+impl Handler<Subscribe> for EventStream {
+	type Result = Option<mpsc::UnboundedReceiver<Bytes>>;
+
+	fn handle(
+		&mut self, msg: Subscribe, _: &mut Self::Context,
+	) -> Self::Result {
+		// Every request counts as the client still being there, even those
+		// which do not stay attached.
+		self.detached_at = Instant::now();
+
+		let replay = self.replay(msg.after)?;
+		let (tx, rx) = mpsc::unbounded();
+
+		for (seq, payload) in &replay {
+			let _ = tx.unbounded_send(sse_event(
+				self.session_id,
+				Some(*seq),
+				payload,
+			));
+		}
+
+		self.consumer = Some(Consumer::Sse(tx));
+
+		Some(rx)
+	}
+}
+
+/// Events returned to a long-poll request.
+#[derive(Serialize, Debug)]
+pub struct Polled {
+	/// The session to poll next
+	pub session_id: usize,
+	/// Payloads dispatched since the last poll
+	pub events: Vec<serde_json::Value>,
+}
+
+/// Waits up to POLL_TIMEOUT for payloads dispatched after `after`. Returns
+/// None if the stream cannot be resumed from `after`.
+#[derive(Message)]
+#[rtype(result = "Option<Polled>")]
+pub struct Poll {
+	pub after: u64,
+}
+
+impl Handler<Poll> for EventStream {
+	type Result = ResponseFuture<Option<Polled>>;
+
+	fn handle(&mut self, msg: Poll, _: &mut Self::Context) -> Self::Result {
+		// Polls answered straight from the buffer never attach a consumer,
+		// so the stream would otherwise time out on a busy session.
+		self.detached_at = Instant::now();
+
+		let session_id = self.session_id;
+		let replay = match self.replay(msg.after) {
+			Some(replay) => replay,
+			None => return Box::pin(async { None }),
+		};
+
+		if !replay.is_empty() {
+			let events =
+				replay.into_iter().map(|(_, payload)| payload).collect();
+			return Box::pin(
+				async move { Some(Polled { session_id, events }) },
+			);
+		}
+
+		// A request polling already gets an empty response.
+		let (tx, rx) = oneshot::channel();
+		self.consumer = Some(Consumer::Poll(tx));
+
+		Box::pin(async move {
+			let events = match tokio::time::timeout(POLL_TIMEOUT, rx).await {
+				Ok(Ok(events)) => events,
+				_ => Vec::new(),
+			};
+
+			Some(Polled { session_id, events })
+		})
+	}
+}
+
+/// Command a client sent over REST for its stream.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Command(pub GatewayCommand);
+
+impl Handler<Command> for EventStream {
+	type Result = ();
+
+	fn handle(&mut self, msg: Command, _: &mut Self::Context) {
+		log::debug!("Stream {} received command: {:?}", self.session_id, msg.0);
+
+		match msg.0 {
+			GatewayCommand::PresenceUpdate(presence) => {
+				self.addr.do_send(server::UpdatePresence {
+					user_id: self.user_id,
+					status: presence.status,
+					custom_status: presence.custom_status,
+				});
+			}
+			GatewayCommand::RequestGuildMembers(request) => {
+				self.addr.do_send(server::RequestGuildMembers {
+					id: self.session_id,
+					request,
+				});
+			}
+			GatewayCommand::MemberListSubscribe(subscribe) => {
+				self.addr.do_send(server::SubscribeMemberList {
+					id: self.session_id,
+					guild_id: subscribe.guild_id,
+				});
+			}
+			GatewayCommand::VoiceStateUpdate(voice) => {
+				self.addr.do_send(server::UpdateVoiceState {
+					user_id: self.user_id,
+					channel_id: voice.channel_id,
+					self_mute: voice.self_mute,
+					self_deaf: voice.self_deaf,
+				});
+			}
+			GatewayCommand::TypingStart(typing) => {
+				self.addr.do_send(server::StartTyping {
+					user_id: self.user_id,
+					channel_id: typing.channel_id,
+				});
+			}
+			// Streams are authenticated when they are opened, and keep-alives
+			// replace heartbeats.
+			GatewayCommand::Identify(_)
+			| GatewayCommand::Heartbeat(_)
+			| GatewayCommand::Resume(_) => (),
+		}
+	}
+}